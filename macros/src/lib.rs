@@ -7,9 +7,9 @@ use quote::{quote, ToTokens};
 use syn::parse::{Parse, ParseStream, Result};
 use syn::punctuated::Punctuated;
 use syn::{
-    braced, parenthesized, parse_macro_input, token, GenericArgument, GenericParam, Generics,
-    Ident, Lifetime, Path, PathArguments, PathSegment, ReturnType, Token, TypeParamBound,
-    TypeTraitObject, WherePredicate,
+    braced, parenthesized, parse_macro_input, token, Attribute, GenericArgument, GenericParam,
+    Generics, Ident, Lifetime, Path, PathArguments, PathSegment, ReturnType, Token, TypeParamBound,
+    TypeTraitObject, Visibility, WherePredicate,
 };
 
 use self::proc_macro::TokenStream;
@@ -53,12 +53,23 @@ struct Function {
     receiver: Receiver,
     args: Vec<Type>,
     ret: Option<Type>,
+    is_async: bool,
 }
 
 struct ItemMacro {
     name: Ident,
 }
 
+/// The function under `#[reflect::derive_macro(...)]`, of which only the name is
+/// needed
+struct DeriveFn {
+    attrs: Vec<Attribute>,
+    vis: Visibility,
+    name: Ident,
+    /// The signature after the name, and the body
+    rest: TokenStream2,
+}
+
 enum Receiver {
     None,
     ByValue,
@@ -114,7 +125,7 @@ impl Item {
     fn parse(input: ParseStream, mod_path: &Path) -> Result<Self> {
         let lookahead = input.lookahead1();
         if lookahead.peek(Token![mod]) {
-            ItemMod::parse(&input, mod_path).map(Item::Mod)
+            ItemMod::parse(input, mod_path).map(Item::Mod)
         } else if lookahead.peek(Token![type]) {
             input.parse().map(Item::Type)
         } else if lookahead.peek(Token![impl]) {
@@ -229,6 +240,7 @@ impl Parse for ItemTrait {
 
 impl Parse for Function {
     fn parse(input: ParseStream) -> Result<Self> {
+        let asyncness: Option<Token![async]> = input.parse()?;
         input.parse::<Token![fn]>()?;
         let name: Ident = input.parse()?;
 
@@ -267,6 +279,7 @@ impl Parse for Function {
         input.parse::<Token![;]>()?;
 
         Ok(Function {
+            is_async: asyncness.is_some(),
             name,
             generics,
             receiver,
@@ -331,6 +344,22 @@ impl Parse for Type {
     }
 }
 
+impl Parse for DeriveFn {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let vis: Visibility = input.parse()?;
+        input.parse::<Token![fn]>()?;
+        let name: Ident = input.parse()?;
+        let rest: TokenStream2 = input.parse()?;
+        Ok(DeriveFn {
+            attrs,
+            vis,
+            name,
+            rest,
+        })
+    }
+}
+
 impl Parse for ItemMacro {
     fn parse(input: ParseStream) -> Result<Self> {
         input.parse::<Token![macro]>()?;
@@ -375,6 +404,46 @@ pub fn library(input: TokenStream) -> TokenStream {
     })
 }
 
+/// Turns `fn name(ex: Execution)` into the `proc_macro_derive` entry point
+/// `name`, with the arguments of the attribute passed on to
+/// `proc_macro_derive`.
+///
+/// ```
+/// # macro_rules! ignore {
+/// #     ($($tt:tt)*) => {};
+/// # }
+/// # ignore! {
+/// #[reflect::derive_macro(MyDebug)]
+/// fn derive(ex: reflect::Execution) {
+///     ex.make_trait_impl(RUNTIME::std::fmt::Debug, ex.target_type(), |block| {
+///         block.make_function(RUNTIME::std::fmt::Debug::fmt, debug_fmt);
+///     });
+/// }
+/// # }
+/// ```
+#[proc_macro_attribute]
+pub fn derive_macro(args: TokenStream, input: TokenStream) -> TokenStream {
+    let args = TokenStream2::from(args);
+    let DeriveFn {
+        attrs,
+        vis,
+        name,
+        rest,
+    } = parse_macro_input!(input as DeriveFn);
+
+    TokenStream::from(quote! {
+        #(#attrs)*
+        #[proc_macro_derive(#args)]
+        pub fn #name(input: ::proc_macro::TokenStream) -> ::proc_macro::TokenStream {
+            extern crate reflect as _reflect;
+
+            #vis fn #name #rest
+
+            _reflect::derive(input, #name)
+        }
+    })
+}
+
 fn declare_mod(module: &ItemMod) -> TokenStream2 {
     let path = &module.path;
     let name = path.segments.last();
@@ -437,7 +506,7 @@ fn declare_parent(
         });
 
         Some(quote! {
-            parent_builder.set_generic_params(&[#(#param_strings),*]);
+            parent_builder.set_generic_params(&[#(#param_strings),*]).unwrap();
         })
     } else {
         None
@@ -450,7 +519,7 @@ fn declare_parent(
         });
 
         Some(quote! {
-            parent_builder.set_generic_constraints(&[#(#constraint_strings),*]);
+            parent_builder.set_generic_constraints(&[#(#constraint_strings),*]).unwrap();
         })
     } else {
         None
@@ -573,6 +642,13 @@ fn declare_function(
             sig.set_self_by_reference_mut();
         }),
     };
+    let set_async = if function.is_async {
+        Some(quote! {
+            sig.set_async();
+        })
+    } else {
+        None
+    };
     let params: &Vec<_> = &function
         .generics
         .params
@@ -588,7 +664,7 @@ fn declare_function(
         });
 
         Some(quote! {
-            sig.set_generic_params(&[#(#param_strings),*]);
+            sig.set_generic_params(&[#(#param_strings),*]).unwrap();
         })
     } else {
         None
@@ -609,7 +685,7 @@ fn declare_function(
         });
 
         Some(quote! {
-            sig.set_generic_constraints(&[#(#constraint_strings),*]);
+            sig.set_generic_constraints(&[#(#constraint_strings),*]).unwrap();
         })
     } else {
         None
@@ -620,7 +696,7 @@ fn declare_function(
         quote!(sig.add_input(|param_map: &mut _reflect::SynParamMap| {#ty});)
     });
     let set_output = function.ret.as_ref().map(|ty| {
-        let ty = to_runtime_type(&ty, mod_path, params);
+        let ty = to_runtime_type(ty, mod_path, params);
         quote!(sig.set_output(|param_map: &mut _reflect::SynParamMap| {#ty});)
     });
 
@@ -630,7 +706,7 @@ fn declare_function(
 
     quote! {
         impl __Indirect<#parent> {
-            #[allow(dead_code)]
+            #[allow(dead_code, non_local_definitions)]
             fn #name() {
                 #[allow(non_camel_case_types)]
                 #[derive(Copy, Clone)]
@@ -646,6 +722,7 @@ fn declare_function(
                                 #add_parent_params
                                 #set_sig_constraints
                                 #setup_receiver
+                                #set_async
                                 #(
                                     #setup_inputs
                                 )*
@@ -698,6 +775,14 @@ fn declare_macro(item: &ItemMacro) -> TokenStream2 {
             ) -> _reflect::Value {
                 MODULE().invoke_macro(#macro_name, values)
             }
+
+            pub fn INVOKE_TYPED(
+                self,
+                values: &[_reflect::Value],
+                output: _reflect::TypeNode,
+            ) -> _reflect::Value {
+                MODULE().invoke_macro_typed(#macro_name, values, output)
+            }
         }
     }
 }
@@ -716,7 +801,7 @@ fn to_runtime_type(ty: &Type, mod_path: &Path, params: &[&GenericParam]) -> Toke
                 if ident_is_param(ident, params) {
                     let type_param = ident.to_string();
                     return quote! {
-                        _reflect::TypeNode::new_type_param_from_str(#type_param, param_map)
+                        _reflect::TypeNode::new_type_param_from_str(#type_param, param_map).unwrap()
                     };
                 }
             }
@@ -732,7 +817,7 @@ fn to_runtime_type(ty: &Type, mod_path: &Path, params: &[&GenericParam]) -> Toke
                 .map(|bound| bound.to_token_stream().to_string());
 
             quote! {
-                _reflect::TypeNode::new_trait_object(&[#(#bound_strings),*], param_map)
+                _reflect::TypeNode::new_dyn_trait(&[#(#bound_strings),*], param_map).unwrap()
             }
         }
 
@@ -745,7 +830,7 @@ fn to_runtime_type(ty: &Type, mod_path: &Path, params: &[&GenericParam]) -> Toke
             if let Some(lifetime) = lifetime {
                 let lifetime_str = lifetime.to_string();
                 quote! {
-                    #inner.new_reference_with_lifetime(#lifetime_str, param_map)
+                    #inner.new_reference_with_lifetime(#lifetime_str, param_map).unwrap()
                 }
             } else {
                 quote! {
@@ -760,7 +845,7 @@ fn to_runtime_type(ty: &Type, mod_path: &Path, params: &[&GenericParam]) -> Toke
             if let Some(lifetime) = lifetime {
                 let lifetime_str = lifetime.to_string();
                 quote! {
-                    #inner.new_reference_mut_with_lifetime(#lifetime_str, param_map)
+                    #inner.new_reference_mut_with_lifetime(#lifetime_str, param_map).unwrap()
                 }
             } else {
                 quote! {
@@ -800,12 +885,12 @@ fn to_runtime_path(path: &Path, mod_path: &Path, params: &[&GenericParam]) -> To
     if path.segments.len() == 1 && path.leading_colon.is_none() {
         let segment = path.segments[0].to_token_stream().to_string();
         quote! {
-            MODULE().get_path(#segment, param_map)
+            MODULE().get_path(#segment, param_map).unwrap()
         }
     } else {
         let path_str = path.to_token_stream().to_string();
         quote! {
-            _reflect::Path::path_from_str(#path_str, param_map)
+            _reflect::Path::path_from_str(#path_str, param_map).unwrap()
         }
     }
 }
@@ -1,9 +1,58 @@
+//! Helpers for reading the attributes of data structures, fields and
+//! variants.
+
 use ref_cast::RefCast;
 use std::fmt::{self, Debug, Display};
-use syn::{AttrStyle, Attribute};
+use syn::{AttrStyle, Attribute, Meta, NestedMeta};
+
+/// The first attribute whose path is `path`, such as `"serde"` or
+/// `"rustfmt::skip"`
+pub fn find<'a>(attrs: &'a [Attribute], path: &str) -> Option<&'a Attribute> {
+    attrs.iter().find(|attr| has_path(attr, path))
+}
+
+/// Every attribute whose path is `path`, in order
+pub fn filter<'a>(attrs: &'a [Attribute], path: &str) -> Vec<&'a Attribute> {
+    attrs.iter().filter(|attr| has_path(attr, path)).collect()
+}
+
+pub fn has(attrs: &[Attribute], path: &str) -> bool {
+    find(attrs, path).is_some()
+}
+
+/// Parses the arguments of the attribute, as in `#[serde(rename = "x")]`
+pub fn parse_meta(attr: &Attribute) -> Result<syn::Meta, syn::Error> {
+    attr.parse_meta()
+}
+
+/// Whether an attribute whose path is `path` lists the word `word` among its
+/// arguments, as `#[serde(skip)]` does for `has_word(attrs, "serde", "skip")`.
+/// Attributes whose arguments do not parse as a list are ignored.
+pub fn has_word(attrs: &[Attribute], path: &str, word: &str) -> bool {
+    filter(attrs, path)
+        .into_iter()
+        .any(|attr| match parse_meta(attr) {
+            Ok(Meta::List(list)) => list.nested.iter().any(|nested| match nested {
+                NestedMeta::Meta(Meta::Path(meta_path)) => meta_path.is_ident(word),
+                _ => false,
+            }),
+            _ => false,
+        })
+}
+
+fn has_path(attr: &Attribute, path: &str) -> bool {
+    let mut segments = path.split("::");
+    attr.path.leading_colon.is_none()
+        && attr
+            .path
+            .segments
+            .iter()
+            .all(|segment| segments.next().is_some_and(|next| segment.ident == next))
+        && segments.next().is_none()
+}
 
 #[allow(clippy::ptr_arg)]
-pub fn debug(attrs: &Vec<Attribute>) -> &impl Debug {
+pub(crate) fn debug(attrs: &Vec<Attribute>) -> &impl Debug {
     Wrapper::ref_cast(attrs)
 }
 
@@ -42,6 +91,43 @@ impl Debug for Wrapper<Attribute> {
     }
 }
 
+#[test]
+fn test_find() {
+    use syn::parse_quote;
+
+    let attrs: Vec<Attribute> = vec![
+        parse_quote!(#[serde(rename = "x")]),
+        parse_quote!(#[doc = "first"]),
+        parse_quote!(#[rustfmt::skip]),
+        parse_quote!(#[doc = "second"]),
+    ];
+
+    assert!(has(&attrs, "serde"));
+    assert!(has(&attrs, "rustfmt::skip"));
+    assert!(!has(&attrs, "rustfmt"));
+    assert!(!has(&attrs, "skip"));
+    assert_eq!(filter(&attrs, "doc").len(), 2);
+
+    let doc = parse_meta(find(&attrs, "doc").unwrap()).unwrap();
+    assert_eq!(doc, parse_quote!(doc = "first"));
+}
+
+#[test]
+fn test_has_word() {
+    use syn::parse_quote;
+
+    let attrs: Vec<Attribute> = vec![
+        parse_quote!(#[serde(rename = "x", skip)]),
+        parse_quote!(#[debug(skip = "always")]),
+        parse_quote!(#[doc = "skip"]),
+    ];
+
+    assert!(has_word(&attrs, "serde", "skip"));
+    assert!(!has_word(&attrs, "serde", "rename"));
+    assert!(!has_word(&attrs, "debug", "skip"));
+    assert!(!has_word(&attrs, "doc", "skip"));
+}
+
 #[test]
 fn test_debug() {
     use syn::parse_quote;
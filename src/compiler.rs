@@ -1,9 +1,10 @@
 use crate::ident::Ident;
 use crate::{
-    Function, GlobalBorrow, InvokeRef, MacroInvokeRef, Parent, Print, Receiver, SimplePath,
-    TraitInferenceResult, TypeNode, ValueNode, ValueRef, INVOKES, MACROS, VALUES,
+    Function, InvokeRef, MacroInvokeRef, Parent, PathSegment, Print, Receiver, SimplePath,
+    TraitInferenceResult, TypeNode, ValueNode, ValueRef, VariantPayload, WhereClause, INVOKES,
+    MACROS, VALUES,
 };
-use proc_macro2::{Span, TokenStream};
+use proc_macro2::{Literal, Span, TokenStream};
 use quote::{quote, ToTokens};
 use ref_cast::RefCast;
 use std::collections::BTreeSet as Set;
@@ -35,6 +36,14 @@ pub(crate) struct CompleteFunction {
     pub ret: Option<ValueRef>,
 }
 
+/// The values of a function that are bound by a let, and those of them that
+/// are bound mutably. Computed once for the whole function, closure bodies
+/// included.
+struct Bindings {
+    reachable: Set<ValueRef>,
+    mutable: Set<ValueRef>,
+}
+
 impl Program {
     pub fn compile(&self) -> TokenStream {
         let impls = self.impls.iter().map(CompleteImpl::compile);
@@ -109,7 +118,7 @@ impl CompleteImpl {
 
 impl CompleteFunction {
     fn compile(&self) -> TokenStream {
-        let name = Ident::new(&self.f.name);
+        let name = &self.f.name;
 
         let generics = &self.f.sig.generics;
         let (params, where_clause) = if generics.params.is_empty() {
@@ -117,13 +126,8 @@ impl CompleteFunction {
         } else {
             let params = generics.params.iter().map(Print::ref_cast);
             let params = Some(quote!(<#(#params),*>));
-            let where_clause = if generics.constraints.is_empty() {
-                None
-            } else {
-                let constraints = generics.constraints.iter().map(Print::ref_cast);
-                Some(quote!(where #(#constraints,)*))
-            };
-            (params, where_clause)
+            let where_clause = Print::ref_cast(WhereClause::ref_cast(generics));
+            (params, Some(where_clause))
         };
 
         let mut inputs = Vec::new();
@@ -144,47 +148,95 @@ impl CompleteFunction {
             }
         };
 
-        let reachable = self.compute_reachability();
-        let mutable = self.compute_mutability();
-        VALUES.with_borrow(|value_nodes| {
-            let values = self.refs().flat_map(|v| {
-                // Don't create let bindings for inlineable values
-                if value_nodes[v.0].inlineable() {
-                    return None;
-                }
+        let bindings = &Bindings {
+            reachable: self.compute_reachability(),
+            mutable: self.compute_mutability(),
+        };
+        let values = self.compile_statements(self.values.clone(), bindings);
+        let ret = self.ret.and_then(|v| self.compile_ret(v, bindings));
 
-                let expr = self.compile_value(v);
-                if reachable.contains(&v) {
-                    let let_mut = if mutable.contains(&v) {
-                        quote!(let mut)
-                    } else {
-                        quote!(let)
-                    };
-                    let binding = v.binding();
-                    Some(quote! {
-                        #let_mut #binding = #expr;
-                    })
-                } else if self.is_important(v) {
-                    Some(quote! {
-                        let _ = #expr;
-                    })
-                } else {
-                    None
-                }
-            });
+        let asyncness = if self.f.sig.is_async {
+            Some(quote!(async))
+        } else {
+            None
+        };
+        let unsafety = if self.f.sig.is_unsafe {
+            Some(quote!(unsafe))
+        } else {
+            None
+        };
+        let attrs = &self.f.attrs;
 
-            let ret = self.ret.and_then(|v| match &value_nodes[v.0] {
-                ValueNode::Tuple(values) if values.is_empty() => None,
-                value if value.inlineable() => Some(self.compile_value(v)),
-                _ => Some(v.binding().to_token_stream()),
-            });
+        quote! {
+            #(#attrs)*
+            #asyncness #unsafety fn #name #params (#(#inputs),*) #output #where_clause {
+                #(#values)*
+                #ret
+            }
+        }
+    }
 
-            quote! {
-                fn #name #params (#(#inputs),*) #output #where_clause {
-                    #(#values)*
-                    #ret
+    /// The statements of the values in `refs`. The values in the body of a
+    /// closure or of a match arm are left out, as they are emitted inside
+    /// it.
+    fn compile_statements(&self, refs: Range<ValueRef>, bindings: &Bindings) -> Vec<TokenStream> {
+        VALUES.with_borrow(|value_nodes| {
+            let refs = (refs.start.0..refs.end.0).map(ValueRef);
+            let mut in_closure = Set::new();
+            for v in refs.clone() {
+                match &value_nodes[v.0] {
+                    ValueNode::Closure { body, .. } => {
+                        in_closure.extend((body.start.0..body.end.0).map(ValueRef));
+                    }
+                    ValueNode::Match { arms, .. } => {
+                        for arm in arms {
+                            in_closure.extend((arm.body.start.0..arm.body.end.0).map(ValueRef));
+                        }
+                    }
+                    _ => {}
                 }
             }
+
+            refs.filter(|v| !in_closure.contains(v))
+                .flat_map(|v| {
+                    // Don't create let bindings for inlineable values
+                    if value_nodes[v.0].inlineable() {
+                        return None;
+                    }
+
+                    let expr = self.compile_value(v, bindings);
+                    if value_nodes[v.0].is_statement() {
+                        return Some(expr);
+                    }
+                    if bindings.reachable.contains(&v) {
+                        let let_mut = if bindings.mutable.contains(&v) {
+                            quote!(let mut)
+                        } else {
+                            quote!(let)
+                        };
+                        let binding = v.binding();
+                        Some(quote! {
+                            #let_mut #binding = #expr;
+                        })
+                    } else if self.is_important(v) {
+                        Some(quote! {
+                            let _ = #expr;
+                        })
+                    } else {
+                        None
+                    }
+                })
+                .collect()
+        })
+    }
+
+    /// The value at the end of a block, or nothing if it is `()`
+    fn compile_ret(&self, v: ValueRef, bindings: &Bindings) -> Option<TokenStream> {
+        VALUES.with_borrow(|value_nodes| match &value_nodes[v.0] {
+            ValueNode::Tuple(values) if values.is_empty() => None,
+            value if value.is_statement() => None,
+            value if value.inlineable() => Some(self.compile_value(v, bindings)),
+            _ => Some(v.binding().to_token_stream()),
         })
     }
 
@@ -206,19 +258,50 @@ impl CompleteFunction {
         while let Some(v) = stack.pop() {
             VALUES.with_borrow(|values| match &values[v.0] {
                 Tuple(values) => {
-                    for &v in values.iter() {
+                    for &v in values {
                         if reachable.insert(v) {
                             stack.extend(values);
                         }
                     }
                 }
                 Str(s) => {}
-                Reference { value: v, .. } | Dereference(v) => {
+                StrLit(s) => {}
+                Expr(expr) => {}
+                Reference { value: v, .. }
+                | Dereference(v)
+                | Try(v)
+                | Await(v)
+                | Let { init: v, .. } => {
                     if reachable.insert(*v) {
                         stack.push(*v);
                     }
                 }
                 Binding { name, .. } => {}
+                Local { name, .. } => {}
+                PathValue { .. } => {}
+                ConstValue(_) => {}
+                Closure { ret, .. } => {
+                    if reachable.insert(*ret) {
+                        stack.push(*ret);
+                    }
+                }
+                Match {
+                    scrutinee, arms, ..
+                } => {
+                    let rets = arms.iter().map(|arm| arm.ret);
+                    for v in Some(*scrutinee).into_iter().chain(rets) {
+                        if reachable.insert(v) {
+                            stack.push(v);
+                        }
+                    }
+                }
+                Assign { place, value } => {
+                    for &v in &[*place, *value] {
+                        if reachable.insert(v) {
+                            stack.push(v);
+                        }
+                    }
+                }
                 Invoke(invoke) => INVOKES.with_borrow(|invokes| {
                     for &v in &invokes[invoke.0].args {
                         if reachable.insert(v) {
@@ -238,6 +321,27 @@ impl CompleteFunction {
                         stack.push(*parent);
                     }
                 }
+                StructLiteral { fields, .. } => {
+                    for &(_, v) in fields {
+                        if reachable.insert(v) {
+                            stack.push(v);
+                        }
+                    }
+                }
+                TupleStructLiteral { fields, .. } => {
+                    for &v in fields {
+                        if reachable.insert(v) {
+                            stack.push(v);
+                        }
+                    }
+                }
+                VariantConstruct { payload, .. } => {
+                    for v in payload.values() {
+                        if reachable.insert(v) {
+                            stack.push(v);
+                        }
+                    }
+                }
                 DataStructure { .. } => unimplemented!(),
             })
         }
@@ -263,26 +367,44 @@ impl CompleteFunction {
 
     fn is_important(&self, v: ValueRef) -> bool {
         VALUES.with_borrow(|values| {
-            if let ValueNode::Invoke(_) | ValueNode::MacroInvocation(_) = values[v.0] {
+            if let ValueNode::Invoke(_)
+            | ValueNode::MacroInvocation(_)
+            | ValueNode::Try(_)
+            | ValueNode::Await(_)
+            | ValueNode::Let { .. }
+            | ValueNode::Assign { .. }
+            | ValueNode::Match { .. } = values[v.0]
+            {
                 return true;
             }
             false
         })
     }
 
-    fn compile_value(&self, v: ValueRef) -> TokenStream {
+    fn compile_value(&self, v: ValueRef, bindings: &Bindings) -> TokenStream {
         VALUES.with_borrow(|values| match &values[v.0] {
             ValueNode::Tuple(values) => {
-                let values = self.make_values_list(values);
+                let trailing_comma = if values.len() == 1 {
+                    Some(quote!(,))
+                } else {
+                    None
+                };
+                let values = self.make_values_list(values, bindings);
 
                 quote! {
-                    ( #values )
+                    ( #values #trailing_comma )
                 }
             }
             ValueNode::Str(s) => quote! { #s },
+            ValueNode::StrLit(s) => {
+                let lit = Literal::string(s);
+                quote! { #lit }
+            }
+            ValueNode::Expr(expr @ syn::Expr::Lit(_)) => quote! { #expr },
+            ValueNode::Expr(expr) => quote! { (#expr) },
             ValueNode::Reference { is_mut, value } if !is_mut => {
                 if values[value.0].inlineable() {
-                    let v = self.compile_value(*value);
+                    let v = self.compile_value(*value, bindings);
                     quote! { &#v }
                 } else {
                     let v = value.binding();
@@ -291,7 +413,7 @@ impl CompleteFunction {
             }
             ValueNode::Reference { is_mut, value } => {
                 if values[value.0].inlineable() {
-                    let v = self.compile_value(*value);
+                    let v = self.compile_value(*value, bindings);
                     quote! { &mut #v }
                 } else {
                     let v = value.binding();
@@ -300,28 +422,146 @@ impl CompleteFunction {
             }
             ValueNode::Dereference(v) => {
                 if values[v.0].inlineable() {
-                    let v = self.compile_value(*v);
+                    let v = self.compile_value(*v, bindings);
                     quote! { *#v }
                 } else {
                     let v = v.binding();
                     quote! { *#v }
                 }
             }
+            ValueNode::Try(v) => {
+                if values[v.0].inlineable() {
+                    let v = self.compile_value(*v, bindings);
+                    quote! { (#v)? }
+                } else {
+                    let v = v.binding();
+                    quote! { #v? }
+                }
+            }
+            ValueNode::Await(v) => {
+                if values[v.0].inlineable() {
+                    let v = self.compile_value(*v, bindings);
+                    quote! { (#v).await }
+                } else {
+                    let v = v.binding();
+                    quote! { #v.await }
+                }
+            }
             ValueNode::Binding { name, .. } => quote! { #name },
+            ValueNode::Let {
+                name,
+                is_mut,
+                ty,
+                init,
+            } => {
+                let mutability = if *is_mut { Some(quote!(mut)) } else { None };
+                let ty = Print::ref_cast(ty);
+                let init = self.make_values_list(&[*init], bindings);
+                quote! {
+                    let #mutability #name: #ty = #init;
+                }
+            }
+            ValueNode::Local { name, .. } => quote! { #name },
+            ValueNode::PathValue { path, .. } => {
+                let path = Print::ref_cast(path);
+                quote! { #path }
+            }
+            ValueNode::ConstValue(const_param) => {
+                let const_param = Print::ref_cast(const_param);
+                quote! { #const_param }
+            }
+            ValueNode::Closure { params, body, ret } => {
+                let params = params
+                    .iter()
+                    .map(|param| self.compile_value(*param, bindings));
+                let statements = self.compile_statements(body.clone(), bindings);
+                let ret = self.compile_ret(*ret, bindings);
+                quote! {
+                    |#(#params),*| { #(#statements)* #ret }
+                }
+            }
+            ValueNode::Match {
+                scrutinee,
+                arms,
+                wildcard,
+            } => {
+                let enum_path = enum_path(&scrutinee.get_type());
+                let scrutinee = self.make_values_list(&[*scrutinee], bindings);
+                let arms = arms.iter().map(|arm| {
+                    let variant = &arm.variant;
+                    let fields = match &arm.payload {
+                        VariantPayload::Unit => None,
+                        VariantPayload::Tuple(_) => {
+                            let values = self.make_values_list(&arm.payload.values(), bindings);
+                            Some(quote!(( #values )))
+                        }
+                        VariantPayload::Struct(fields) => {
+                            let fields = fields.iter().map(|(ident, value)| {
+                                let value = self.make_values_list(&[value.index], bindings);
+                                quote!(#ident: #value)
+                            });
+                            Some(quote!({ #(#fields),* }))
+                        }
+                    };
+                    let statements = self.compile_statements(arm.body.clone(), bindings);
+                    let ret = self.compile_ret(arm.ret, bindings);
+                    quote! {
+                        #enum_path :: #variant #fields => { #(#statements)* #ret }
+                    }
+                });
+                let wildcard = if *wildcard {
+                    Some(quote! {
+                        #[allow(unreachable_patterns)]
+                        _ => ::core::unreachable!(),
+                    })
+                } else {
+                    None
+                };
+                quote! {
+                    match #scrutinee { #(#arms)* #wildcard }
+                }
+            }
+            ValueNode::Assign { place, value } => {
+                let place = self.make_values_list(&[*place], bindings);
+                let value = self.make_values_list(&[*value], bindings);
+                quote! {
+                    #place = #value;
+                }
+            }
             ValueNode::Invoke(invoke) => INVOKES.with_borrow(|invokes| {
                 let invoke = &invokes[invoke.0];
-                let parent_type = match invoke.function.parent {
-                    Some(ref parent) => {
+                let (parent_type, name) = match (&invoke.function.path, &invoke.function.parent) {
+                    (Some(path), _) => {
+                        let print = Print::ref_cast(SimplePath::ref_cast(path));
+                        // Generic arguments of the function itself are
+                        // written as a turbofish
+                        let turbofish = path.last_segment().and_then(PathSegment::generic_args);
+                        let turbofish = turbofish.map(|args| {
+                            let args = args.args.iter().map(Print::ref_cast);
+                            quote!(::<#(#args),*>)
+                        });
+                        (None, quote!(#print #turbofish))
+                    }
+                    (None, Some(parent)) => {
                         let print = Print::ref_cast(SimplePath::ref_cast(&parent.path));
-                        Some(quote!(#print ::))
+                        let name = &invoke.function.name;
+                        (Some(quote!(#print ::)), quote!(#name))
+                    }
+                    (None, None) => {
+                        let name = &invoke.function.name;
+                        (None, quote!(#name))
                     }
-                    None => None,
                 };
-                let name = Ident::new(&invoke.function.name);
-                let args = self.make_values_list(&invoke.args);
+                let args = self.make_values_list(&invoke.args, bindings);
 
-                quote! {
-                    #parent_type #name ( #args )
+                if invoke.function.sig.is_unsafe {
+                    quote! {
+                        unsafe { #parent_type #name ( #args ) }
+                    }
+                } else {
+                    quote! {
+                        #parent_type #name ( #args )
+                    }
                 }
             }),
             ValueNode::Destructure {
@@ -330,7 +570,7 @@ impl CompleteFunction {
                 ty,
             } => {
                 let mut node = &parent.node().get_type();
-                let parent = parent.binding();
+                let parent = self.make_values_list(&[*parent], bindings);
                 let accessor = Print::ref_cast(accessor);
                 let mut references = TokenStream::new();
 
@@ -344,10 +584,52 @@ impl CompleteFunction {
                 quote!(#references #parent.#accessor)
             }
             ValueNode::DataStructure { .. } => unimplemented!(),
+            ValueNode::StructLiteral { name, fields } => {
+                let fields = fields.iter().map(|(ident, value)| {
+                    let value = self.make_values_list(&[*value], bindings);
+                    quote!(#ident: #value)
+                });
+
+                quote! {
+                    #name { #(#fields),* }
+                }
+            }
+            ValueNode::TupleStructLiteral { name, fields } => {
+                let fields = self.make_values_list(fields, bindings);
+
+                quote! {
+                    #name ( #fields )
+                }
+            }
+            ValueNode::VariantConstruct {
+                path,
+                variant,
+                payload,
+            } => {
+                let path = Print::ref_cast(path);
+                let payload = match payload {
+                    VariantPayload::Unit => None,
+                    VariantPayload::Tuple(_) => {
+                        let values = self.make_values_list(&payload.values(), bindings);
+                        Some(quote!(( #values )))
+                    }
+                    VariantPayload::Struct(fields) => {
+                        let fields = fields.iter().map(|(ident, value)| {
+                            let value = self.make_values_list(&[value.index], bindings);
+                            quote!(#ident: #value)
+                        });
+                        Some(quote!({ #(#fields),* }))
+                    }
+                };
+
+                quote! {
+                    #path :: #variant #payload
+                }
+            }
             ValueNode::MacroInvocation(invoke) => MACROS.with_borrow(|macros| {
                 let invoke = &macros[invoke.0];
                 let path = Print::ref_cast(&invoke.macro_path);
-                let args = self.make_values_list(&invoke.args);
+                let args = self.make_values_list(&invoke.args, bindings);
 
                 let tokens = quote! {
                     #path ! ( #args )
@@ -359,10 +641,10 @@ impl CompleteFunction {
     }
 
     /// Makes a list of comma-separated values with string literals inlined
-    fn make_values_list(&self, values_refs: &[ValueRef]) -> TokenStream {
+    fn make_values_list(&self, values_refs: &[ValueRef], bindings: &Bindings) -> TokenStream {
         VALUES.with_borrow(|values| {
             let values = values_refs.iter().map(|value| match &values[value.0] {
-                node if node.inlineable() => self.compile_value(*value),
+                node if node.inlineable() => self.compile_value(*value, bindings),
                 _ => value.binding().to_token_stream(),
             });
 
@@ -371,37 +653,54 @@ impl CompleteFunction {
     }
 }
 
+/// The path that the variants of the enum `ty`, or a reference to it, are
+/// written under in patterns, without generic arguments
+fn enum_path(ty: &TypeNode) -> TokenStream {
+    match ty {
+        TypeNode::Reference { inner, .. } => enum_path(inner),
+        TypeNode::DataStructure(data) => data.name.to_token_stream(),
+        TypeNode::Path(path) => Print::ref_cast(SimplePath::ref_cast(path)).to_token_stream(),
+        other => panic!("enum_path: Not an enum, but {}", other),
+    }
+}
+
 fn receiver_tokens(receiver: Receiver) -> Option<TokenStream> {
     match receiver {
         Receiver::NoSelf => None,
         Receiver::SelfByValue => Some(quote!(self)),
         Receiver::SelfByReference { is_mut, lifetime } if !is_mut => {
-            let lifetime = lifetime
-                .0
-                .as_ref()
-                .map(|lifetime| Print::ref_cast(lifetime));
+            let lifetime = lifetime.0.as_ref().map(Print::ref_cast);
             Some(quote!(&#lifetime self))
         }
         Receiver::SelfByReference { is_mut, lifetime } => {
-            let lifetime = lifetime
-                .0
-                .as_ref()
-                .map(|lifetime| Print::ref_cast(lifetime));
+            let lifetime = lifetime.0.as_ref().map(Print::ref_cast);
             Some(quote!(&#lifetime mut self))
         }
     }
 }
 
 impl ValueRef {
-    fn binding(self) -> Ident {
+    pub(crate) fn binding(self) -> Ident {
         Ident::new(format!("__v{}", self.0))
     }
 }
 
 impl ValueNode {
+    /// Whether the node is emitted as a statement of its own rather than as
+    /// the expression of a let binding
+    fn is_statement(&self) -> bool {
+        matches!(self, Self::Let { .. } | Self::Assign { .. })
+    }
+
     fn inlineable(&self) -> bool {
         match self {
-            Self::Str(_) => true,
+            Self::Str(_)
+            | Self::StrLit(_)
+            | Self::Expr(_)
+            | Self::Local { .. }
+            | Self::PathValue { .. }
+            | Self::ConstValue(_)
+            | Self::Closure { .. } => true,
             Self::Tuple(values) => values.is_empty(),
             _ => false,
         }
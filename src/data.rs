@@ -1,7 +1,10 @@
-use crate::{attr, Field, Value};
+use crate::node::MatchArm;
+use crate::{
+    attr, repr, Field, GlobalPush, Ident, ReflectError, Repr, Value, ValueNode, ValueRef,
+    VariantPayload, VALUES,
+};
 use std::fmt;
 use std::fmt::Debug;
-use std::marker::PhantomData;
 use syn::Attribute;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -17,6 +20,78 @@ impl<T> Data<T> {
             Self::Enum(e) => &e.attrs,
         }
     }
+
+    /// Whether the struct or enum is marked `#[non_exhaustive]`
+    pub fn is_non_exhaustive(&self) -> bool {
+        attr::has(self.attrs(), "non_exhaustive")
+    }
+
+    /// The modifiers of the `#[repr(...)]` attributes, so `#[repr(C, packed)]`
+    /// gives `[Repr::C, Repr::Packed(None)]`. Empty if there is none.
+    pub fn repr(&self) -> Vec<Repr> {
+        repr::reprs(self.attrs())
+    }
+
+    /// The number of fields of a struct, or of all the variants of an enum
+    pub fn field_count(&self) -> usize {
+        match self {
+            Self::Struct(s) => s.fields().len(),
+            Self::Enum(e) => e
+                .variants
+                .iter()
+                .map(|variant| match variant {
+                    Variant::Unit(_) => 0,
+                    Variant::Tuple(variant) => variant.fields().len(),
+                    Variant::Struct(variant) => variant.fields().len(),
+                })
+                .sum(),
+        }
+    }
+
+    /// The fields of a struct. Panics on an enum, whose fields belong to
+    /// its variants.
+    pub fn fields(&self) -> &[Field<T>] {
+        match self {
+            Self::Struct(s) => s.fields(),
+            Self::Enum(_) => panic!("Data::fields: Not a struct"),
+        }
+    }
+
+    pub fn is_struct(&self) -> bool {
+        matches!(self, Self::Struct(_))
+    }
+
+    pub fn is_enum(&self) -> bool {
+        matches!(self, Self::Enum(_))
+    }
+
+    pub fn as_struct(&self) -> Option<&Struct<T>> {
+        match self {
+            Self::Struct(s) => Some(s),
+            Self::Enum(_) => None,
+        }
+    }
+
+    pub fn as_enum(&self) -> Option<&Enum<T>> {
+        match self {
+            Self::Struct(_) => None,
+            Self::Enum(e) => Some(e),
+        }
+    }
+
+    pub fn unwrap_struct(self) -> Struct<T> {
+        match self {
+            Self::Struct(s) => s,
+            Self::Enum(_) => panic!("Data::unwrap_struct: Not a struct"),
+        }
+    }
+
+    pub fn unwrap_enum(self) -> Enum<T> {
+        match self {
+            Self::Struct(_) => panic!("Data::unwrap_enum: Not an enum"),
+            Self::Enum(e) => e,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -27,6 +102,16 @@ pub enum Struct<T> {
 }
 
 impl<T> Struct<T> {
+    /// The name of the struct, which is also the name of the `DataStructure`
+    /// it came from
+    pub fn name(&self) -> &Ident {
+        match self {
+            Self::Unit(us) => &us.ident,
+            Self::Tuple(ts) => &ts.ident,
+            Self::Struct(ss) => &ss.ident,
+        }
+    }
+
     pub fn attrs(&self) -> &[Attribute] {
         match self {
             Self::Unit(us) => &us.attrs,
@@ -34,16 +119,52 @@ impl<T> Struct<T> {
             Self::Struct(ss) => &ss.attrs,
         }
     }
+
+    pub fn is_unit(&self) -> bool {
+        matches!(self, Self::Unit(_))
+    }
+
+    pub fn is_tuple(&self) -> bool {
+        matches!(self, Self::Tuple(_))
+    }
+
+    /// Returns true for a struct with named fields
+    pub fn is_named(&self) -> bool {
+        matches!(self, Self::Struct(_))
+    }
+
+    pub fn as_unit(&self) -> Option<&UnitStruct> {
+        match self {
+            Self::Unit(us) => Some(us),
+            _ => None,
+        }
+    }
+
+    pub fn as_tuple(&self) -> Option<&TupleStruct<T>> {
+        match self {
+            Self::Tuple(ts) => Some(ts),
+            _ => None,
+        }
+    }
+
+    pub fn as_named(&self) -> Option<&StructStruct<T>> {
+        match self {
+            Self::Struct(ss) => Some(ss),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Clone, PartialEq, Eq, Hash)]
 pub struct UnitStruct {
+    pub(crate) ident: Ident,
     pub(crate) attrs: Vec<Attribute>,
 }
 
 impl Debug for UnitStruct {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("UnitStruct")
+            .field("ident", &self.ident)
             .field("attrs", attr::debug(&self.attrs))
             .finish()
     }
@@ -51,6 +172,7 @@ impl Debug for UnitStruct {
 
 #[derive(Clone, PartialEq, Eq, Hash)]
 pub struct TupleStruct<T> {
+    pub(crate) ident: Ident,
     pub(crate) fields: Vec<Field<T>>,
     pub(crate) attrs: Vec<Attribute>,
 }
@@ -58,6 +180,7 @@ pub struct TupleStruct<T> {
 impl<T: Debug> Debug for TupleStruct<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("TupleStruct")
+            .field("ident", &self.ident)
             .field("fields", &self.fields)
             .field("attrs", attr::debug(&self.attrs))
             .finish()
@@ -66,6 +189,7 @@ impl<T: Debug> Debug for TupleStruct<T> {
 
 #[derive(Clone, PartialEq, Eq, Hash)]
 pub struct StructStruct<T> {
+    pub(crate) ident: Ident,
     pub(crate) fields: Vec<Field<T>>,
     pub(crate) attrs: Vec<Attribute>,
 }
@@ -73,6 +197,7 @@ pub struct StructStruct<T> {
 impl<T: Debug> Debug for StructStruct<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("StructStruct")
+            .field("ident", &self.ident)
             .field("fields", &self.fields)
             .field("attrs", attr::debug(&self.attrs))
             .finish()
@@ -89,7 +214,17 @@ impl<T> Struct<T> {
     }
 }
 
+impl UnitStruct {
+    pub fn name(&self) -> &Ident {
+        &self.ident
+    }
+}
+
 impl<T> TupleStruct<T> {
+    pub fn name(&self) -> &Ident {
+        &self.ident
+    }
+
     pub fn fields(&self) -> &[Field<T>] {
         &self.fields
     }
@@ -100,10 +235,20 @@ impl<T> TupleStruct<T> {
 }
 
 impl<T> StructStruct<T> {
+    pub fn name(&self) -> &Ident {
+        &self.ident
+    }
+
     pub fn fields(&self) -> &[Field<T>] {
         &self.fields
     }
 
+    /// The field named `name`. A raw field such as `r#type` is found by
+    /// both `"type"` and `"r#type"`.
+    pub fn field_by_name(&self, name: &str) -> Option<&Field<T>> {
+        field_by_name(&self.fields, name)
+    }
+
     pub fn attrs(&self) -> &[Attribute] {
         &self.attrs
     }
@@ -113,6 +258,9 @@ impl<T> StructStruct<T> {
 pub struct Enum<T> {
     pub(crate) variants: Vec<Variant<T>>,
     pub(crate) attrs: Vec<Attribute>,
+    /// The value an `Enum<Value>` was taken from, which `match_variant`
+    /// matches on
+    pub(crate) scrutinee: Option<ValueRef>,
 }
 
 impl<T: Debug> Debug for Enum<T> {
@@ -124,17 +272,181 @@ impl<T: Debug> Debug for Enum<T> {
     }
 }
 
+impl<T> Enum<T> {
+    pub fn variants(&self) -> &[Variant<T>] {
+        &self.variants
+    }
+
+    pub fn variant_count(&self) -> usize {
+        self.variants.len()
+    }
+
+    /// The variant with the given name, which may be written with or without
+    /// the `r#` prefix of a raw identifier, as in `field_by_name`
+    pub fn find_variant(&self, name: &str) -> Option<&Variant<T>> {
+        let name = name.strip_prefix("r#").unwrap_or(name);
+        self.variants
+            .iter()
+            .find(|variant| variant.name().as_str() == name)
+    }
+
+    pub fn has_variant(&self, name: &str) -> bool {
+        self.find_variant(name).is_some()
+    }
+
+    pub fn has_unit_variants(&self) -> bool {
+        self.variants
+            .iter()
+            .any(|variant| matches!(variant, Variant::Unit(_)))
+    }
+
+    pub fn has_tuple_variants(&self) -> bool {
+        self.variants
+            .iter()
+            .any(|variant| matches!(variant, Variant::Tuple(_)))
+    }
+
+    pub fn has_struct_variants(&self) -> bool {
+        self.variants
+            .iter()
+            .any(|variant| matches!(variant, Variant::Struct(_)))
+    }
+
+    /// Whether the enum is marked `#[non_exhaustive]`, so that a match on it
+    /// outside its crate needs a wildcard arm
+    pub fn has_non_exhaustive(&self) -> bool {
+        attr::has(&self.attrs, "non_exhaustive")
+    }
+}
+
+impl<T: Clone> Enum<T> {
+    /// The discriminant of each variant. A variant without an explicit
+    /// discriminant has the discriminant of the previous variant plus one, or
+    /// zero if it is the first.
+    ///
+    /// Fails if an explicit discriminant is not an integer literal, possibly
+    /// negated, since its value can not be known before compilation. Use
+    /// `Enum::discriminant_of` to refer to such a discriminant in generated
+    /// code instead.
+    pub fn discriminants(&self) -> Result<Vec<(Variant<T>, i128)>, ReflectError> {
+        let mut next = Some(0);
+        self.variants
+            .iter()
+            .map(|variant| {
+                let discriminant = match variant.discriminant() {
+                    Some(expr) => literal_discriminant(expr).ok_or(ReflectError::Unsupported(
+                        "discriminants that are not integer literals",
+                    ))?,
+                    None => next.ok_or(ReflectError::Unsupported(
+                        "implicit discriminants past i128::MAX",
+                    ))?,
+                };
+                next = discriminant.checked_add(1);
+                Ok((variant.clone(), discriminant))
+            })
+            .collect()
+    }
+}
+
+fn literal_discriminant(expr: &syn::Expr) -> Option<i128> {
+    match expr {
+        syn::Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Int(int),
+            ..
+        }) => int.base10_parse().ok(),
+        syn::Expr::Unary(syn::ExprUnary {
+            op: syn::UnOp::Neg(_),
+            expr,
+            ..
+        }) => literal_discriminant(expr)?.checked_neg(),
+        syn::Expr::Paren(syn::ExprParen { expr, .. }) => literal_discriminant(expr),
+        _ => None,
+    }
+}
+
 impl Enum<Value> {
+    /// The discriminant of `variant` as a value. An explicit discriminant is
+    /// used as written. An implicit one is computed from the previous
+    /// variants, which gives `None` if one of those has an explicit
+    /// discriminant that is not an integer literal, or if it overflows.
+    pub fn discriminant_of(&self, variant: &Variant<Value>) -> Option<Value> {
+        if let Some(expr) = variant.discriminant() {
+            return Some(Value::new_expr(expr.clone()));
+        }
+        let mut next = Some(0);
+        for other in &self.variants {
+            let discriminant = match other.discriminant() {
+                Some(expr) => literal_discriminant(expr)?,
+                None => next?,
+            };
+            if other.name() == variant.name() {
+                // Parses negative values as a negation of a literal
+                let expr = syn::parse_str(&discriminant.to_string()).unwrap();
+                return Some(Value::new_expr(expr));
+            }
+            next = discriminant.checked_add(1);
+        }
+        None
+    }
+
+    /// A `match` on the enum value with an arm for each variant, whose body
+    /// is built by `run` from the variant with its fields bound by the arm's
+    /// pattern. A `#[non_exhaustive]` enum gets an unreachable wildcard arm
+    /// as well. The arms must all produce values of the same type.
     pub fn match_variant<Run>(&self, run: Run) -> Value
     where
         Run: Fn(Variant<Value>) -> Value,
     {
-        let mut arms = Vec::new();
-        for variant in self.variants.clone() {
-            arms.push(run(variant));
+        let scrutinee = self
+            .scrutinee
+            .expect("Enum::match_variant: Not taken from a value");
+        let arms = self
+            .variants
+            .iter()
+            .map(|variant| {
+                let start = VALUES.with_borrow(|values| ValueRef(values.len()));
+                let variant = variant.clone().map(|field| {
+                    let index = VALUES.with_borrow(|values| ValueRef(values.len()));
+                    let node = ValueNode::Local {
+                        name: index.binding(),
+                        ty: field.element.index.get_type(),
+                    };
+                    Value {
+                        index: VALUES.index_push(node),
+                    }
+                });
+                let payload = match &variant {
+                    Variant::Unit(_) => VariantPayload::Unit,
+                    Variant::Tuple(variant) => VariantPayload::Tuple(
+                        variant.fields.iter().map(|field| field.element).collect(),
+                    ),
+                    Variant::Struct(variant) => VariantPayload::Struct(
+                        variant
+                            .fields
+                            .iter()
+                            .filter_map(|field| Some((field.ident()?.clone(), field.element)))
+                            .collect(),
+                    ),
+                };
+                let name = variant.name().clone();
+                let ret = run(variant);
+                let end = VALUES.with_borrow(|values| ValueRef(values.len()));
+                MatchArm {
+                    variant: name,
+                    payload,
+                    body: start..end,
+                    ret: ret.index,
+                }
+            })
+            .collect();
+        let node = ValueNode::Match {
+            scrutinee,
+            arms,
+            wildcard: self.has_non_exhaustive(),
+        };
+        Value {
+            index: VALUES.index_push(node),
         }
-        // FIXME introduce a match node
-        unimplemented!()
     }
 
     pub fn attrs(&self) -> &[Attribute] {
@@ -150,6 +462,14 @@ pub enum Variant<T> {
 }
 
 impl<T> Variant<T> {
+    pub fn name(&self) -> &Ident {
+        match self {
+            Self::Unit(uv) => &uv.ident,
+            Self::Tuple(tv) => &tv.ident,
+            Self::Struct(sv) => &sv.ident,
+        }
+    }
+
     pub fn attrs(&self) -> &[Attribute] {
         match self {
             Self::Unit(uv) => &uv.attrs,
@@ -157,45 +477,102 @@ impl<T> Variant<T> {
             Self::Struct(sv) => &sv.attrs,
         }
     }
+
+    /// The explicit discriminant of the variant, if it has one
+    pub fn discriminant(&self) -> Option<&syn::Expr> {
+        match self {
+            Self::Unit(uv) => uv.discriminant.as_ref(),
+            Self::Tuple(tv) => tv.discriminant.as_ref(),
+            Self::Struct(sv) => sv.discriminant.as_ref(),
+        }
+    }
 }
 
 #[derive(Clone, PartialEq, Eq, Hash)]
 pub struct UnitVariant {
+    pub(crate) ident: Ident,
     pub(crate) attrs: Vec<Attribute>,
+    /// The explicit discriminant, as in `Variant = 1`
+    pub(crate) discriminant: Option<syn::Expr>,
 }
 
 impl Debug for UnitVariant {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("UnitVariant")
+            .field("ident", &self.ident)
             .field("attrs", attr::debug(&self.attrs))
+            .field("discriminant", &self.discriminant)
             .finish()
     }
 }
 
 #[derive(Clone, PartialEq, Eq, Hash)]
 pub struct TupleVariant<T> {
-    pub(crate) phantom: PhantomData<T>,
+    pub(crate) ident: Ident,
+    pub(crate) fields: Vec<Field<T>>,
     pub(crate) attrs: Vec<Attribute>,
+    pub(crate) discriminant: Option<syn::Expr>,
 }
 
 impl<T: Debug> Debug for TupleVariant<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("TupleVariant")
+            .field("ident", &self.ident)
+            .field("fields", &self.fields)
             .field("attrs", attr::debug(&self.attrs))
+            .field("discriminant", &self.discriminant)
             .finish()
     }
 }
 
 #[derive(Clone, PartialEq, Eq, Hash)]
 pub struct StructVariant<T> {
-    pub(crate) phantom: PhantomData<T>,
+    pub(crate) ident: Ident,
+    pub(crate) fields: Vec<Field<T>>,
     pub(crate) attrs: Vec<Attribute>,
+    pub(crate) discriminant: Option<syn::Expr>,
 }
 
 impl<T: Debug> Debug for StructVariant<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("StructVariant")
+            .field("ident", &self.ident)
+            .field("fields", &self.fields)
             .field("attrs", attr::debug(&self.attrs))
+            .field("discriminant", &self.discriminant)
             .finish()
     }
 }
+
+impl<T> TupleVariant<T> {
+    pub fn fields(&self) -> &[Field<T>] {
+        &self.fields
+    }
+
+    pub fn attrs(&self) -> &[Attribute] {
+        &self.attrs
+    }
+}
+
+impl<T> StructVariant<T> {
+    pub fn fields(&self) -> &[Field<T>] {
+        &self.fields
+    }
+
+    /// The field named `name`. A raw field such as `r#type` is found by
+    /// both `"type"` and `"r#type"`.
+    pub fn field_by_name(&self, name: &str) -> Option<&Field<T>> {
+        field_by_name(&self.fields, name)
+    }
+
+    pub fn attrs(&self) -> &[Attribute] {
+        &self.attrs
+    }
+}
+
+fn field_by_name<'a, T>(fields: &'a [Field<T>], name: &str) -> Option<&'a Field<T>> {
+    let name = name.strip_prefix("r#").unwrap_or(name);
+    fields
+        .iter()
+        .find(|field| field.ident().is_some_and(|ident| ident.as_str() == name))
+}
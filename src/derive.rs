@@ -1,7 +1,8 @@
 use crate::{
     global_data, Accessor, CompleteFunction, CompleteImpl, Data, DataStructure, Enum, Execution,
-    Field, Generics, Ident, Program, Struct, StructStruct, Tracker, TupleStruct, TypeNode,
-    UnitStruct, WipFunction, WipImpl,
+    Field, Generics, Ident, Program, ReflectError, Struct, StructStruct, StructVariant,
+    SynParamMap, Tracker, TupleStruct, TupleVariant, TypeNode, UnitStruct, UnitVariant, Variant,
+    Visibility, WipFunction, WipImpl,
 };
 use proc_macro2::TokenStream;
 use syn::DeriveInput;
@@ -17,8 +18,13 @@ where
 }
 
 fn derive2(input: TokenStream, run: fn(Execution)) -> TokenStream {
-    let input = syn::parse2(input).unwrap();
-    let ty = syn_to_type(input);
+    let ty = match syn::parse2(input)
+        .map_err(ReflectError::from)
+        .and_then(syn_to_type)
+    {
+        Ok(ty) => ty,
+        Err(err) => return syn::Error::from(err).to_compile_error(),
+    };
 
     let tracker = Tracker::new();
     run(Execution {
@@ -30,58 +36,105 @@ fn derive2(input: TokenStream, run: fn(Execution)) -> TokenStream {
     program.compile()
 }
 
-fn syn_to_type(input: DeriveInput) -> TypeNode {
-    let attrs: Vec<_> = input
-        .attrs
-        .into_iter()
-        .map(std::convert::Into::into)
-        .collect();
-    let mut generics = Generics::syn_to_generics(input.generics);
+fn syn_to_type(input: DeriveInput) -> Result<TypeNode, ReflectError> {
+    let attrs: Vec<_> = input.attrs.into_iter().collect();
+    let mut generics = Generics::syn_to_generics(input.generics)?;
+    let param_map = &mut generics.param_map;
 
     let data = match input.data {
         syn::Data::Struct(data) => match data.fields {
             syn::Fields::Named(fields) => Data::Struct(Struct::Struct(StructStruct {
-                fields: fields
-                    .named
-                    .into_iter()
-                    .map(|field| Field {
-                        attrs: field.attrs,
-                        accessor: Accessor::Name(Ident::from(field.ident.unwrap())),
-                        element: TypeNode::syn_to_type(field.ty, &mut generics.param_map),
-                    })
-                    .collect(),
+                ident: Ident::from(input.ident.clone()),
+                fields: syn_to_named_fields(fields, param_map)?,
                 attrs,
             })),
             syn::Fields::Unnamed(fields) => Data::Struct(Struct::Tuple(TupleStruct {
-                fields: fields
-                    .unnamed
-                    .into_iter()
-                    .enumerate()
-                    .map(|(i, field)| Field {
-                        attrs: field.attrs,
-                        accessor: Accessor::Index(i),
-                        element: TypeNode::syn_to_type(field.ty, &mut generics.param_map),
-                    })
-                    .collect(),
+                ident: Ident::from(input.ident.clone()),
+                fields: syn_to_unnamed_fields(fields, param_map)?,
                 attrs,
             })),
-            syn::Fields::Unit => Data::Struct(Struct::Unit(UnitStruct { attrs })),
-        },
-        syn::Data::Enum(data) => {
-            // FIXME convert enum variants
-            Data::Enum(Enum {
-                variants: Vec::new(),
+            syn::Fields::Unit => Data::Struct(Struct::Unit(UnitStruct {
+                ident: Ident::from(input.ident.clone()),
                 attrs,
-            })
-        }
-        syn::Data::Union(_) => unimplemented!("union"),
+            })),
+        },
+        syn::Data::Enum(data) => Data::Enum(Enum {
+            variants: data
+                .variants
+                .into_iter()
+                .map(|variant| {
+                    let ident = Ident::from(variant.ident);
+                    let attrs = variant.attrs;
+                    let discriminant = variant.discriminant.map(|(_eq, expr)| expr);
+                    Ok(match variant.fields {
+                        syn::Fields::Named(fields) => Variant::Struct(StructVariant {
+                            ident,
+                            fields: syn_to_named_fields(fields, param_map)?,
+                            attrs,
+                            discriminant,
+                        }),
+                        syn::Fields::Unnamed(fields) => Variant::Tuple(TupleVariant {
+                            ident,
+                            fields: syn_to_unnamed_fields(fields, param_map)?,
+                            attrs,
+                            discriminant,
+                        }),
+                        syn::Fields::Unit => Variant::Unit(UnitVariant {
+                            ident,
+                            attrs,
+                            discriminant,
+                        }),
+                    })
+                })
+                .collect::<Result<_, ReflectError>>()?,
+            attrs,
+            scrutinee: None,
+        }),
+        syn::Data::Union(_) => return Err(ReflectError::Unsupported("unions")),
     };
 
-    TypeNode::DataStructure(Box::new(DataStructure {
+    Ok(TypeNode::DataStructure(Box::new(DataStructure {
         name: Ident::from(input.ident),
         generics,
         data,
-    }))
+    })))
+}
+
+fn syn_to_named_fields(
+    fields: syn::FieldsNamed,
+    param_map: &mut SynParamMap,
+) -> Result<Vec<Field<TypeNode>>, ReflectError> {
+    fields
+        .named
+        .into_iter()
+        .map(|field| {
+            Ok(Field {
+                attrs: field.attrs,
+                accessor: Accessor::Name(Ident::from(field.ident.unwrap())),
+                element: TypeNode::syn_to_type(field.ty, param_map)?,
+                vis: Visibility::syn_to_visibility(field.vis, param_map)?,
+            })
+        })
+        .collect()
+}
+
+fn syn_to_unnamed_fields(
+    fields: syn::FieldsUnnamed,
+    param_map: &mut SynParamMap,
+) -> Result<Vec<Field<TypeNode>>, ReflectError> {
+    fields
+        .unnamed
+        .into_iter()
+        .enumerate()
+        .map(|(i, field)| {
+            Ok(Field {
+                attrs: field.attrs,
+                accessor: Accessor::Index(i),
+                element: TypeNode::syn_to_type(field.ty, param_map)?,
+                vis: Visibility::syn_to_visibility(field.vis, param_map)?,
+            })
+        })
+        .collect()
 }
 
 fn tracker_to_program(tracker: Tracker) -> Program {
@@ -0,0 +1,87 @@
+use proc_macro2::Span;
+use std::error::Error;
+use std::fmt::{self, Display};
+
+/// An error encountered while converting syn syntax trees into reflect's
+/// representation.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ReflectError {
+    /// A construct that reflect does not support, such as array types
+    Unsupported(&'static str),
+    /// A lifetime that is not declared in the surrounding generics
+    NotALifetime(String),
+    /// An identifier that was expected to name a type parameter
+    NotATypeParam(String),
+    /// An identifier that was expected to name a const parameter
+    NotAConstParam(String),
+    /// A name that is already given to another param in scope
+    DuplicateParam(String),
+    /// A string that failed to parse as Rust syntax
+    Parse(syn::Error),
+    /// A value or type that is not of the kind an operation needs, such as
+    /// iterating over something that is not a `Vec` or slice
+    UnexpectedType {
+        expected: &'static str,
+        found: String,
+    },
+}
+
+impl Display for ReflectError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Unsupported(what) => write!(f, "reflect does not support {}", what),
+            Self::NotALifetime(ident) => write!(f, "`{}` is not a lifetime in scope", ident),
+            Self::NotATypeParam(ident) => write!(f, "`{}` is not a type parameter", ident),
+            Self::NotAConstParam(ident) => write!(f, "`{}` is not a const parameter", ident),
+            Self::DuplicateParam(ident) => write!(f, "`{}` is already a param in scope", ident),
+            Self::Parse(err) => Display::fmt(err, f),
+            Self::UnexpectedType { expected, found } => {
+                write!(f, "expected {}, found `{}`", expected, found)
+            }
+        }
+    }
+}
+
+impl Error for ReflectError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Parse(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<syn::Error> for ReflectError {
+    fn from(err: syn::Error) -> Self {
+        Self::Parse(err)
+    }
+}
+
+impl From<ReflectError> for syn::Error {
+    fn from(err: ReflectError) -> Self {
+        match err {
+            ReflectError::Parse(err) => err,
+            other => syn::Error::new(Span::call_site(), other),
+        }
+    }
+}
+
+/// An error from `Data::zip`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ZipError {
+    /// The two sides are not both structs of the same kind with the same
+    /// fields, or not both enums with the same variants
+    ShapeMismatch,
+}
+
+impl Display for ZipError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::ShapeMismatch => f.write_str("data structures differ in shape"),
+        }
+    }
+}
+
+impl Error for ZipError {}
@@ -1,4 +1,6 @@
-use crate::{attr, GlobalPush, Ident, TypeNode, Value, ValueNode, VALUES};
+use crate::{
+    attr, GlobalPush, Ident, Path, ReflectError, SynParamMap, TypeNode, Value, ValueNode, VALUES,
+};
 use std::fmt::{self, Debug, Display};
 use syn::Attribute;
 
@@ -13,6 +15,19 @@ pub struct Field<T> {
     pub(crate) accessor: Accessor,
     pub(crate) element: T,
     pub(crate) attrs: Vec<Attribute>,
+    pub(crate) vis: Visibility,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Visibility {
+    /// `pub`
+    Public,
+    /// `crate` or `pub(crate)`
+    Crate,
+    /// `pub(self)`, `pub(super)` or `pub(in some::module)`
+    Restricted(Path),
+    /// No visibility modifier
+    Inherited,
 }
 
 impl<T: Debug> Debug for Field<T> {
@@ -21,6 +36,7 @@ impl<T: Debug> Debug for Field<T> {
             .field("accessor", &self.accessor)
             .field("element", &self.element)
             .field("attrs", attr::debug(&self.attrs))
+            .field("vis", &self.vis)
             .finish()
     }
 }
@@ -35,6 +51,65 @@ impl Display for Accessor {
     }
 }
 
+impl Visibility {
+    pub(crate) fn syn_to_visibility(
+        vis: syn::Visibility,
+        param_map: &mut SynParamMap,
+    ) -> Result<Self, ReflectError> {
+        Ok(match vis {
+            syn::Visibility::Public(_) => Visibility::Public,
+            syn::Visibility::Crate(_) => Visibility::Crate,
+            syn::Visibility::Restricted(restricted) => {
+                if restricted.in_token.is_none() && restricted.path.is_ident("crate") {
+                    Visibility::Crate
+                } else {
+                    Visibility::Restricted(Path::syn_to_path(*restricted.path, param_map)?)
+                }
+            }
+            syn::Visibility::Inherited => Visibility::Inherited,
+        })
+    }
+}
+
+impl<T> Field<T> {
+    /// The name of a field in a struct with named fields, or `None` for a
+    /// tuple struct field
+    pub fn ident(&self) -> Option<&Ident> {
+        match &self.accessor {
+            Accessor::Name(ident) => Some(ident),
+            Accessor::Index(_) => None,
+        }
+    }
+
+    /// The position of a field in a tuple struct, or `None` for a named field
+    pub fn index(&self) -> Option<usize> {
+        match self.accessor {
+            Accessor::Name(_) => None,
+            Accessor::Index(i) => Some(i),
+        }
+    }
+
+    pub fn is_public(&self) -> bool {
+        self.vis == Visibility::Public
+    }
+
+    pub fn visibility(&self) -> Visibility {
+        self.vis.clone()
+    }
+
+    /// Whether the field has an attribute `#[attr_name(meta_name)]`, possibly
+    /// among other arguments
+    pub fn has_meta_attr(&self, attr_name: &str, meta_name: &str) -> bool {
+        attr::has_word(&self.attrs, attr_name, meta_name)
+    }
+
+    /// Whether the field is marked `#[derive_name(skip)]`, as derives do to
+    /// leave a field out of the generated code
+    pub fn should_skip(&self, derive_name: &str) -> bool {
+        self.has_meta_attr(derive_name, "skip")
+    }
+}
+
 impl Field<Value> {
     pub fn get_name(&self) -> Value {
         let node = ValueNode::Str(self.accessor.to_string());
@@ -0,0 +1,583 @@
+use crate::generics::{Binding, Constraint, Expr};
+use crate::node::MatchArm;
+use crate::path::{ParenthesizedGenericArguments, PathSegment};
+use crate::{
+    Accessor, AngleBracketedGenericArguments, ConstParam, DataStructure, GenericArgument,
+    GenericArguments, GenericConstraint, GenericParam, Generics, GlobalPush, Invoke, InvokeRef,
+    Lifetime, LifetimeDef, MacroInvoke, MacroInvokeRef, Path, PathArguments, PredicateType,
+    SynParamMap, TraitBound, TypeNode, TypeParam, TypeParamBound, Value, ValueNode, ValueRef,
+    VariantPayload, INVOKES, MACROS,
+};
+use std::ops::Range;
+
+/// A structure preserving transformation of a type tree.
+///
+/// Each method returns the folded copy of its node. The default methods
+/// rebuild the node from its folded children using the `fold_*` free
+/// functions of the same name, so an implementation only needs to override
+/// the nodes it wants to change. Folding leaves with `fold_type_param` and
+/// `fold_lifetime` is usually enough.
+pub trait TypeFolder {
+    fn fold_type(&mut self, node: &TypeNode) -> TypeNode {
+        fold_type(self, node)
+    }
+
+    fn fold_type_param(&mut self, type_param: TypeParam) -> TypeParam {
+        type_param
+    }
+
+    fn fold_lifetime(&mut self, lifetime: Lifetime) -> Lifetime {
+        lifetime
+    }
+
+    fn fold_const_param(&mut self, const_param: ConstParam) -> ConstParam {
+        const_param
+    }
+
+    fn fold_path(&mut self, path: &Path) -> Path {
+        fold_path(self, path)
+    }
+
+    fn fold_generic_arguments(&mut self, args: &GenericArguments) -> GenericArguments {
+        fold_generic_arguments(self, args)
+    }
+
+    fn fold_type_param_bound(&mut self, bound: &TypeParamBound) -> TypeParamBound {
+        fold_type_param_bound(self, bound)
+    }
+
+    fn fold_data_structure(&mut self, data: &DataStructure) -> DataStructure {
+        fold_data_structure(self, data)
+    }
+}
+
+/// A `TypeFolder` that returns an identical copy of the tree
+pub struct DefaultTypeFolder;
+
+impl TypeFolder for DefaultTypeFolder {}
+
+pub fn fold_type<F: TypeFolder + ?Sized>(f: &mut F, node: &TypeNode) -> TypeNode {
+    match node {
+        TypeNode::Infer => TypeNode::Infer,
+        TypeNode::Tuple(types) => TypeNode::Tuple(types.iter().map(|ty| f.fold_type(ty)).collect()),
+        TypeNode::PrimitiveStr => TypeNode::PrimitiveStr,
+        TypeNode::Never => TypeNode::Never,
+        TypeNode::Reference {
+            is_mut,
+            lifetime,
+            inner,
+        } => TypeNode::Reference {
+            is_mut: *is_mut,
+            lifetime: lifetime.map(|lifetime| f.fold_lifetime(lifetime)),
+            inner: Box::new(f.fold_type(inner)),
+        },
+        TypeNode::Dereference(inner) => TypeNode::Dereference(Box::new(f.fold_type(inner))),
+        TypeNode::Slice(elem) => TypeNode::Slice(Box::new(f.fold_type(elem))),
+        TypeNode::TraitObject(bounds) => TypeNode::TraitObject(
+            bounds
+                .iter()
+                .map(|bound| f.fold_type_param_bound(bound))
+                .collect(),
+        ),
+        TypeNode::DataStructure(data) => {
+            TypeNode::DataStructure(Box::new(f.fold_data_structure(data)))
+        }
+        TypeNode::Path(path) => TypeNode::Path(f.fold_path(path)),
+        TypeNode::TypeParam(type_param) => TypeNode::TypeParam(f.fold_type_param(*type_param)),
+        TypeNode::Associated(path) => TypeNode::Associated(f.fold_path(path)),
+        TypeNode::ImplTrait(bounds) => TypeNode::ImplTrait(
+            bounds
+                .iter()
+                .map(|bound| f.fold_type_param_bound(bound))
+                .collect(),
+        ),
+    }
+}
+
+pub fn fold_path<F: TypeFolder + ?Sized>(f: &mut F, path: &Path) -> Path {
+    Path {
+        global: path.global,
+        path: path
+            .path
+            .iter()
+            .map(|segment| PathSegment {
+                ident: segment.ident.clone(),
+                args: match &segment.args {
+                    PathArguments::None => PathArguments::None,
+                    PathArguments::AngleBracketed(args) => {
+                        PathArguments::AngleBracketed(AngleBracketedGenericArguments {
+                            args: f.fold_generic_arguments(&args.args),
+                        })
+                    }
+                    PathArguments::Parenthesized(args) => {
+                        PathArguments::Parenthesized(ParenthesizedGenericArguments {
+                            inputs: args.inputs.iter().map(|ty| f.fold_type(ty)).collect(),
+                            output: args.output.as_ref().map(|ty| f.fold_type(ty)),
+                        })
+                    }
+                },
+            })
+            .collect(),
+        qself: path
+            .qself
+            .as_ref()
+            .map(|qself| Box::new(f.fold_type(qself))),
+    }
+}
+
+pub fn fold_type_param_bound<F: TypeFolder + ?Sized>(
+    f: &mut F,
+    bound: &TypeParamBound,
+) -> TypeParamBound {
+    match bound {
+        TypeParamBound::Trait(bound) => TypeParamBound::Trait(TraitBound {
+            lifetimes: bound
+                .lifetimes
+                .iter()
+                .map(|lifetime| f.fold_lifetime(*lifetime))
+                .collect(),
+            path: f.fold_path(&bound.path),
+        }),
+        TypeParamBound::Lifetime(lifetime) => TypeParamBound::Lifetime(f.fold_lifetime(*lifetime)),
+    }
+}
+
+/// Folds the field types of a data structure. The generics are left as they
+/// are, since they declare the params rather than use them.
+pub fn fold_data_structure<F: TypeFolder + ?Sized>(
+    f: &mut F,
+    data: &DataStructure,
+) -> DataStructure {
+    DataStructure {
+        name: data.name.clone(),
+        generics: data.generics.clone(),
+        data: data.data.clone().map(|field| f.fold_type(&field.element)),
+    }
+}
+
+pub fn fold_generic_arguments<F: TypeFolder + ?Sized>(
+    f: &mut F,
+    args: &GenericArguments,
+) -> GenericArguments {
+    GenericArguments {
+        args: args
+            .args
+            .iter()
+            .map(|arg| match arg {
+                GenericArgument::Type(ty) => GenericArgument::Type(f.fold_type(ty)),
+                GenericArgument::Lifetime(lifetime) => {
+                    GenericArgument::Lifetime(f.fold_lifetime(*lifetime))
+                }
+                GenericArgument::Binding(binding) => GenericArgument::Binding(Binding {
+                    ident: binding.ident.clone(),
+                    ty: f.fold_type(&binding.ty),
+                }),
+                GenericArgument::Constraint(constraint) => {
+                    GenericArgument::Constraint(Constraint {
+                        ident: constraint.ident.clone(),
+                        bounds: constraint
+                            .bounds
+                            .iter()
+                            .map(|bound| f.fold_type_param_bound(bound))
+                            .collect(),
+                    })
+                }
+                GenericArgument::Const(Expr::Param(const_param)) => {
+                    GenericArgument::Const(Expr::Param(f.fold_const_param(*const_param)))
+                }
+                // Any other const expression is kept as it is written
+                GenericArgument::Const(expr) => GenericArgument::Const(expr.clone()),
+            })
+            .collect(),
+    }
+}
+
+pub(crate) fn fold_generic_constraint<F: TypeFolder + ?Sized>(
+    f: &mut F,
+    constraint: &GenericConstraint,
+) -> GenericConstraint {
+    match constraint {
+        GenericConstraint::Type(predicate) => GenericConstraint::Type(PredicateType {
+            lifetimes: predicate
+                .lifetimes
+                .iter()
+                .map(|lifetime| f.fold_lifetime(*lifetime))
+                .collect(),
+            bounded_ty: f.fold_type(&predicate.bounded_ty),
+            bounds: predicate
+                .bounds
+                .iter()
+                .map(|bound| f.fold_type_param_bound(bound))
+                .collect(),
+        }),
+        GenericConstraint::Lifetime(lifetime_def) => GenericConstraint::Lifetime(LifetimeDef {
+            lifetime: f.fold_lifetime(lifetime_def.lifetime),
+            bounds: lifetime_def
+                .bounds
+                .iter()
+                .map(|lifetime| f.fold_lifetime(*lifetime))
+                .collect(),
+        }),
+    }
+}
+
+/// Folds the params in the order they are declared, then the constraints and
+/// the params of the names in the param map
+pub(crate) fn fold_generics<F: TypeFolder + ?Sized>(f: &mut F, generics: &Generics) -> Generics {
+    let params = generics
+        .params
+        .iter()
+        .map(|param| fold_generic_param(f, *param))
+        .collect();
+    let constraints = generics
+        .constraints
+        .iter()
+        .map(|constraint| fold_generic_constraint(f, constraint))
+        .collect();
+    let mut param_map = SynParamMap::new();
+    for (name, param) in generics.param_map.iter() {
+        param_map.insert(name.to_owned(), fold_generic_param(f, *param));
+    }
+    Generics {
+        params,
+        constraints,
+        param_map,
+    }
+}
+
+fn fold_generic_param<F: TypeFolder + ?Sized>(f: &mut F, param: GenericParam) -> GenericParam {
+    match param {
+        GenericParam::Type(type_param) => GenericParam::Type(f.fold_type_param(type_param)),
+        GenericParam::Lifetime(lifetime) => GenericParam::Lifetime(f.fold_lifetime(lifetime)),
+        GenericParam::Const(const_param) => GenericParam::Const(f.fold_const_param(const_param)),
+    }
+}
+
+/// A structure preserving transformation of value nodes, the counterpart of
+/// `TypeFolder` for the values of a function body.
+///
+/// Children are referred to by `ValueRef`, so folding a node does not recurse
+/// on its own. A pass that rewrites a whole function folds each of its nodes
+/// in order and maps the refs of the nodes it has already folded in
+/// `fold_value_ref`. The bodies of closures and match arms are folded as
+/// ranges by `fold_value_range`, which maps both ends, so such a pass must
+/// also map the ref just past the end of a body.
+pub(crate) trait ValueFolder {
+    fn fold_value(&mut self, node: &ValueNode) -> ValueNode {
+        fold_value(self, node)
+    }
+
+    fn fold_value_ref(&mut self, value: ValueRef) -> ValueRef {
+        value
+    }
+
+    fn fold_value_range(&mut self, range: &Range<ValueRef>) -> Range<ValueRef> {
+        self.fold_value_ref(range.start)..self.fold_value_ref(range.end)
+    }
+
+    fn fold_value_type(&mut self, ty: &TypeNode) -> TypeNode {
+        ty.clone()
+    }
+
+    fn fold_reference(&mut self, is_mut: bool, value: ValueRef) -> ValueNode {
+        ValueNode::Reference {
+            is_mut,
+            value: self.fold_value_ref(value),
+        }
+    }
+
+    fn fold_dereference(&mut self, value: ValueRef) -> ValueNode {
+        ValueNode::Dereference(self.fold_value_ref(value))
+    }
+
+    fn fold_invoke(&mut self, invoke: InvokeRef) -> InvokeRef {
+        fold_invoke(self, invoke)
+    }
+
+    fn fold_macro_invoke(&mut self, invoke: MacroInvokeRef) -> MacroInvokeRef {
+        fold_macro_invoke(self, invoke)
+    }
+
+    fn fold_destructure(
+        &mut self,
+        parent: ValueRef,
+        accessor: &Accessor,
+        ty: &TypeNode,
+    ) -> ValueNode {
+        ValueNode::Destructure {
+            parent: self.fold_value_ref(parent),
+            accessor: accessor.clone(),
+            ty: self.fold_value_type(ty),
+        }
+    }
+
+    fn fold_match_arm(&mut self, arm: &MatchArm) -> MatchArm {
+        MatchArm {
+            variant: arm.variant.clone(),
+            payload: fold_variant_payload(self, &arm.payload),
+            body: self.fold_value_range(&arm.body),
+            ret: self.fold_value_ref(arm.ret),
+        }
+    }
+}
+
+/// A `ValueFolder` that returns an identical copy of each node
+pub(crate) struct DefaultValueFolder;
+
+impl ValueFolder for DefaultValueFolder {}
+
+pub(crate) fn fold_value<F: ValueFolder + ?Sized>(f: &mut F, node: &ValueNode) -> ValueNode {
+    match node {
+        ValueNode::Tuple(values) => ValueNode::Tuple(
+            values
+                .iter()
+                .map(|value| f.fold_value_ref(*value))
+                .collect(),
+        ),
+        ValueNode::Str(s) => ValueNode::Str(s.clone()),
+        ValueNode::StrLit(s) => ValueNode::StrLit(s.clone()),
+        ValueNode::Reference { is_mut, value } => f.fold_reference(*is_mut, *value),
+        ValueNode::Dereference(value) => f.fold_dereference(*value),
+        ValueNode::Binding { name, ty } => ValueNode::Binding {
+            name: name.clone(),
+            ty: f.fold_value_type(ty),
+        },
+        ValueNode::DataStructure { name, data } => ValueNode::DataStructure {
+            name: name.clone(),
+            data: data.clone().map(|field| f.fold_value_ref(field.element)),
+        },
+        ValueNode::Invoke(invoke) => ValueNode::Invoke(f.fold_invoke(*invoke)),
+        ValueNode::Destructure {
+            parent,
+            accessor,
+            ty,
+        } => f.fold_destructure(*parent, accessor, ty),
+        ValueNode::MacroInvocation(invoke) => {
+            ValueNode::MacroInvocation(f.fold_macro_invoke(*invoke))
+        }
+        ValueNode::StructLiteral { name, fields } => ValueNode::StructLiteral {
+            name: name.clone(),
+            fields: fields
+                .iter()
+                .map(|(ident, value)| (ident.clone(), f.fold_value_ref(*value)))
+                .collect(),
+        },
+        ValueNode::TupleStructLiteral { name, fields } => ValueNode::TupleStructLiteral {
+            name: name.clone(),
+            fields: fields
+                .iter()
+                .map(|value| f.fold_value_ref(*value))
+                .collect(),
+        },
+        ValueNode::VariantConstruct {
+            path,
+            variant,
+            payload,
+        } => ValueNode::VariantConstruct {
+            path: path.clone(),
+            variant: variant.clone(),
+            payload: fold_variant_payload(f, payload),
+        },
+        ValueNode::Try(value) => ValueNode::Try(f.fold_value_ref(*value)),
+        ValueNode::Await(value) => ValueNode::Await(f.fold_value_ref(*value)),
+        ValueNode::Expr(expr) => ValueNode::Expr(expr.clone()),
+        ValueNode::Let {
+            name,
+            is_mut,
+            ty,
+            init,
+        } => ValueNode::Let {
+            name: name.clone(),
+            is_mut: *is_mut,
+            ty: f.fold_value_type(ty),
+            init: f.fold_value_ref(*init),
+        },
+        ValueNode::Local { name, ty } => ValueNode::Local {
+            name: name.clone(),
+            ty: f.fold_value_type(ty),
+        },
+        ValueNode::Assign { place, value } => ValueNode::Assign {
+            place: f.fold_value_ref(*place),
+            value: f.fold_value_ref(*value),
+        },
+        ValueNode::Closure { params, body, ret } => ValueNode::Closure {
+            params: params
+                .iter()
+                .map(|param| f.fold_value_ref(*param))
+                .collect(),
+            body: f.fold_value_range(body),
+            ret: f.fold_value_ref(*ret),
+        },
+        ValueNode::PathValue { path, ty } => ValueNode::PathValue {
+            path: path.clone(),
+            ty: f.fold_value_type(ty),
+        },
+        ValueNode::ConstValue(const_param) => ValueNode::ConstValue(*const_param),
+        ValueNode::Match {
+            scrutinee,
+            arms,
+            wildcard,
+        } => ValueNode::Match {
+            scrutinee: f.fold_value_ref(*scrutinee),
+            arms: arms.iter().map(|arm| f.fold_match_arm(arm)).collect(),
+            wildcard: *wildcard,
+        },
+    }
+}
+
+/// Folds the arguments of the invocation. An invocation is only pushed again
+/// if one of its arguments changed, so an identity fold returns `invoke`.
+pub(crate) fn fold_invoke<F: ValueFolder + ?Sized>(f: &mut F, invoke: InvokeRef) -> InvokeRef {
+    let Invoke { function, args } = INVOKES.with_borrow(|invokes| invokes[invoke.0].clone());
+    let folded: Vec<ValueRef> = args.iter().map(|arg| f.fold_value_ref(*arg)).collect();
+    if folded == args {
+        return invoke;
+    }
+    INVOKES.index_push(Invoke {
+        function,
+        args: folded,
+    })
+}
+
+/// Folds the arguments and output type of the macro invocation, pushing it
+/// again only if one of them changed
+pub(crate) fn fold_macro_invoke<F: ValueFolder + ?Sized>(
+    f: &mut F,
+    invoke: MacroInvokeRef,
+) -> MacroInvokeRef {
+    let MacroInvoke {
+        macro_path,
+        args,
+        output,
+    } = MACROS.with_borrow(|macros| macros[invoke.0].clone());
+    let folded_args: Vec<ValueRef> = args.iter().map(|arg| f.fold_value_ref(*arg)).collect();
+    let folded_output = f.fold_value_type(&output);
+    if folded_args == args && folded_output == output {
+        return invoke;
+    }
+    MACROS.index_push(MacroInvoke {
+        macro_path,
+        args: folded_args,
+        output: folded_output,
+    })
+}
+
+fn fold_variant_payload<F: ValueFolder + ?Sized>(
+    f: &mut F,
+    payload: &VariantPayload,
+) -> VariantPayload {
+    let mut fold = |value: &Value| Value {
+        index: f.fold_value_ref(value.index),
+    };
+    match payload {
+        VariantPayload::Unit => VariantPayload::Unit,
+        VariantPayload::Tuple(values) => {
+            VariantPayload::Tuple(values.iter().map(&mut fold).collect())
+        }
+        VariantPayload::Struct(fields) => VariantPayload::Struct(
+            fields
+                .iter()
+                .map(|(ident, value)| (ident.clone(), fold(value)))
+                .collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+struct Offset(usize);
+
+#[cfg(test)]
+impl ValueFolder for Offset {
+    fn fold_value_ref(&mut self, value: ValueRef) -> ValueRef {
+        ValueRef(value.0 + self.0)
+    }
+}
+
+#[test]
+fn test_fold_closure() {
+    let closure = ValueNode::Closure {
+        params: vec![ValueRef(1)],
+        body: ValueRef(1)..ValueRef(3),
+        ret: ValueRef(2),
+    };
+    match Offset(10).fold_value(&closure) {
+        ValueNode::Closure { params, body, ret } => {
+            assert_eq!(params, [ValueRef(11)]);
+            assert_eq!(body, ValueRef(11)..ValueRef(13));
+            assert_eq!(ret, ValueRef(12));
+        }
+        node => panic!("expected a closure, found {:?}", node),
+    }
+}
+
+#[test]
+fn test_fold_match() {
+    let node = ValueNode::Match {
+        scrutinee: ValueRef(0),
+        arms: vec![
+            MatchArm {
+                variant: crate::Ident::new("Some"),
+                payload: VariantPayload::Tuple(vec![Value { index: ValueRef(1) }]),
+                body: ValueRef(1)..ValueRef(3),
+                ret: ValueRef(2),
+            },
+            MatchArm {
+                variant: crate::Ident::new("None"),
+                payload: VariantPayload::Unit,
+                body: ValueRef(3)..ValueRef(4),
+                ret: ValueRef(3),
+            },
+        ],
+        wildcard: false,
+    };
+    match Offset(10).fold_value(&node) {
+        ValueNode::Match {
+            scrutinee,
+            arms,
+            wildcard,
+        } => {
+            assert_eq!(scrutinee, ValueRef(10));
+            assert!(!wildcard);
+            match &arms[0].payload {
+                VariantPayload::Tuple(values) => assert_eq!(values[0].index, ValueRef(11)),
+                payload => panic!("expected a tuple payload, found {:?}", payload),
+            }
+            assert_eq!(arms[0].body, ValueRef(11)..ValueRef(13));
+            assert_eq!(arms[0].ret, ValueRef(12));
+            assert_eq!(arms[1].body, ValueRef(13)..ValueRef(14));
+            assert_eq!(arms[1].ret, ValueRef(13));
+        }
+        node => panic!("expected a match, found {:?}", node),
+    }
+}
+
+#[test]
+fn test_fold_invoke() {
+    use crate::{Function, Signature};
+    use std::rc::Rc;
+
+    let function = Rc::new(Function::get_function("f", Signature::new()));
+    let invoke = INVOKES.index_push(Invoke {
+        function,
+        args: vec![ValueRef(0), ValueRef(1)],
+    });
+    let node = ValueNode::Invoke(invoke);
+    let invokes = INVOKES.with_borrow(Vec::len);
+
+    match DefaultValueFolder.fold_value(&node) {
+        ValueNode::Invoke(folded) => assert_eq!(folded.0, invoke.0),
+        node => panic!("expected an invocation, found {:?}", node),
+    }
+    assert_eq!(INVOKES.with_borrow(Vec::len), invokes);
+
+    match Offset(10).fold_value(&node) {
+        ValueNode::Invoke(folded) => {
+            assert_ne!(folded.0, invoke.0);
+            let args = INVOKES.with_borrow(|invokes| invokes[folded.0].args.clone());
+            assert_eq!(args, [ValueRef(10), ValueRef(11)]);
+        }
+        node => panic!("expected an invocation, found {:?}", node),
+    }
+    let args = INVOKES.with_borrow(|invokes| invokes[invoke.0].args.clone());
+    assert_eq!(args, [ValueRef(0), ValueRef(1)]);
+}
@@ -1,11 +1,20 @@
-use crate::{Generics, GlobalPush, Invoke, Parent, Signature, Value, ValueNode, INVOKES, VALUES};
+use crate::{
+    Generics, GlobalPush, Ident, Invoke, Parent, Path, ReflectError, Signature, TypeNode, Value,
+    ValueNode, INVOKES, VALUES,
+};
 use std::rc::Rc;
+use syn::{parse_quote, Attribute};
 
 #[derive(Debug, Clone)]
 pub struct Function {
     pub(crate) parent: Option<Rc<Parent>>,
-    pub(crate) name: String,
+    pub(crate) name: Ident,
     pub(crate) sig: Signature,
+    /// Attributes emitted on the function when it is generated
+    pub(crate) attrs: Vec<Attribute>,
+    /// The full path of an externally defined function, which calls are
+    /// emitted through instead of the parent and name
+    pub(crate) path: Option<Path>,
 }
 
 impl Function {
@@ -21,15 +30,44 @@ impl Function {
         }
     }
 
-    pub fn get_function(name: &str, mut sig: Signature) -> Function {
+    /// A function named `name`, which is parsed as by `Ident::from_str`, so
+    /// a keyword must be given as a raw ident such as `r#type`
+    pub fn get_function(name: &str, sig: Signature) -> Function {
+        Self::with_name(Ident::from_str(name), sig)
+    }
+
+    fn with_name(name: Ident, mut sig: Signature) -> Function {
         sig.insert_elided_lifetimes();
         Self {
             parent: None,
-            name: name.to_owned(),
+            name,
             sig,
+            attrs: Vec::new(),
+            path: None,
         }
     }
 
+    /// A function defined outside of the generated code, such as in `std` or
+    /// another crate, which is called as `path(args...)`
+    pub fn new_extern(path: Path, sig: Signature) -> Rc<Self> {
+        let name = match path.path.last() {
+            Some(segment) => segment.ident.clone(),
+            None => panic!("Function::new_extern: Empty path"),
+        };
+        let mut function = Self::with_name(name, sig);
+        function.path = Some(path);
+        Rc::new(function)
+    }
+
+    /// Adds `#[allow(lint)]` to the function when it is generated, such as
+    /// `allow("unused_variables")` or `allow("clippy::needless_return")`.
+    /// Gives `ReflectError::Parse` if `lint` is not a path.
+    pub fn allow(&mut self, lint: &str) -> Result<&mut Self, ReflectError> {
+        let lint: syn::Path = syn::parse_str(lint)?;
+        self.attrs.push(parse_quote!(#[allow(#lint)]));
+        Ok(self)
+    }
+
     /// When calling `set_parent` it is important to use a reference to the
     /// same Parent struct for all functions declared inside of the same impl
     /// or trait definition. Otherwise the trait inference may not work
@@ -77,7 +115,11 @@ impl Function {
                         .map(|ty| ty.clone_with_fresh_generics(&param_map))
                         .collect(),
                     output: old_sig.output.clone_with_fresh_generics(&param_map),
+                    is_async: old_sig.is_async,
+                    is_unsafe: old_sig.is_unsafe,
                 },
+                attrs: self.attrs.clone(),
+                path: self.path.clone(),
             })
         } else if self.sig.generics.params.is_empty() {
             self.clone()
@@ -97,8 +139,70 @@ impl Function {
                         .map(|ty| ty.clone_with_fresh_generics(&param_map))
                         .collect(),
                     output: old_sig.output.clone_with_fresh_generics(&param_map),
+                    is_async: old_sig.is_async,
+                    is_unsafe: old_sig.is_unsafe,
                 },
+                attrs: self.attrs.clone(),
+                path: self.path.clone(),
             })
         }
     }
 }
+
+/// Declares a function for generated code to call, without going through
+/// `library!`. The types are taken as they are given, so any params in them
+/// must be params of the generics passed to `generics`.
+#[derive(Debug, Clone)]
+pub struct FunctionBuilder {
+    name: Option<Ident>,
+    sig: Signature,
+}
+
+impl FunctionBuilder {
+    pub fn new() -> Self {
+        Self {
+            name: None,
+            sig: Signature::new(),
+        }
+    }
+
+    pub fn name(mut self, name: Ident) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    /// Appends an argument of type `ty`. The arguments of generated functions
+    /// are named by position, so inputs have no name of their own.
+    pub fn input(mut self, ty: TypeNode) -> Self {
+        self.sig.add_input(ty);
+        self
+    }
+
+    /// The return type, `()` if not set
+    pub fn output(mut self, ty: TypeNode) -> Self {
+        self.sig.set_output(ty);
+        self
+    }
+
+    pub fn generics(mut self, generics: Generics) -> Self {
+        self.sig.generics = generics;
+        self
+    }
+
+    pub fn is_unsafe(mut self, is_unsafe: bool) -> Self {
+        self.sig.is_unsafe = is_unsafe;
+        self
+    }
+
+    /// Panics if no name was given
+    pub fn build(self) -> Rc<Function> {
+        let name = self.name.expect("FunctionBuilder::build: Missing name");
+        Rc::new(Function::with_name(name, self.sig))
+    }
+}
+
+impl Default for FunctionBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
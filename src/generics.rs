@@ -1,9 +1,17 @@
-use crate::{GlobalCounter, Ident, Path, TypeNode, LIFETIMES, STATIC_LIFETIME, TYPE_PARAMS};
+use crate::visit::{walk_type_node, FreeParams};
+use crate::{
+    fold, print, DataStructure, GlobalCounter, Ident, Path, Print, ReflectError, TypeFolder,
+    TypeNode, CONST_PARAMS, LIFETIMES, STATIC_LIFETIME, TYPE_PARAMS,
+};
+use proc_macro2::{Delimiter, TokenTree};
+use quote::{quote, ToTokens};
+use ref_cast::RefCast;
 use std::collections::BTreeMap;
 use std::default::Default;
-use syn::{parse_str, BoundLifetimes, PredicateLifetime, WhereClause, WherePredicate};
+use std::iter;
+use syn::{parse_str, BoundLifetimes, PredicateLifetime, WherePredicate};
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
 pub struct Generics {
     /// Represents the generic params without bounds.
     /// The bounds are moved to constraints.
@@ -17,11 +25,18 @@ pub struct Generics {
     pub(crate) param_map: SynParamMap,
 }
 
+/// The constraints of a `Generics`, printed as a where clause
+#[derive(RefCast)]
+#[repr(C)]
+pub(crate) struct WhereClause {
+    pub(crate) generics: Generics,
+}
+
+/// A lifetime, type or const param, as found in a `SynParamMap`
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub(crate) enum GenericParam {
+pub enum GenericParam {
     Lifetime(Lifetime),
     Type(TypeParam),
-    // Not supported
     Const(ConstParam),
 }
 
@@ -31,6 +46,11 @@ pub struct TypeParam(pub usize);
 #[derive(Debug, Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Hash)]
 pub struct Lifetime(pub usize);
 
+/// A const param, as in `const N: usize`. Its type is kept alongside the
+/// params of the other derives on the thread, see `ConstParam::ty`.
+#[derive(Debug, Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct ConstParam(pub usize);
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub(crate) enum GenericConstraint {
     Type(PredicateType),
@@ -64,13 +84,10 @@ pub(crate) struct LifetimeDef {
     pub(crate) bounds: Vec<Lifetime>,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub(crate) struct ConstParam {
-    pub(crate) private: (),
-}
-
+/// The arguments between the angle brackets of a path segment, as in
+/// `HashMap<K, V>` or `Iterator<Item = T>`
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub(crate) struct GenericArguments {
+pub struct GenericArguments {
     pub(crate) args: Vec<GenericArgument>,
 }
 
@@ -83,8 +100,9 @@ pub(crate) enum GenericArgument {
     Const(Expr),
 }
 
+/// An associated type binding, as in `Item = T`
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub(crate) struct Binding {
+pub struct Binding {
     pub(crate) ident: Ident,
     pub(crate) ty: TypeNode,
 }
@@ -95,9 +113,14 @@ pub(crate) struct Constraint {
     pub(crate) bounds: Vec<TypeParamBound>,
 }
 
+/// The value of a const argument
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub(crate) struct Expr {
-    pub(crate) private: (),
+pub(crate) enum Expr {
+    /// A const param in scope, as in `Buf<N>`
+    Param(ConstParam),
+    /// Any other expression, such as `3` or `{ N + 1 }`, taken as written
+    /// except that the params in it have their generated names
+    Value(syn::Expr),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -130,22 +153,50 @@ impl SynParamMap {
         self.map.append(&mut other.map)
     }
 
+    /// The params in the map ordered by identifier, including `'static`
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &GenericParam)> {
+        self.map.iter().map(|(key, param)| (key.as_str(), param))
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.map.contains_key(key)
+    }
+
+    /// The number of params in the map. A new map already contains `'static`.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
     pub(crate) fn clone_with_fresh_generics(&self, param_map: &ParamMap) -> Self {
         let mut syn_param_map = Self::new();
-        for key in self.map.keys() {
-            let value = self.get(key).unwrap();
-
+        for (key, value) in self.iter() {
             if let Some(value) = param_map.get(value) {
-                syn_param_map.insert(key.clone(), *value);
+                syn_param_map.insert(key.to_owned(), *value);
             }
         }
         syn_param_map
     }
 
-    pub(crate) fn get_lifetime(&self, ident: &str) -> Lifetime {
+    pub(crate) fn get_lifetime(&self, ident: &str) -> Result<Lifetime, ReflectError> {
         self.get(ident)
             .and_then(|param| param.lifetime())
-            .expect("Lifetime::get_lifetime: ident is not a lifetime")
+            .ok_or_else(|| ReflectError::NotALifetime(ident.to_owned()))
+    }
+
+    pub(crate) fn get_type_param(&self, ident: &str) -> Result<TypeParam, ReflectError> {
+        self.get(ident)
+            .and_then(|param| param.type_param())
+            .ok_or_else(|| ReflectError::NotATypeParam(ident.to_owned()))
+    }
+
+    pub(crate) fn get_const_param(&self, ident: &str) -> Result<ConstParam, ReflectError> {
+        self.get(ident)
+            .and_then(|param| param.const_param())
+            .ok_or_else(|| ReflectError::NotAConstParam(ident.to_owned()))
     }
 }
 
@@ -170,45 +221,208 @@ impl ParamMap {
     pub(crate) fn get(&self, key: &GenericParam) -> Option<&GenericParam> {
         self.map.get(key)
     }
+
+    /// A map from each param of `generics` to itself
+    pub(crate) fn identity_for(generics: &Generics) -> Self {
+        let mut param_map = Self::new();
+        for param in &generics.params {
+            param_map.insert(*param, *param);
+        }
+        param_map
+    }
+
+    /// A map that applies `self` and then `other`, so that each key `k` of
+    /// `self` maps to `other[self[k]]`. Params that `other` does not map are
+    /// kept as `self` maps them.
+    pub(crate) fn compose(&self, other: &ParamMap) -> Self {
+        let map = self
+            .map
+            .iter()
+            .map(|(key, value)| (*key, *other.get(value).unwrap_or(value)))
+            .collect();
+        Self { map }
+    }
+}
+
+/// Replaces each generic param with the fresh param it maps to
+pub(crate) struct FreshGenerics<'a>(pub(crate) &'a ParamMap);
+
+impl TypeFolder for FreshGenerics<'_> {
+    fn fold_type_param(&mut self, type_param: TypeParam) -> TypeParam {
+        self.0
+            .get(&GenericParam::Type(type_param))
+            .and_then(|param| param.type_param())
+            .unwrap()
+    }
+
+    fn fold_lifetime(&mut self, lifetime: Lifetime) -> Lifetime {
+        self.0
+            .get(&GenericParam::Lifetime(lifetime))
+            .and_then(|param| param.lifetime())
+            .unwrap()
+    }
+
+    fn fold_const_param(&mut self, const_param: ConstParam) -> ConstParam {
+        self.0
+            .get(&GenericParam::Const(const_param))
+            .and_then(|param| param.const_param())
+            .unwrap()
+    }
+
+    // A data structure is the input type of the derive, whose params belong
+    // to the impl rather than to the signature being refreshed
+    fn fold_data_structure(&mut self, data: &DataStructure) -> DataStructure {
+        data.clone()
+    }
+}
+
+/// Renumbers params in the order they are first seen, starting from zero,
+/// so that types that only differ in the numbering of their params become
+/// equal
+#[derive(Default)]
+pub(crate) struct Canonicalize {
+    type_params: BTreeMap<TypeParam, TypeParam>,
+    lifetimes: BTreeMap<Lifetime, Lifetime>,
+}
+
+impl Canonicalize {
+    pub(crate) fn fold_generics(&mut self, generics: &Generics) -> Generics {
+        Generics {
+            param_map: SynParamMap::new(),
+            ..fold::fold_generics(self, generics)
+        }
+    }
+}
+
+impl TypeFolder for Canonicalize {
+    fn fold_type_param(&mut self, type_param: TypeParam) -> TypeParam {
+        let next = TypeParam(self.type_params.len());
+        *self.type_params.entry(type_param).or_insert(next)
+    }
+
+    fn fold_lifetime(&mut self, lifetime: Lifetime) -> Lifetime {
+        if lifetime == STATIC_LIFETIME {
+            return lifetime;
+        }
+        let next = Lifetime(self.lifetimes.len() + 1);
+        *self.lifetimes.entry(lifetime).or_insert(next)
+    }
+
+    // Const params are kept, as their number is where their type is looked
+    // up. Generics with const params only have equal canonical forms if they
+    // share the const params.
+
+    fn fold_data_structure(&mut self, data: &DataStructure) -> DataStructure {
+        DataStructure {
+            name: data.name.clone(),
+            generics: self.fold_generics(&data.generics),
+            data: data
+                .data
+                .clone()
+                .map(|field| self.fold_type(&field.element)),
+        }
+    }
+}
+
+/// Replaces the type params that are keys of the map. The names of the
+/// params of data structures are dropped, as they do not change the shape of
+/// the type.
+pub(crate) struct RenameTypeParams(pub(crate) BTreeMap<TypeParam, TypeParam>);
+
+impl TypeFolder for RenameTypeParams {
+    fn fold_type_param(&mut self, type_param: TypeParam) -> TypeParam {
+        self.0.get(&type_param).copied().unwrap_or(type_param)
+    }
+
+    fn fold_data_structure(&mut self, data: &DataStructure) -> DataStructure {
+        DataStructure {
+            name: data.name.clone(),
+            generics: Generics {
+                param_map: SynParamMap::new(),
+                ..fold::fold_generics(self, &data.generics)
+            },
+            data: data
+                .data
+                .clone()
+                .map(|field| self.fold_type(&field.element)),
+        }
+    }
+}
+
+impl TraitBound {
+    /// The bound `path`, without a `for<...>` binder
+    pub fn new(path: Path) -> Self {
+        Self::with_for_lifetimes(path, &[])
+    }
+
+    /// The higher-ranked bound `for<'a, ...> path`
+    pub fn with_for_lifetimes(path: Path, lifetimes: &[Lifetime]) -> Self {
+        TraitBound {
+            lifetimes: lifetimes.to_vec(),
+            path,
+        }
+    }
 }
 
 impl TypeParamBound {
+    /// The lifetime bound `'a`, as in `T: 'a`
+    pub fn new_lifetime(lifetime: Lifetime) -> Self {
+        TypeParamBound::Lifetime(lifetime)
+    }
+
     pub(crate) fn get_type_param_bound(
         type_param_bound: &str,
         param_map: &mut SynParamMap,
-    ) -> Self {
-        syn_to_type_param_bound(
-            parse_str(type_param_bound)
-                .expect("TypeParamBound::get_type_param_bound: Not a TypeParamBound"),
-            param_map,
-        )
+    ) -> Result<Self, ReflectError> {
+        syn_to_type_param_bound(parse_str(type_param_bound)?, param_map)
     }
 
     pub(crate) fn clone_with_fresh_generics(&self, param_map: &ParamMap) -> Self {
-        match self {
-            Self::Lifetime(lifetime) => {
-                Self::Lifetime(lifetime.clone_with_fresh_generics(param_map))
-            }
-
-            Self::Trait(bound) => Self::Trait(TraitBound {
-                lifetimes: bound
-                    .lifetimes
-                    .iter()
-                    .map(|lifetime| lifetime.clone_with_fresh_generics(param_map))
-                    .collect(),
-
-                path: bound.path.clone_with_fresh_generics(param_map),
-            }),
-        }
+        FreshGenerics(param_map).fold_type_param_bound(self)
     }
 }
 
 impl Lifetime {
+    /// The name of the lifetime in `map`, including the apostrophe
+    pub fn ident<'a>(&self, map: &'a SynParamMap) -> Option<&'a str> {
+        map.iter()
+            .find(|(_, param)| **param == GenericParam::Lifetime(*self))
+            .map(|(name, _)| name)
+    }
+
     pub(crate) fn clone_with_fresh_generics(self, param_map: &ParamMap) -> Self {
-        param_map
-            .get(&GenericParam::Lifetime(self))
-            .and_then(|param| param.lifetime())
-            .unwrap()
+        FreshGenerics(param_map).fold_lifetime(self)
+    }
+}
+
+impl TypeParam {
+    /// The name of the type param in `map`
+    pub fn ident<'a>(&self, map: &'a SynParamMap) -> Option<&'a str> {
+        map.iter()
+            .find(|(_, param)| **param == GenericParam::Type(*self))
+            .map(|(name, _)| name)
+    }
+}
+
+impl ConstParam {
+    /// A new const param of type `ty`
+    pub(crate) fn new(ty: TypeNode) -> Self {
+        CONST_PARAMS.with_borrow_mut(|types| {
+            types.push(ty);
+            ConstParam(types.len() - 1)
+        })
+    }
+
+    /// The type of the const param, as in `usize` for `const N: usize`
+    pub fn ty(&self) -> TypeNode {
+        CONST_PARAMS.with_borrow(|types| types[self.0].clone())
+    }
+
+    /// The name of the const param in `map`
+    pub fn ident<'a>(&self, map: &'a SynParamMap) -> Option<&'a str> {
+        map.iter()
+            .find(|(_, param)| **param == GenericParam::Const(*self))
+            .map(|(name, _)| name)
     }
 }
 
@@ -227,76 +441,312 @@ impl GenericParam {
         }
     }
 
+    pub(crate) fn const_param(self) -> Option<ConstParam> {
+        match self {
+            Self::Const(const_param) => Some(const_param),
+            _ => None,
+        }
+    }
+
     pub(crate) fn get_fresh_param(self) -> Self {
         match self {
             Self::Type(type_param) => Self::Type(TYPE_PARAMS.count()),
 
             Self::Lifetime(lifetime) => Self::Lifetime(LIFETIMES.count()),
 
-            Self::Const(_const) => unimplemented!("GenericParam::get_fresh_param: Const"),
+            Self::Const(const_param) => Self::Const(ConstParam::new(const_param.ty())),
         }
     }
 }
 
 impl GenericConstraint {
     pub(crate) fn clone_with_fresh_generics(&self, param_map: &ParamMap) -> Self {
+        fold::fold_generic_constraint(&mut FreshGenerics(param_map), self)
+    }
+}
+
+impl GenericConstraint {
+    /// The params the constraint refers to, leaving out the lifetimes bound
+    /// by `for<...>`
+    fn free_params(&self) -> FreeParams {
+        let mut free = FreeParams::default();
         match self {
-            Self::Type(predicate) => Self::Type(PredicateType {
-                lifetimes: predicate
-                    .lifetimes
-                    .iter()
-                    .map(|lifetime| lifetime.clone_with_fresh_generics(param_map))
-                    .collect(),
-                bounded_ty: predicate.bounded_ty.clone_with_fresh_generics(param_map),
-                bounds: predicate
-                    .bounds
-                    .iter()
-                    .map(|bound| bound.clone_with_fresh_generics(param_map))
-                    .collect(),
-            }),
-            Self::Lifetime(lifetime_def) => Self::Lifetime(LifetimeDef {
-                lifetime: lifetime_def.lifetime.clone_with_fresh_generics(param_map),
-                bounds: lifetime_def
-                    .bounds
-                    .iter()
-                    .map(|lifetime| lifetime.clone_with_fresh_generics(param_map))
-                    .collect(),
-            }),
+            GenericConstraint::Type(predicate) => {
+                walk_type_node(&mut free, &predicate.bounded_ty);
+                walk_type_node(&mut free, &TypeNode::TraitObject(predicate.bounds.clone()));
+                for lifetime in &predicate.lifetimes {
+                    free.lifetimes.remove(lifetime);
+                }
+                for bound in &predicate.bounds {
+                    if let TypeParamBound::Trait(bound) = bound {
+                        for lifetime in &bound.lifetimes {
+                            free.lifetimes.remove(lifetime);
+                        }
+                    }
+                }
+            }
+            GenericConstraint::Lifetime(def) => {
+                for &lifetime in iter::once(&def.lifetime).chain(&def.bounds) {
+                    if lifetime != STATIC_LIFETIME {
+                        free.lifetimes.insert(lifetime);
+                    }
+                }
+            }
         }
+        free
     }
 }
 
 impl Generics {
-    pub fn set_generic_params(&mut self, params: &[&str]) {
-        let syn_params = params.iter().map(|param| parse_str(param).unwrap());
-        let (params, constraints, mut param_map) = syn_to_generic_params(syn_params);
+    /// The names of the params, as they were written in the source
+    pub fn param_map(&self) -> &SynParamMap {
+        &self.param_map
+    }
+
+    pub fn lifetime_params(&self) -> impl Iterator<Item = Lifetime> + '_ {
+        self.params.iter().filter_map(|param| param.lifetime())
+    }
+
+    pub fn type_params(&self) -> impl Iterator<Item = TypeParam> + '_ {
+        self.params.iter().filter_map(|param| param.type_param())
+    }
+
+    pub fn const_params(&self) -> impl Iterator<Item = ConstParam> + '_ {
+        self.params.iter().filter_map(|param| param.const_param())
+    }
+
+    /// Returns true if there are no generic params. Constraints are not
+    /// considered, since a where clause without params can not be emitted.
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty()
+    }
+
+    pub fn lifetime_count(&self) -> usize {
+        self.lifetime_params().count()
+    }
+
+    pub fn type_param_count(&self) -> usize {
+        self.type_params().count()
+    }
+
+    /// Returns true if the constraints already require `ty: bound`, so that
+    /// adding it again would be redundant.
+    ///
+    /// Trait bounds are only matched exactly; supertraits are not known to
+    /// reflect. A `ty: 'static` constraint implies every lifetime bound.
+    pub fn implies_bound(&self, ty: &TypeNode, bound: &TypeParamBound) -> bool {
+        self.constraints.iter().any(|constraint| match constraint {
+            GenericConstraint::Type(predicate) => {
+                predicate.bounded_ty == *ty
+                    && predicate.bounds.iter().any(|existing| {
+                        existing == bound
+                            || (*existing == TypeParamBound::Lifetime(STATIC_LIFETIME)
+                                && matches!(bound, TypeParamBound::Lifetime(_)))
+                    })
+            }
+            GenericConstraint::Lifetime(_) => false,
+        })
+    }
+
+    /// A copy of the generics with the constraint `param: bound` added,
+    /// unless it is already implied
+    pub fn with_additional_bound(&self, param: TypeParam, bound: TraitBound) -> Generics {
+        let mut generics = self.clone();
+        let ty = TypeNode::TypeParam(param);
+        let bound = TypeParamBound::Trait(bound);
+        if !generics.implies_bound(&ty, &bound) {
+            generics
+                .constraints
+                .push(GenericConstraint::Type(PredicateType {
+                    lifetimes: Vec::new(),
+                    bounded_ty: ty,
+                    bounds: vec![bound],
+                }));
+        }
+        generics
+    }
+
+    /// A copy of the generics with `bound` added to every type param, as in
+    /// `with_additional_bound`
+    pub fn with_bound_for_all_type_params(&self, bound: TraitBound) -> Generics {
+        self.type_params().fold(self.clone(), |generics, param| {
+            generics.with_additional_bound(param, bound.clone())
+        })
+    }
+
+    /// The generics restricted to the params that appear in `ty`, along with
+    /// the constraints that refer to no other params. This gives the
+    /// generics of an impl for `ty` that leaves out the params in scope that
+    /// the type does not use.
+    pub fn used_by(&self, ty: &TypeNode) -> Generics {
+        let used = ty.free_params();
+        let is_used = |param: &GenericParam| match param {
+            GenericParam::Type(type_param) => used.type_params.contains(type_param),
+            GenericParam::Lifetime(lifetime) => {
+                *lifetime == STATIC_LIFETIME || used.lifetimes.contains(lifetime)
+            }
+            GenericParam::Const(const_param) => used.const_params.contains(const_param),
+        };
+
+        let params = self.params.iter().copied().filter(is_used).collect();
+        let constraints = self
+            .constraints
+            .iter()
+            .filter(|constraint| {
+                let free = constraint.free_params();
+                free.type_params.is_subset(&used.type_params)
+                    && free.lifetimes.is_subset(&used.lifetimes)
+                    && free.const_params.is_subset(&used.const_params)
+            })
+            .cloned()
+            .collect();
+        let mut param_map = SynParamMap::new();
+        for (name, param) in self.param_map.iter() {
+            if is_used(param) {
+                param_map.insert(name.to_owned(), *param);
+            }
+        }
+
+        Generics {
+            params,
+            constraints,
+            param_map,
+        }
+    }
+
+    /// Gives `old` the name `new_name` in the param map. Params are referred
+    /// to by number rather than by name, so the params and constraints
+    /// themselves stay the same.
+    pub(crate) fn rename_param(
+        &mut self,
+        old: GenericParam,
+        new_name: &str,
+    ) -> Result<(), ReflectError> {
+        let generated = || Print::ref_cast(&old).to_token_stream().to_string();
+        let old_name = match old {
+            GenericParam::Lifetime(STATIC_LIFETIME) => {
+                return Err(ReflectError::Unsupported("renaming 'static"))
+            }
+            GenericParam::Lifetime(lifetime) => {
+                parse_str::<syn::Lifetime>(new_name)?;
+                lifetime.ident(&self.param_map)
+            }
+            GenericParam::Type(type_param) => {
+                parse_str::<syn::Ident>(new_name)?;
+                type_param.ident(&self.param_map)
+            }
+            GenericParam::Const(const_param) => {
+                parse_str::<syn::Ident>(new_name)?;
+                const_param.ident(&self.param_map)
+            }
+        };
+        let old_name = match (old_name, old) {
+            (Some(old_name), _) => old_name.to_owned(),
+            (None, GenericParam::Lifetime(_)) => {
+                return Err(ReflectError::NotALifetime(generated()))
+            }
+            (None, GenericParam::Type(_)) => return Err(ReflectError::NotATypeParam(generated())),
+            (None, GenericParam::Const(_)) => {
+                return Err(ReflectError::NotAConstParam(generated()))
+            }
+        };
+        match self.param_map.get(new_name) {
+            Some(param) if *param == old => return Ok(()),
+            Some(_) => return Err(ReflectError::DuplicateParam(new_name.to_owned())),
+            None => {}
+        }
+
+        self.param_map.map.remove(&old_name);
+        self.param_map.insert(new_name.to_owned(), old);
+        Ok(())
+    }
+
+    /// Gives the type param the name `new_name`, which must not already be
+    /// in use, as in `rename_lifetime`
+    pub fn rename_type_param(
+        &mut self,
+        old: TypeParam,
+        new_name: &str,
+    ) -> Result<(), ReflectError> {
+        self.rename_param(GenericParam::Type(old), new_name)
+    }
+
+    /// Gives the lifetime the name `new_name`, including the apostrophe, to
+    /// avoid a clash when combining generics. Fails if another param already
+    /// has the name.
+    pub fn rename_lifetime(&mut self, old: Lifetime, new_name: &str) -> Result<(), ReflectError> {
+        self.rename_param(GenericParam::Lifetime(old), new_name)
+    }
+
+    /// The generics with their params renumbered in the order they are
+    /// declared and the names of the params dropped. Generics that are the
+    /// same up to renaming their params have equal canonical forms, which
+    /// also hash the same, so they can be used as keys of a cache. The
+    /// numbers clash with those of other params, so a canonical form should
+    /// not be used to generate code. Const params are not renumbered.
+    pub fn canonical(&self) -> Generics {
+        Canonicalize::default().fold_generics(self)
+    }
+
+    /// Converts the generics back to syn. Params are emitted with the
+    /// generated names, such as `'__a1` and `__T0`, and all bounds end up in
+    /// the where clause.
+    pub fn to_syn(&self) -> syn::Generics {
+        let mut generics = if self.is_empty() {
+            syn::Generics::default()
+        } else {
+            // syn requires lifetimes to come before type params
+            let (lifetimes, type_params): (Vec<_>, Vec<_>) = self
+                .params
+                .iter()
+                .partition(|param| param.lifetime().is_some());
+            let lifetimes = lifetimes.into_iter().map(Print::ref_cast);
+            let type_params = type_params.into_iter().map(Print::ref_cast);
+            syn::parse2(quote!(<#(#lifetimes,)* #(#type_params),*>))
+                .expect("Generics::to_syn: params")
+        };
+        let where_clause = Print::ref_cast(WhereClause::ref_cast(self));
+        generics.where_clause =
+            syn::parse2(quote!(#where_clause)).expect("Generics::to_syn: where clause");
+        generics
+    }
+
+    pub fn set_generic_params(&mut self, params: &[&str]) -> Result<(), ReflectError> {
+        let syn_params = params
+            .iter()
+            .map(|param| parse_str(param))
+            .collect::<Result<Vec<_>, _>>()?;
+        let (params, constraints, mut param_map) = syn_to_generic_params(syn_params)?;
         self.params.extend(params);
         self.constraints.extend(constraints);
         self.param_map.append(&mut param_map);
+        Ok(())
     }
 
-    pub fn set_generic_constraints(&mut self, constraints: &[&str]) {
+    pub fn set_generic_constraints(&mut self, constraints: &[&str]) -> Result<(), ReflectError> {
         let syn_constraints = constraints
             .iter()
-            .map(|constraint| parse_str(constraint).unwrap());
+            .map(|constraint| parse_str(constraint))
+            .collect::<Result<Vec<_>, _>>()?;
         let constraints =
-            syn_where_predicates_to_generic_constraints(syn_constraints, &mut self.param_map);
+            syn_where_predicates_to_generic_constraints(syn_constraints, &mut self.param_map)?;
         self.constraints.extend(constraints);
+        Ok(())
     }
 
-    pub(crate) fn syn_to_generics(generics: syn::Generics) -> Self {
-        let (params, mut constraints, mut param_map) = syn_to_generic_params(generics.params);
+    pub(crate) fn syn_to_generics(generics: syn::Generics) -> Result<Self, ReflectError> {
+        let (params, mut constraints, mut param_map) = syn_to_generic_params(generics.params)?;
         if let Some(where_clause) = generics.where_clause {
             constraints.extend(syn_where_clause_to_generic_constraints(
                 where_clause,
                 &mut param_map,
-            ));
+            )?);
         };
-        Self {
+        Ok(Self {
             params,
             constraints,
             param_map,
-        }
+        })
     }
 
     pub(crate) fn clone_with_fresh_generics(&self) -> (Self, ParamMap) {
@@ -324,13 +774,9 @@ impl Generics {
     }
 }
 
-impl Default for Generics {
+impl Default for SynParamMap {
     fn default() -> Self {
-        Self {
-            params: Vec::new(),
-            constraints: Vec::new(),
-            param_map: SynParamMap::new(),
-        }
+        Self::new()
     }
 }
 
@@ -356,97 +802,102 @@ fn syn_to_bound_lifetimes(
     })
 }
 
-fn syn_where_clause_to_generic_constraints<'a>(
-    where_clause: WhereClause,
-    param_map: &'a mut SynParamMap,
-) -> impl Iterator<Item = GenericConstraint> + 'a {
-    syn_where_predicates_to_generic_constraints(where_clause.predicates.into_iter(), param_map)
+fn syn_where_clause_to_generic_constraints(
+    where_clause: syn::WhereClause,
+    param_map: &mut SynParamMap,
+) -> Result<Vec<GenericConstraint>, ReflectError> {
+    syn_where_predicates_to_generic_constraints(where_clause.predicates, param_map)
 }
 
-pub(crate) fn syn_where_predicates_to_generic_constraints<'a, I>(
+pub(crate) fn syn_where_predicates_to_generic_constraints<I>(
     where_predicates: I,
-    param_map: &'a mut SynParamMap,
-) -> impl Iterator<Item = GenericConstraint> + 'a
+    param_map: &mut SynParamMap,
+) -> Result<Vec<GenericConstraint>, ReflectError>
 where
-    I: Iterator<Item = WherePredicate> + 'a,
+    I: IntoIterator<Item = WherePredicate>,
 {
-    where_predicates.map(move |predicate| match predicate {
-        WherePredicate::Type(syn::PredicateType {
-            lifetimes,
-            bounded_ty,
-            bounds,
-            ..
-        }) => GenericConstraint::Type(PredicateType {
-            lifetimes: syn_to_bound_lifetimes(lifetimes, param_map),
-            bounded_ty: TypeNode::syn_to_type(bounded_ty, param_map),
-            bounds: syn_to_type_param_bounds(bounds, param_map).collect(),
-        }),
-        WherePredicate::Lifetime(PredicateLifetime {
-            lifetime, bounds, ..
-        }) => GenericConstraint::Lifetime(LifetimeDef {
-            lifetime: param_map.get_lifetime(&lifetime.to_string()),
-            bounds: bounds
-                .into_iter()
-                .map(|lifetime| param_map.get_lifetime(&lifetime.to_string()))
-                .collect(),
-        }),
-        WherePredicate::Eq(_eq) => unimplemented!("Generics::syn_to_generics: Eq"),
-    })
+    where_predicates
+        .into_iter()
+        .map(|predicate| match predicate {
+            WherePredicate::Type(syn::PredicateType {
+                lifetimes,
+                bounded_ty,
+                bounds,
+                ..
+            }) => Ok(GenericConstraint::Type(PredicateType {
+                lifetimes: syn_to_bound_lifetimes(lifetimes, param_map),
+                bounded_ty: TypeNode::syn_to_type(bounded_ty, param_map)?,
+                bounds: syn_to_type_param_bounds(bounds, param_map)?,
+            })),
+            WherePredicate::Lifetime(PredicateLifetime {
+                lifetime, bounds, ..
+            }) => Ok(GenericConstraint::Lifetime(LifetimeDef {
+                lifetime: param_map.get_lifetime(&lifetime.to_string())?,
+                bounds: bounds
+                    .into_iter()
+                    .map(|lifetime| param_map.get_lifetime(&lifetime.to_string()))
+                    .collect::<Result<_, _>>()?,
+            })),
+            WherePredicate::Eq(_eq) => Err(ReflectError::Unsupported("equality where predicates")),
+        })
+        .collect()
 }
 
 pub(crate) fn syn_to_generic_params<T>(
     params: T,
-) -> (Vec<GenericParam>, Vec<GenericConstraint>, SynParamMap)
+) -> Result<(Vec<GenericParam>, Vec<GenericConstraint>, SynParamMap), ReflectError>
 where
     T: IntoIterator<Item = syn::GenericParam>,
 {
     let mut param_map = SynParamMap::new();
     let mut constraints = Vec::new();
     let params: Vec<_> = params.into_iter().collect();
-    params
-        .iter()
-        .for_each(|param| param_mapping(param, &mut param_map));
+    for param in &params {
+        param_mapping(param, &mut param_map)?;
+    }
     let params = params
         .into_iter()
         .map(|param| match param {
             syn::GenericParam::Type(syn::TypeParam { ident, bounds, .. }) => {
-                let &param = param_map.get(&ident.to_string()).unwrap();
+                let type_param = param_map.get_type_param(&ident.to_string())?;
                 if !bounds.is_empty() {
                     constraints.push(GenericConstraint::Type(PredicateType {
                         lifetimes: Vec::new(),
-                        bounded_ty: TypeNode::TypeParam(
-                            param
-                                .type_param()
-                                .expect("syn_to_generic_params: Not a type param ref"),
-                        ),
-                        bounds: syn_to_type_param_bounds(bounds, &mut param_map).collect(),
+                        bounded_ty: TypeNode::TypeParam(type_param),
+                        bounds: syn_to_type_param_bounds(bounds, &mut param_map)?,
                     }));
                 }
-                param
+                Ok(GenericParam::Type(type_param))
             }
             syn::GenericParam::Lifetime(syn::LifetimeDef {
                 lifetime, bounds, ..
             }) => {
-                let lifetime = lifetime.to_string();
-                let &param = param_map.get(&lifetime).unwrap();
+                let lifetime = param_map.get_lifetime(&lifetime.to_string())?;
                 if !bounds.is_empty() {
                     constraints.push(GenericConstraint::Lifetime(LifetimeDef {
-                        lifetime: param_map.get_lifetime(&lifetime),
+                        lifetime,
                         bounds: bounds
                             .into_iter()
                             .map(|lifetime| param_map.get_lifetime(&lifetime.to_string()))
-                            .collect(),
+                            .collect::<Result<_, _>>()?,
                     }));
                 }
-                param
+                Ok(GenericParam::Lifetime(lifetime))
             }
-            syn::GenericParam::Const(_const) => unimplemented!("Generics::syn_to_generics: Const"),
+            // The type of the param was parsed along with its name, and a
+            // default value only matters to the users of the type
+            syn::GenericParam::Const(syn::ConstParam { ident, .. }) => Ok(GenericParam::Const(
+                param_map.get_const_param(&ident.to_string())?,
+            )),
         })
-        .collect();
-    (params, constraints, param_map)
+        .collect::<Result<_, ReflectError>>()?;
+    Ok((params, constraints, param_map))
 }
 
-pub(crate) fn param_mapping(param: &syn::GenericParam, param_map: &mut SynParamMap) {
+pub(crate) fn param_mapping(
+    param: &syn::GenericParam,
+    param_map: &mut SynParamMap,
+) -> Result<(), ReflectError> {
     match &param {
         syn::GenericParam::Type(syn::TypeParam { ident, .. }) => {
             let param = GenericParam::Type(TYPE_PARAMS.count());
@@ -456,48 +907,98 @@ pub(crate) fn param_mapping(param: &syn::GenericParam, param_map: &mut SynParamM
             let param = GenericParam::Lifetime(LIFETIMES.count());
             param_map.insert(lifetime.to_string(), param);
         }
-        syn::GenericParam::Const(_const) => unimplemented!("Generics::param_mapping: Const"),
+        syn::GenericParam::Const(syn::ConstParam { ident, ty, .. }) => {
+            let ty = TypeNode::syn_to_type(ty.clone(), param_map)?;
+            let param = GenericParam::Const(ConstParam::new(ty));
+            param_map.insert(ident.to_string(), param);
+        }
     }
+    Ok(())
 }
 
-pub(crate) fn syn_to_type_param_bounds<'a, T>(
+pub(crate) fn syn_to_type_param_bounds<T>(
     bounds: T,
-    param_map: &'a mut SynParamMap,
-) -> impl Iterator<Item = TypeParamBound> + 'a
+    param_map: &mut SynParamMap,
+) -> Result<Vec<TypeParamBound>, ReflectError>
 where
-    T: IntoIterator<Item = syn::TypeParamBound> + 'a,
+    T: IntoIterator<Item = syn::TypeParamBound>,
 {
     bounds
         .into_iter()
-        .map(move |type_param_bound| syn_to_type_param_bound(type_param_bound, param_map))
+        .map(|type_param_bound| syn_to_type_param_bound(type_param_bound, param_map))
+        .collect()
 }
 
 pub(crate) fn syn_to_type_param_bound(
     type_param_bound: syn::TypeParamBound,
     param_map: &mut SynParamMap,
-) -> TypeParamBound {
-    match type_param_bound {
+) -> Result<TypeParamBound, ReflectError> {
+    Ok(match type_param_bound {
         syn::TypeParamBound::Trait(syn::TraitBound {
             lifetimes, path, ..
         }) => TypeParamBound::Trait(TraitBound {
             lifetimes: syn_to_bound_lifetimes(lifetimes, param_map),
-            path: Path::syn_to_path(path, param_map),
+            path: Path::syn_to_path(path, param_map)?,
         }),
         syn::TypeParamBound::Lifetime(lifetime) => {
-            TypeParamBound::Lifetime(param_map.get_lifetime(&lifetime.to_string()))
+            TypeParamBound::Lifetime(param_map.get_lifetime(&lifetime.to_string())?)
         }
-    }
+    })
 }
 
 impl GenericArguments {
-    pub(crate) fn clone_with_fresh_generics(&self, param_map: &ParamMap) -> Self {
-        Self {
-            args: self
-                .args
-                .iter()
-                .map(|arg| arg.clone_with_fresh_generics(param_map))
-                .collect(),
-        }
+    /// The type arguments, in order
+    pub fn types(&self) -> impl Iterator<Item = &TypeNode> {
+        self.args.iter().filter_map(|arg| match arg {
+            GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        })
+    }
+
+    /// The lifetime arguments, in order
+    pub fn lifetimes(&self) -> impl Iterator<Item = Lifetime> + '_ {
+        self.args.iter().filter_map(|arg| match arg {
+            GenericArgument::Lifetime(lifetime) => Some(*lifetime),
+            _ => None,
+        })
+    }
+
+    /// The associated type bindings, in order
+    pub fn bindings(&self) -> impl Iterator<Item = &Binding> {
+        self.args.iter().filter_map(|arg| match arg {
+            GenericArgument::Binding(binding) => Some(binding),
+            _ => None,
+        })
+    }
+
+    /// An empty argument list, to be filled in with the `push` functions when
+    /// building a path such as `Vec<T>` or `Iterator<Item = T>`
+    pub fn new() -> Self {
+        GenericArguments { args: Vec::new() }
+    }
+
+    pub fn push_type(&mut self, ty: TypeNode) {
+        self.args.push(GenericArgument::Type(ty));
+    }
+
+    pub fn push_lifetime(&mut self, lifetime: Lifetime) {
+        self.args.push(GenericArgument::Lifetime(lifetime));
+    }
+
+    /// Appends an associated type binding, as in `Item = T`
+    pub fn push_binding(&mut self, ident: Ident, ty: TypeNode) {
+        self.args
+            .push(GenericArgument::Binding(Binding { ident, ty }));
+    }
+}
+
+impl Binding {
+    pub fn ident(&self) -> &Ident {
+        &self.ident
+    }
+
+    pub fn ty(&self) -> &TypeNode {
+        &self.ty
     }
 }
 
@@ -505,51 +1006,87 @@ impl GenericArgument {
     pub(crate) fn syn_to_generic_argument(
         arg: syn::GenericArgument,
         param_map: &mut SynParamMap,
-    ) -> Self {
-        match arg {
-            syn::GenericArgument::Type(ty) => Self::Type(TypeNode::syn_to_type(ty, param_map)),
+    ) -> Result<Self, ReflectError> {
+        Ok(match arg {
+            // A const param as an argument parses as a type, as in `Buf<N>`
+            syn::GenericArgument::Type(ty) => match const_param_named(&ty, param_map) {
+                Some(const_param) => Self::Const(Expr::Param(const_param)),
+                None => Self::Type(TypeNode::syn_to_type(ty, param_map)?),
+            },
 
             syn::GenericArgument::Lifetime(lifetime) => {
-                Self::Lifetime(param_map.get_lifetime(&lifetime.to_string()))
+                Self::Lifetime(param_map.get_lifetime(&lifetime.to_string())?)
             }
 
             syn::GenericArgument::Binding(binding) => Self::Binding(Binding {
                 ident: Ident::from(binding.ident),
-                ty: TypeNode::syn_to_type(binding.ty, param_map),
+                ty: TypeNode::syn_to_type(binding.ty, param_map)?,
             }),
 
             syn::GenericArgument::Constraint(constraint) => Self::Constraint(Constraint {
                 ident: Ident::from(constraint.ident),
-                bounds: syn_to_type_param_bounds(constraint.bounds, param_map).collect(),
+                bounds: syn_to_type_param_bounds(constraint.bounds, param_map)?,
             }),
 
-            syn::GenericArgument::Const(_expr) => {
-                unimplemented!("GenericArguments::syn_to_generic_arguments: Const")
-            }
-        }
+            syn::GenericArgument::Const(expr) => Self::Const(Expr::syn_to_expr(expr, param_map)),
+        })
     }
+}
 
-    pub(crate) fn clone_with_fresh_generics(&self, param_map: &ParamMap) -> Self {
-        match self {
-            Self::Type(ty) => Self::Type(ty.clone_with_fresh_generics(param_map)),
-            Self::Lifetime(lifetime) => {
-                Self::Lifetime(lifetime.clone_with_fresh_generics(param_map))
+impl Expr {
+    fn syn_to_expr(expr: syn::Expr, param_map: &SynParamMap) -> Self {
+        let mut tokens = expr.to_token_stream();
+        // `{ N }` is how a lone param is written where it would otherwise
+        // parse as a type
+        if let [TokenTree::Group(group)] = &*tokens.clone().into_iter().collect::<Vec<_>>() {
+            if group.delimiter() == Delimiter::Brace {
+                tokens = group.stream();
             }
-            Self::Binding(binding) => Self::Binding(Binding {
-                ident: binding.ident.clone(),
-                ty: binding.ty.clone_with_fresh_generics(param_map),
-            }),
-            Self::Constraint(constraint) => Self::Constraint(Constraint {
-                ident: constraint.ident.clone(),
-                bounds: constraint
-                    .bounds
-                    .iter()
-                    .map(|bound| bound.clone_with_fresh_generics(param_map))
-                    .collect(),
-            }),
-            Self::Const(expr) => {
-                unimplemented!("GenericArgument::clone_with_fresh_generics: const expr")
+        }
+        if let [TokenTree::Ident(ident)] = &*tokens.into_iter().collect::<Vec<_>>() {
+            if let Ok(const_param) = param_map.get_const_param(&ident.to_string()) {
+                return Expr::Param(const_param);
             }
         }
+        let tokens = print::with_generated_names(expr.to_token_stream(), param_map);
+        Expr::Value(syn::Expr::Verbatim(tokens))
+    }
+}
+
+/// The const param that `ty` names, if it is a single identifier naming one
+fn const_param_named(ty: &syn::Type, param_map: &SynParamMap) -> Option<ConstParam> {
+    match ty {
+        syn::Type::Path(syn::TypePath { qself: None, path }) => {
+            let ident = path.get_ident()?;
+            param_map.get_const_param(&ident.to_string()).ok()
+        }
+        _ => None,
+    }
+}
+
+#[test]
+fn test_compose_param_maps() {
+    let generics = Generics {
+        params: vec![
+            GenericParam::Lifetime(Lifetime(100)),
+            GenericParam::Type(TypeParam(100)),
+        ],
+        ..Generics::default()
+    };
+    let (renamed, rename) = generics.clone_with_fresh_generics();
+    let (_, substitute) = renamed.clone_with_fresh_generics();
+
+    let composed = rename.compose(&substitute);
+    for param in &generics.params {
+        let expected = substitute.get(rename.get(param).unwrap()).unwrap();
+        assert_ne!(expected, param);
+        assert_eq!(composed.get(param), Some(expected));
+    }
+
+    let identity = ParamMap::identity_for(&generics);
+    for param in &generics.params {
+        assert_eq!(identity.get(param), Some(param));
+        assert_eq!(identity.compose(&rename).get(param), rename.get(param));
+        assert_eq!(rename.compose(&identity).get(param), rename.get(param));
     }
 }
@@ -1,17 +1,33 @@
-use crate::{Invoke, Lifetime, MacroInvoke, Push, TypeParam, TypedIndex, ValueNode};
+use crate::{
+    Invoke, Item, Lifetime, MacroInvoke, Path, Push, TypeNode, TypeParam, TypedIndex, ValueNode,
+};
 use std::cell::{Cell, RefCell};
 use std::thread::LocalKey;
 
+// The state of a derive is kept per thread rather than in statics shared
+// between threads. A macro expansion runs start to finish on one thread, so
+// concurrent expansions each see their own arenas and counters and no
+// synchronization is needed. The signatures cached by `library!` are thread
+// local as well, which keeps their type params and lifetimes consistent with
+// these counters.
 thread_local! {
-    pub(crate) static VALUES: RefCell<Vec<ValueNode>> = RefCell::new(Vec::new());
-    pub(crate) static INVOKES: RefCell<Vec<Invoke>> = RefCell::new(Vec::new());
-    pub(crate) static MACROS: RefCell<Vec<MacroInvoke>> = RefCell::new(Vec::new());
-    pub(crate) static TYPE_PARAMS: Cell<usize> = Cell::new(0);
-    pub(crate) static LIFETIMES: Cell<usize> = Cell::new(1);
+    pub(crate) static VALUES: RefCell<Vec<ValueNode>> = const { RefCell::new(Vec::new()) };
+    pub(crate) static INVOKES: RefCell<Vec<Invoke>> = const { RefCell::new(Vec::new()) };
+    pub(crate) static MACROS: RefCell<Vec<MacroInvoke>> = const { RefCell::new(Vec::new()) };
+    pub(crate) static MODULE_ITEMS: RefCell<Vec<(Path, Item)>> = const { RefCell::new(Vec::new()) };
+    pub(crate) static TYPE_PARAMS: Cell<usize> = const { Cell::new(FIRST_TYPE_PARAM) };
+    pub(crate) static LIFETIMES: Cell<usize> = const { Cell::new(FIRST_LIFETIME) };
+    // The type of each const param, indexed by the number of the param. Like
+    // the counters, it outlives a single derive.
+    pub(crate) static CONST_PARAMS: RefCell<Vec<TypeNode>> = const { RefCell::new(Vec::new()) };
 }
 
 pub(crate) const STATIC_LIFETIME: Lifetime = Lifetime(0);
 
+const FIRST_TYPE_PARAM: usize = 0;
+// Lifetime 0 is 'static
+const FIRST_LIFETIME: usize = 1;
+
 pub(crate) trait GlobalBorrow<T> {
     fn with_borrow<R, F>(&'static self, f: F) -> R
     where
@@ -20,6 +36,9 @@ pub(crate) trait GlobalBorrow<T> {
     fn with_borrow_mut<R, F>(&'static self, f: F) -> R
     where
         F: FnOnce(&mut Vec<T>) -> R;
+
+    /// Removes every element
+    fn reset(&'static self);
 }
 
 impl<T> GlobalBorrow<T> for LocalKey<RefCell<Vec<T>>> {
@@ -36,6 +55,10 @@ impl<T> GlobalBorrow<T> for LocalKey<RefCell<Vec<T>>> {
     {
         self.with(|data| f(&mut *data.borrow_mut()))
     }
+
+    fn reset(&'static self) {
+        self.with(|data| data.borrow_mut().clear());
+    }
 }
 
 pub(crate) trait GlobalPush {
@@ -83,7 +106,8 @@ pub(crate) fn clear() {
     // It's not safe to reset TYPE_PARAMS and LIFETIMES as this might
     // interfere with cached values in generic parameters in functions in the
     // reflect! macro
-    VALUES.with(|data| data.borrow_mut().clear());
-    INVOKES.with(|data| data.borrow_mut().clear());
-    MACROS.with(|data| data.borrow_mut().clear());
+    GlobalBorrow::reset(&VALUES);
+    GlobalBorrow::reset(&INVOKES);
+    GlobalBorrow::reset(&MACROS);
+    GlobalBorrow::reset(&MODULE_ITEMS);
 }
@@ -1,31 +1,139 @@
 use proc_macro2::{Span, TokenStream};
 use quote::ToTokens;
 use std::fmt::{self, Display};
+use std::hash::{Hash, Hasher};
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct Ident(String);
+// proc_macro2 does not expose its list of keywords
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "crate",
+    "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in",
+    "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref",
+    "return", "self", "Self", "static", "struct", "super", "trait", "true", "try", "type",
+    "typeof", "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+// The keywords that `r#` can not be put in front of
+const NOT_RAW: &[&str] = &["self", "Self", "crate", "super"];
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Ident {
+    name: String,
+    raw: bool,
+}
 
 impl Ident {
     pub fn new<T: Display>(ident: T) -> Self {
-        Self(ident.to_string())
+        Self {
+            name: ident.to_string(),
+            raw: false,
+        }
+    }
+
+    /// A raw identifier, such as `r#type`. `name` is given without the `r#`
+    /// prefix.
+    ///
+    /// # Panics
+    ///
+    /// If `name` is `self`, `Self`, `crate` or `super`, which can not be raw
+    /// identifiers
+    pub fn new_raw(name: &str) -> Self {
+        if NOT_RAW.contains(&name) {
+            panic!("Ident::new_raw: `{}` can not be a raw identifier", name);
+        }
+        Self {
+            name: name.to_owned(),
+            raw: true,
+        }
+    }
+
+    /// Parses an ident, which is raw if it starts with `r#`
+    ///
+    /// # Panics
+    ///
+    /// If `s` is a keyword without the `r#` prefix, or one of the keywords
+    /// that can not be raw with it
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Self {
+        match s.strip_prefix("r#") {
+            Some(name) => Self::new_raw(name),
+            None => {
+                let ident = Self::new(s);
+                if ident.is_keyword() {
+                    panic!("Ident::from_str: `{}` is a keyword", s);
+                }
+                ident
+            }
+        }
+    }
+
+    /// The name, without the `r#` prefix of a raw ident
+    pub fn as_str(&self) -> &str {
+        &self.name
+    }
+
+    /// Whether the name is a strict or reserved keyword of Rust 2018. Raw
+    /// idents are still checked by name, since they are only usable because
+    /// of the prefix.
+    pub fn is_keyword(&self) -> bool {
+        KEYWORDS.contains(&self.name.as_str())
+    }
+
+    fn to_proc_macro2(&self) -> proc_macro2::Ident {
+        if self.raw {
+            proc_macro2::Ident::new_raw(&self.name, Span::call_site())
+        } else {
+            proc_macro2::Ident::new(&self.name, Span::call_site())
+        }
+    }
+}
+
+// The raw flag is only hashed for raw idents, so that plain idents hash as
+// their names alone and keep the order of hashed collections, such as the
+// where clauses of an impl
+impl Hash for Ident {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+        if self.raw {
+            self.raw.hash(state);
+        }
+    }
+}
+
+// Compares with the ident as written, so `r#type` only equals "r#type"
+impl PartialEq<str> for Ident {
+    fn eq(&self, other: &str) -> bool {
+        match other.strip_prefix("r#") {
+            Some(name) => self.raw && self.name == name,
+            None => !self.raw && self.name == other,
+        }
+    }
+}
+
+impl PartialEq<&str> for Ident {
+    fn eq(&self, other: &&str) -> bool {
+        *self == **other
     }
 }
 
 impl Display for Ident {
     #[inline]
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        proc_macro2::Ident::new(&self.0, Span::call_site()).fmt(f)
+        self.to_proc_macro2().fmt(f)
     }
 }
 
 impl From<proc_macro2::Ident> for Ident {
     fn from(item: proc_macro2::Ident) -> Self {
-        Self(item.to_string())
+        let item = item.to_string();
+        match item.strip_prefix("r#") {
+            Some(name) => Self::new_raw(name),
+            None => Self::new(item),
+        }
     }
 }
 
 impl ToTokens for Ident {
     fn to_tokens(&self, tokens: &mut TokenStream) {
-        proc_macro2::Ident::new(&self.0, Span::call_site()).to_tokens(tokens);
+        self.to_proc_macro2().to_tokens(tokens);
     }
 }
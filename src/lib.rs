@@ -149,6 +149,9 @@
 //! # fn main() {}
 //! ```
 //!
+//! The `#[reflect::derive_macro(MyDebug)]` attribute writes the same entry point
+//! for a function taking the `reflect::Execution`.
+//!
 //! The following looks like a function that does runtime reflection. It receives
 //! function arguments which have the type `reflect::Value` and can pass them
 //! around, pull out their fields, inspect attributes, invoke methods, and so forth.
@@ -319,6 +322,7 @@
 #![allow(
     clippy::needless_pass_by_value,
     clippy::new_without_default,
+    clippy::only_used_in_recursion,
     clippy::large_enum_variant,
     clippy::trivially_copy_pass_by_ref
 )]
@@ -326,14 +330,18 @@
 #[doc(hidden)]
 pub use reflect_internal::*;
 
+pub mod attr;
 pub mod runtime;
+#[cfg(feature = "testing")]
+pub mod testing;
 
-mod attr;
 mod compiler;
 mod data;
 mod derive;
+mod error;
 mod execution;
 mod field;
+mod fold;
 mod function;
 mod generics;
 mod global_data;
@@ -345,10 +353,12 @@ mod node;
 mod parent;
 mod path;
 mod print;
+mod repr;
 mod signature;
 mod trait_inference;
 mod ty;
 mod value;
+mod visit;
 mod wip;
 
 pub use crate::data::{
@@ -356,30 +366,41 @@ pub use crate::data::{
     UnitVariant, Variant,
 };
 pub use crate::derive::derive;
+pub use crate::error::{ReflectError, ZipError};
 pub use crate::execution::Execution;
-pub use crate::field::Field;
-pub use crate::function::Function;
-pub use crate::generics::{Generics, SynParamMap};
-pub use crate::module::Module;
-pub use crate::parent::{Parent, ParentBuilder, ParentKind};
-pub use crate::path::Path;
+pub use crate::field::{Field, Visibility};
+pub use crate::fold::{
+    fold_data_structure, fold_generic_arguments, fold_path, fold_type, fold_type_param_bound,
+    DefaultTypeFolder, TypeFolder,
+};
+pub use crate::function::{Function, FunctionBuilder};
+pub use crate::generics::{
+    Binding, ConstParam, GenericArguments, GenericParam, Generics, Lifetime, SynParamMap,
+    TraitBound, TypeParam, TypeParamBound,
+};
+pub use crate::ident::Ident;
+pub use crate::module::{Item, Module};
+pub use crate::parent::{ImplBuilder, Parent, ParentBuilder, ParentKind};
+pub use crate::path::{Path, PathSegment};
+pub use crate::repr::{IntRepr, Repr};
 pub use crate::signature::Signature;
-pub use crate::ty::TypeNode;
-pub use crate::value::Value;
+pub use crate::ty::{DataStructure, TypeNode};
+pub use crate::value::{Value, VariantPayload};
+pub use crate::visit::{walk_type_node, TypeVisitor};
 pub use crate::wip::{MakeFunction, MakeImpl};
+pub use reflect_internal::derive_macro;
 
 use crate::compiler::{CompleteFunction, CompleteImpl, Program};
 use crate::execution::Tracker;
 use crate::field::Accessor;
 use crate::generics::{
-    GenericArgument, GenericArguments, GenericConstraint, GenericParam, Lifetime, LifetimeDef,
-    ParamMap, PredicateType, TraitBound, TypeParam, TypeParamBound,
+    FreshGenerics, GenericArgument, GenericConstraint, LifetimeDef, ParamMap, PredicateType,
+    WhereClause,
 };
 use crate::global_data::{
-    GlobalBorrow, GlobalCounter, GlobalPush, INVOKES, LIFETIMES, MACROS, STATIC_LIFETIME,
-    TYPE_PARAMS, VALUES,
+    GlobalCounter, GlobalPush, CONST_PARAMS, INVOKES, LIFETIMES, MACROS, MODULE_ITEMS,
+    STATIC_LIFETIME, TYPE_PARAMS, VALUES,
 };
-use crate::ident::Ident;
 use crate::index::{InvokeRef, MacroInvokeRef, Push, TypeEqualitySetRef, TypedIndex, ValueRef};
 use crate::node::ValueNode;
 use crate::path::{AngleBracketedGenericArguments, PathArguments, SimplePath};
@@ -387,5 +408,4 @@ use crate::print::Print;
 use crate::runtime::{RuntimeFunction, RuntimeTrait, RuntimeType};
 use crate::signature::Receiver;
 use crate::trait_inference::{TraitInferenceResult, TypeEqualitySet};
-use crate::ty::DataStructure;
 use crate::wip::{Invoke, MacroInvoke, WipFunction, WipImpl};
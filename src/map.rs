@@ -1,7 +1,7 @@
 use crate::{
-    Data, Enum, Field, Struct, StructStruct, StructVariant, TupleStruct, TupleVariant, Variant,
+    Data, Enum, Field, Struct, StructStruct, StructVariant, TupleStruct, TupleVariant, TypeNode,
+    Variant, ZipError,
 };
-use std::marker::PhantomData;
 
 impl<T> Data<T> {
     pub(crate) fn map<F, R>(self, f: F) -> Data<R>
@@ -13,9 +13,75 @@ impl<T> Data<T> {
             Data::Enum(data) => Data::Enum(data.map(f)),
         }
     }
+
+    /// Like `map`, but stops at the first field for which `f` fails and
+    /// returns its error
+    pub fn try_map<F, R, E>(self, f: F) -> Result<Data<R>, E>
+    where
+        F: FnMut(Field<T>) -> Result<R, E>,
+    {
+        Ok(match self {
+            Data::Struct(data) => Data::Struct(data.try_map(f)?),
+            Data::Enum(data) => Data::Enum(data.try_map(f)?),
+        })
+    }
+}
+
+impl<T> Data<T> {
+    /// The same data keeping only the fields for which `f` returns `Some`,
+    /// with the value it returns. The fields of each variant are filtered on
+    /// their own. Kept fields of a tuple struct or variant keep their
+    /// original index.
+    pub fn filter_fields<F, U>(&self, f: F) -> Data<U>
+    where
+        F: Fn(&Field<T>) -> Option<U>,
+    {
+        match self {
+            Data::Struct(data) => Data::Struct(data.filter_fields(&f)),
+            Data::Enum(data) => Data::Enum(Enum {
+                variants: data
+                    .variants
+                    .iter()
+                    .map(|variant| variant.filter_fields(&f))
+                    .collect(),
+                attrs: data.attrs.clone(),
+                scrutinee: data.scrutinee,
+            }),
+        }
+    }
+}
+
+impl Data<TypeNode> {
+    /// The same data with `f` applied to the type of every field. Field
+    /// names, variants and attributes are kept.
+    pub fn map_type<F>(&self, f: F) -> Data<TypeNode>
+    where
+        F: Fn(&TypeNode) -> TypeNode,
+    {
+        self.clone().map(|field| f(&field.element))
+    }
 }
 
 impl<T> Struct<T> {
+    fn filter_fields<F, U>(&self, f: &F) -> Struct<U>
+    where
+        F: Fn(&Field<T>) -> Option<U>,
+    {
+        match self {
+            Struct::Unit(s) => Struct::Unit(s.clone()),
+            Struct::Tuple(s) => Struct::Tuple(TupleStruct {
+                ident: s.ident.clone(),
+                fields: filter_fields(&s.fields, f),
+                attrs: s.attrs.clone(),
+            }),
+            Struct::Struct(s) => Struct::Struct(StructStruct {
+                ident: s.ident.clone(),
+                fields: filter_fields(&s.fields, f),
+                attrs: s.attrs.clone(),
+            }),
+        }
+    }
+
     pub(crate) fn map<F, R>(self, f: F) -> Struct<R>
     where
         F: FnMut(Field<T>) -> R,
@@ -26,6 +92,17 @@ impl<T> Struct<T> {
             Struct::Struct(s) => Struct::Struct(s.map(f)),
         }
     }
+
+    pub(crate) fn try_map<F, R, E>(self, f: F) -> Result<Struct<R>, E>
+    where
+        F: FnMut(Field<T>) -> Result<R, E>,
+    {
+        Ok(match self {
+            Struct::Unit(s) => Struct::Unit(s),
+            Struct::Tuple(s) => Struct::Tuple(s.try_map(f)?),
+            Struct::Struct(s) => Struct::Struct(s.try_map(f)?),
+        })
+    }
 }
 
 impl<T> TupleStruct<T> {
@@ -34,6 +111,7 @@ impl<T> TupleStruct<T> {
         F: FnMut(Field<T>) -> R,
     {
         TupleStruct {
+            ident: self.ident,
             fields: self
                 .fields
                 .into_iter()
@@ -42,6 +120,21 @@ impl<T> TupleStruct<T> {
             attrs: self.attrs,
         }
     }
+
+    pub(crate) fn try_map<F, R, E>(self, mut f: F) -> Result<TupleStruct<R>, E>
+    where
+        F: FnMut(Field<T>) -> Result<R, E>,
+    {
+        Ok(TupleStruct {
+            ident: self.ident,
+            fields: self
+                .fields
+                .into_iter()
+                .map(|field| field.try_map(&mut f))
+                .collect::<Result<_, _>>()?,
+            attrs: self.attrs,
+        })
+    }
 }
 
 impl<T> StructStruct<T> {
@@ -50,6 +143,7 @@ impl<T> StructStruct<T> {
         F: FnMut(Field<T>) -> R,
     {
         StructStruct {
+            ident: self.ident,
             fields: self
                 .fields
                 .into_iter()
@@ -58,6 +152,21 @@ impl<T> StructStruct<T> {
             attrs: self.attrs,
         }
     }
+
+    pub(crate) fn try_map<F, R, E>(self, mut f: F) -> Result<StructStruct<R>, E>
+    where
+        F: FnMut(Field<T>) -> Result<R, E>,
+    {
+        Ok(StructStruct {
+            ident: self.ident,
+            fields: self
+                .fields
+                .into_iter()
+                .map(|field| field.try_map(&mut f))
+                .collect::<Result<_, _>>()?,
+            attrs: self.attrs,
+        })
+    }
 }
 
 impl<T> Field<T> {
@@ -67,13 +176,31 @@ impl<T> Field<T> {
     {
         let accessor = self.accessor.clone();
         let attrs = self.attrs.clone();
+        let vis = self.vis.clone();
 
         Field {
             attrs,
             accessor,
             element: f(self),
+            vis,
         }
     }
+
+    pub(crate) fn try_map<F, R, E>(self, f: F) -> Result<Field<R>, E>
+    where
+        F: FnOnce(Field<T>) -> Result<R, E>,
+    {
+        let accessor = self.accessor.clone();
+        let attrs = self.attrs.clone();
+        let vis = self.vis.clone();
+
+        Ok(Field {
+            attrs,
+            accessor,
+            element: f(self)?,
+            vis,
+        })
+    }
 }
 
 impl<T> Enum<T> {
@@ -84,11 +211,48 @@ impl<T> Enum<T> {
         Enum {
             variants: self.variants.into_iter().map(|v| v.map(&mut f)).collect(),
             attrs: self.attrs,
+            scrutinee: self.scrutinee,
         }
     }
+
+    pub(crate) fn try_map<F, R, E>(self, mut f: F) -> Result<Enum<R>, E>
+    where
+        F: FnMut(Field<T>) -> Result<R, E>,
+    {
+        Ok(Enum {
+            variants: self
+                .variants
+                .into_iter()
+                .map(|v| v.try_map(&mut f))
+                .collect::<Result<_, _>>()?,
+            attrs: self.attrs,
+            scrutinee: self.scrutinee,
+        })
+    }
 }
 
 impl<T> Variant<T> {
+    fn filter_fields<F, U>(&self, f: &F) -> Variant<U>
+    where
+        F: Fn(&Field<T>) -> Option<U>,
+    {
+        match self {
+            Variant::Unit(v) => Variant::Unit(v.clone()),
+            Variant::Tuple(v) => Variant::Tuple(TupleVariant {
+                ident: v.ident.clone(),
+                fields: filter_fields(&v.fields, f),
+                attrs: v.attrs.clone(),
+                discriminant: v.discriminant.clone(),
+            }),
+            Variant::Struct(v) => Variant::Struct(StructVariant {
+                ident: v.ident.clone(),
+                fields: filter_fields(&v.fields, f),
+                attrs: v.attrs.clone(),
+                discriminant: v.discriminant.clone(),
+            }),
+        }
+    }
+
     pub(crate) fn map<F, R>(self, f: F) -> Variant<R>
     where
         F: FnMut(Field<T>) -> R,
@@ -99,28 +263,205 @@ impl<T> Variant<T> {
             Variant::Struct(v) => Variant::Struct(v.map(f)),
         }
     }
+
+    pub(crate) fn try_map<F, R, E>(self, f: F) -> Result<Variant<R>, E>
+    where
+        F: FnMut(Field<T>) -> Result<R, E>,
+    {
+        Ok(match self {
+            Variant::Unit(v) => Variant::Unit(v),
+            Variant::Tuple(v) => Variant::Tuple(v.try_map(f)?),
+            Variant::Struct(v) => Variant::Struct(v.try_map(f)?),
+        })
+    }
 }
 
 impl<T> TupleVariant<T> {
-    pub(crate) fn map<F, R>(self, f: F) -> TupleVariant<R>
+    pub(crate) fn map<F, R>(self, mut f: F) -> TupleVariant<R>
     where
         F: FnMut(Field<T>) -> R,
     {
         TupleVariant {
+            ident: self.ident,
+            fields: self
+                .fields
+                .into_iter()
+                .map(|field| field.map(&mut f))
+                .collect(),
             attrs: self.attrs,
-            phantom: PhantomData,
+            discriminant: self.discriminant,
         }
     }
+
+    pub(crate) fn try_map<F, R, E>(self, mut f: F) -> Result<TupleVariant<R>, E>
+    where
+        F: FnMut(Field<T>) -> Result<R, E>,
+    {
+        Ok(TupleVariant {
+            ident: self.ident,
+            fields: self
+                .fields
+                .into_iter()
+                .map(|field| field.try_map(&mut f))
+                .collect::<Result<_, _>>()?,
+            attrs: self.attrs,
+            discriminant: self.discriminant,
+        })
+    }
 }
 
 impl<T> StructVariant<T> {
-    pub(crate) fn map<F, R>(self, f: F) -> StructVariant<R>
+    pub(crate) fn map<F, R>(self, mut f: F) -> StructVariant<R>
     where
         F: FnMut(Field<T>) -> R,
     {
         StructVariant {
+            ident: self.ident,
+            fields: self
+                .fields
+                .into_iter()
+                .map(|field| field.map(&mut f))
+                .collect(),
             attrs: self.attrs,
-            phantom: PhantomData,
+            discriminant: self.discriminant,
         }
     }
+
+    pub(crate) fn try_map<F, R, E>(self, mut f: F) -> Result<StructVariant<R>, E>
+    where
+        F: FnMut(Field<T>) -> Result<R, E>,
+    {
+        Ok(StructVariant {
+            ident: self.ident,
+            fields: self
+                .fields
+                .into_iter()
+                .map(|field| field.try_map(&mut f))
+                .collect::<Result<_, _>>()?,
+            attrs: self.attrs,
+            discriminant: self.discriminant,
+        })
+    }
+}
+
+impl<T> Data<T> {
+    /// Pairs every field with the field in the same position of `other`.
+    /// Struct fields must match by name or index, and enum variants are
+    /// paired by name. The attributes are taken from `self`.
+    pub fn zip<U>(self, other: Data<U>) -> Result<Data<(T, U)>, ZipError> {
+        Ok(match (self, other) {
+            (Data::Struct(a), Data::Struct(b)) => Data::Struct(a.zip(b)?),
+            (Data::Enum(a), Data::Enum(b)) => Data::Enum(a.zip(b)?),
+            _ => return Err(ZipError::ShapeMismatch),
+        })
+    }
+}
+
+impl<T> Struct<T> {
+    pub(crate) fn zip<U>(self, other: Struct<U>) -> Result<Struct<(T, U)>, ZipError> {
+        Ok(match (self, other) {
+            (Struct::Unit(a), Struct::Unit(_)) => Struct::Unit(a),
+            (Struct::Tuple(a), Struct::Tuple(b)) => Struct::Tuple(TupleStruct {
+                ident: a.ident,
+                fields: zip_fields(a.fields, b.fields)?,
+                attrs: a.attrs,
+            }),
+            (Struct::Struct(a), Struct::Struct(b)) => Struct::Struct(StructStruct {
+                ident: a.ident,
+                fields: zip_fields(a.fields, b.fields)?,
+                attrs: a.attrs,
+            }),
+            _ => return Err(ZipError::ShapeMismatch),
+        })
+    }
+}
+
+impl<T> Enum<T> {
+    pub(crate) fn zip<U>(self, other: Enum<U>) -> Result<Enum<(T, U)>, ZipError> {
+        if self.variants.len() != other.variants.len() {
+            return Err(ZipError::ShapeMismatch);
+        }
+
+        let mut others = other.variants;
+        let variants = self
+            .variants
+            .into_iter()
+            .map(|variant| {
+                let i = others
+                    .iter()
+                    .position(|other| other.name() == variant.name())
+                    .ok_or(ZipError::ShapeMismatch)?;
+                variant.zip(others.swap_remove(i))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Enum {
+            variants,
+            attrs: self.attrs,
+            scrutinee: self.scrutinee,
+        })
+    }
+}
+
+impl<T> Variant<T> {
+    pub(crate) fn zip<U>(self, other: Variant<U>) -> Result<Variant<(T, U)>, ZipError> {
+        Ok(match (self, other) {
+            (Variant::Unit(a), Variant::Unit(_)) => Variant::Unit(a),
+            (Variant::Tuple(a), Variant::Tuple(b)) => Variant::Tuple(TupleVariant {
+                ident: a.ident,
+                fields: zip_fields(a.fields, b.fields)?,
+                attrs: a.attrs,
+                discriminant: a.discriminant,
+            }),
+            (Variant::Struct(a), Variant::Struct(b)) => Variant::Struct(StructVariant {
+                ident: a.ident,
+                fields: zip_fields(a.fields, b.fields)?,
+                attrs: a.attrs,
+                discriminant: a.discriminant,
+            }),
+            _ => return Err(ZipError::ShapeMismatch),
+        })
+    }
+}
+
+fn zip_fields<T, U>(
+    fields: Vec<Field<T>>,
+    others: Vec<Field<U>>,
+) -> Result<Vec<Field<(T, U)>>, ZipError> {
+    if fields.len() != others.len() {
+        return Err(ZipError::ShapeMismatch);
+    }
+
+    fields
+        .into_iter()
+        .zip(others)
+        .map(|(field, other)| {
+            if field.accessor != other.accessor {
+                return Err(ZipError::ShapeMismatch);
+            }
+            Ok(Field {
+                accessor: field.accessor,
+                element: (field.element, other.element),
+                attrs: field.attrs,
+                vis: field.vis,
+            })
+        })
+        .collect()
+}
+
+fn filter_fields<T, U, F>(fields: &[Field<T>], f: &F) -> Vec<Field<U>>
+where
+    F: Fn(&Field<T>) -> Option<U>,
+{
+    fields
+        .iter()
+        .filter_map(|field| {
+            f(field).map(|element| Field {
+                attrs: field.attrs.clone(),
+                accessor: field.accessor.clone(),
+                element,
+                vis: field.vis.clone(),
+            })
+        })
+        .collect()
 }
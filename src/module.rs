@@ -1,44 +1,176 @@
+use crate::path::{PathArguments, PathSegment};
 use crate::{
-    GlobalPush, MacroInvoke, Path, RuntimeType, SynParamMap, TypeNode, Value, ValueNode, MACROS,
-    VALUES,
+    Function, GlobalPush, Ident, MacroInvoke, Parent, Path, ReflectError, RuntimeType, SynParamMap,
+    TypeNode, Value, ValueNode, MACROS, MODULE_ITEMS, VALUES,
 };
+use std::rc::Rc;
 
 #[derive(Debug, Clone)]
 pub struct Module {
     pub(crate) path: Path,
 }
 
+/// An item registered in a module with `Module::register_function` or
+/// `Module::register_parent`
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum Item {
+    Function(Rc<Function>),
+    Parent(Rc<Parent>),
+}
+
 impl Module {
     pub fn root() -> Self {
         Self {
             path: Path {
                 path: Vec::new(),
                 global: true,
+                qself: None,
             },
         }
     }
 
+    /// The `::std` module
+    pub fn std_module() -> Self {
+        Self::root().get_module("std")
+    }
+
+    /// The `::core` module, for items that are also available to `no_std`
+    /// crates
+    pub fn core_module() -> Self {
+        Self::root().get_module("core")
+    }
+
+    /// The root of the crate being compiled, so that paths are emitted as
+    /// `crate::segment` rather than `::segment`
+    pub fn crate_root() -> Self {
+        Self {
+            path: Path::crate_root(),
+        }
+    }
+
     pub fn get_module(&self, name: &str) -> Self {
         Self {
             path: self.path.get_simple_path(name),
         }
     }
 
-    /// Get a path by appending a path segment at the end of a module path
-    pub fn get_path(&self, segment: &str, param_map: &mut SynParamMap) -> Path {
+    /// The parent of this module. A relative module that is already at the
+    /// current module or above it gets another `super` segment. The crate
+    /// root and the global root have no parent and give
+    /// `ReflectError::Unsupported`.
+    pub fn super_module(&self) -> Result<Self, ReflectError> {
+        let mut path = self.path.clone();
+        match path.path.last().map(|segment| segment.ident.to_string()) {
+            Some(ref last) if last == "crate" => {
+                return Err(ReflectError::Unsupported("the parent of the crate root"))
+            }
+            None if path.global => {
+                return Err(ReflectError::Unsupported("the parent of the global root"))
+            }
+            Some(ref last) if last == "self" => {
+                path.path.pop();
+                path.path.push(keyword_segment("super"));
+            }
+            Some(ref last) if last != "super" => {
+                path.path.pop();
+            }
+            _ => path.path.push(keyword_segment("super")),
+        }
+        Ok(Self { path })
+    }
+
+    /// This module, written relative to the current module with a `self`
+    /// prefix. Modules that are global or already start at `crate`, `self`
+    /// or `super` are returned as they are.
+    pub fn self_module(&self) -> Self {
+        let mut path = self.path.clone();
+        let is_relative = match path.path.first() {
+            Some(segment) => !matches!(
+                segment.ident.to_string().as_str(),
+                "crate" | "self" | "super"
+            ),
+            None => true,
+        };
+        if !path.global && is_relative {
+            path.path.insert(0, keyword_segment("self"));
+        }
+        Self { path }
+    }
+
+    /// Get a path by appending a path segment at the end of a module path.
+    /// Gives `ReflectError::Parse` if `segment` is not a path segment.
+    pub fn get_path(
+        &self,
+        segment: &str,
+        param_map: &mut SynParamMap,
+    ) -> Result<Path, ReflectError> {
         self.path.get_path(segment, param_map)
     }
 
     /// Get a path type by appending a path segment at the end of a module path
-    pub fn get_path_type(&self, segment: &str, param_map: &mut SynParamMap) -> TypeNode {
-        self.get_path(segment, param_map).SELF()
+    pub fn get_path_type(
+        &self,
+        segment: &str,
+        param_map: &mut SynParamMap,
+    ) -> Result<TypeNode, ReflectError> {
+        Ok(self.get_path(segment, param_map)?.SELF())
+    }
+
+    /// Get the path of the item `ident` of this module, which has no generic
+    /// arguments
+    pub(crate) fn get_simple_path(&self, ident: &str) -> Path {
+        self.path.get_simple_path(ident)
+    }
+
+    /// Refers to the `static` or `const` item `segment` of this module, whose
+    /// type is `ty`
+    pub fn get_path_value(&self, segment: &str, ty: TypeNode) -> Result<Value, ReflectError> {
+        let path = self.get_path(segment, &mut SynParamMap::default())?;
+        Ok(Value::new_path_value(path, ty))
+    }
+
+    /// Records `function` as an item of this module, to be listed by
+    /// `all_items`. Registrations last until the end of the current derive.
+    pub fn register_function(&self, function: Rc<Function>) {
+        self.register(Item::Function(function));
+    }
+
+    /// Records `parent` as an item of this module, as in `register_function`
+    pub fn register_parent(&self, parent: Rc<Parent>) {
+        self.register(Item::Parent(parent));
+    }
+
+    fn register(&self, item: Item) {
+        MODULE_ITEMS.with_borrow_mut(|items| items.push((self.path.clone(), item)));
     }
 
+    /// The items registered in this module, in the order they were
+    /// registered. Items of submodules are not included, and items that were
+    /// never registered can not be discovered at compile time.
+    pub fn all_items(&self) -> Vec<Item> {
+        MODULE_ITEMS.with_borrow(|items| {
+            items
+                .iter()
+                .filter(|(path, _)| *path == self.path)
+                .map(|(_, item)| item.clone())
+                .collect()
+        })
+    }
+
+    /// Invoke a macro whose output type is left to be inferred. Use
+    /// `invoke_macro_typed` if the type of the result is needed.
     pub fn invoke_macro(&self, name: &str, values: &[Value]) -> Value {
+        self.invoke_macro_typed(name, values, TypeNode::Infer)
+    }
+
+    /// Invoke a macro that expands to an expression of type `output`
+    pub fn invoke_macro_typed(&self, name: &str, values: &[Value], output: TypeNode) -> Value {
         let macro_path = self.path.get_simple_path(name);
         let invoke = MACROS.index_push(MacroInvoke {
             macro_path,
             args: values.iter().map(|value| value.index).collect(),
+            output,
         });
 
         let node = ValueNode::MacroInvocation(invoke);
@@ -46,4 +178,21 @@ impl Module {
             index: VALUES.index_push(node),
         }
     }
+
+    /// Invoke a macro such as `format!` or `println!` whose first argument is
+    /// a format string, emitting `name!("fmt", args...)`. The output type is
+    /// left to be inferred.
+    pub fn invoke_format_macro(&self, name: &str, fmt: &str, args: &[Value]) -> Value {
+        let mut values = vec![Value::new_str_literal(fmt)];
+        values.extend_from_slice(args);
+        self.invoke_macro(name, &values)
+    }
+}
+
+/// A `self`, `super` or `crate` segment, which can not be parsed as an `Ident`
+fn keyword_segment(keyword: &str) -> PathSegment {
+    PathSegment {
+        ident: Ident::new(keyword),
+        args: PathArguments::None,
+    }
 }
@@ -1,12 +1,17 @@
 use crate::{
-    Accessor, Data, GlobalBorrow, Ident, InvokeRef, MacroInvokeRef, TypeNode, ValueRef, INVOKES,
-    VALUES,
+    Accessor, ConstParam, Data, DataStructure, Field, Generics, Ident, InvokeRef, MacroInvokeRef,
+    Path, Struct, StructStruct, TupleStruct, TypeNode, ValueRef, VariantPayload, Visibility,
+    INVOKES, MACROS, STATIC_LIFETIME, VALUES,
 };
+use std::ops::Range;
 
 #[derive(Debug, Clone)]
 pub(crate) enum ValueNode {
     Tuple(Vec<ValueRef>),
     Str(String),
+    /// A string literal such as `"hello"`, as opposed to the type name held
+    /// by `Str`
+    StrLit(String),
     // TODO: Add lifetime parameter
     Reference {
         is_mut: bool,
@@ -28,6 +33,74 @@ pub(crate) enum ValueNode {
         ty: TypeNode,
     },
     MacroInvocation(MacroInvokeRef),
+    StructLiteral {
+        name: Ident,
+        fields: Vec<(Ident, ValueRef)>,
+    },
+    TupleStructLiteral {
+        name: Ident,
+        fields: Vec<ValueRef>,
+    },
+    VariantConstruct {
+        path: Path,
+        variant: Ident,
+        payload: VariantPayload,
+    },
+    Try(ValueRef),
+    Await(ValueRef),
+    /// An expression taken as written, such as an enum discriminant
+    Expr(syn::Expr),
+    /// The statement `let name: ty = init;`, or `let mut` if `is_mut`, whose
+    /// bound value is referred to by a `Local`
+    Let {
+        name: Ident,
+        is_mut: bool,
+        ty: TypeNode,
+        init: ValueRef,
+    },
+    /// A variable bound by a `Let`, referred to by its name wherever it is
+    /// used, unlike a `Binding`, which is copied into a value of its own
+    Local {
+        name: Ident,
+        ty: TypeNode,
+    },
+    /// The statement `place = value;`
+    Assign {
+        place: ValueRef,
+        value: ValueRef,
+    },
+    /// The closure `|params...| { ... ret }`, whose statements are the values
+    /// in `body`. The params are `Local`s at the start of `body`.
+    Closure {
+        params: Vec<ValueRef>,
+        body: Range<ValueRef>,
+        ret: ValueRef,
+    },
+    /// A named item of type `ty`, such as a `static`, a `const` or a function,
+    /// referred to by its path
+    PathValue {
+        path: Path,
+        ty: TypeNode,
+    },
+    /// The value of a const param, as in `0..N`
+    ConstValue(ConstParam),
+    /// `match scrutinee { arms... }` on an enum, followed by an unreachable
+    /// `_` arm if `wildcard`
+    Match {
+        scrutinee: ValueRef,
+        arms: Vec<MatchArm>,
+        wildcard: bool,
+    },
+}
+
+/// The arm of a `Match` for one variant. Its pattern binds the fields of the
+/// variant to the `Local`s in `payload`, which are at the start of `body`.
+#[derive(Debug, Clone)]
+pub(crate) struct MatchArm {
+    pub(crate) variant: Ident,
+    pub(crate) payload: VariantPayload,
+    pub(crate) body: Range<ValueRef>,
+    pub(crate) ret: ValueRef,
 }
 
 impl ValueNode {
@@ -37,6 +110,11 @@ impl ValueNode {
                 TypeNode::Tuple(types.iter().map(|type_ref| type_ref.get_type()).collect())
             }
             Self::Str(_) => TypeNode::PrimitiveStr,
+            Self::StrLit(_) => TypeNode::Reference {
+                is_mut: false,
+                lifetime: Some(STATIC_LIFETIME),
+                inner: Box::new(TypeNode::PrimitiveStr),
+            },
             Self::Reference { is_mut, value } => TypeNode::Reference {
                 is_mut: *is_mut,
                 lifetime: None,
@@ -51,6 +129,62 @@ impl ValueNode {
             Self::Invoke(invoke_ref) => {
                 INVOKES.with_borrow(|invokes| invokes[invoke_ref.0].function.sig.output.clone())
             }
+            Self::VariantConstruct { path, .. } => TypeNode::Path(path.clone()),
+            Self::MacroInvocation(invoke_ref) => {
+                MACROS.with_borrow(|macros| macros[invoke_ref.0].output.clone())
+            }
+            // Checked by `Value::try_propagate` and `Value::await_value`
+            Self::Try(value) => value.get_type().try_output().unwrap(),
+            Self::Await(value) => value.await_output().unwrap(),
+            Self::Expr(_) | Self::Closure { .. } => TypeNode::Infer,
+            Self::Let { .. } | Self::Assign { .. } => TypeNode::new_unit(),
+            Self::Local { ty, .. } | Self::PathValue { ty, .. } => ty.clone(),
+            Self::ConstValue(const_param) => const_param.ty(),
+            // An arm that diverges says nothing about the type of the match
+            Self::Match { arms, .. } => arms
+                .iter()
+                .map(|arm| arm.ret.get_type())
+                .find(|ty| *ty != TypeNode::Never)
+                .unwrap_or(TypeNode::Never),
+            Self::StructLiteral { name, fields } => {
+                let fields = fields
+                    .iter()
+                    .map(|(ident, value)| Field {
+                        accessor: Accessor::Name(ident.clone()),
+                        element: value.get_type(),
+                        attrs: Vec::new(),
+                        vis: Visibility::Inherited,
+                    })
+                    .collect();
+                literal_type(
+                    name,
+                    Struct::Struct(StructStruct {
+                        ident: name.clone(),
+                        fields,
+                        attrs: Vec::new(),
+                    }),
+                )
+            }
+            Self::TupleStructLiteral { name, fields } => {
+                let fields = fields
+                    .iter()
+                    .enumerate()
+                    .map(|(index, value)| Field {
+                        accessor: Accessor::Index(index),
+                        element: value.get_type(),
+                        attrs: Vec::new(),
+                        vis: Visibility::Inherited,
+                    })
+                    .collect();
+                literal_type(
+                    name,
+                    Struct::Tuple(TupleStruct {
+                        ident: name.clone(),
+                        fields,
+                        attrs: Vec::new(),
+                    }),
+                )
+            }
 
             node => panic!("ValueNode::get_type"),
         }
@@ -75,9 +209,24 @@ impl ValueNode {
                 let types = format!("({})", types.trim_end_matches(", "));
                 Self::Str(types)
             }
-            Self::Str(_) => Self::Str(String::from("str")),
+            Self::Str(_) | Self::StrLit(_) => Self::Str(String::from("str")),
+            Self::Let { .. } | Self::Assign { .. } => Self::Str(String::from("()")),
+            Self::Local { ty, .. } | Self::PathValue { ty, .. } => Self::Str(ty.get_name()),
+            Self::ConstValue(const_param) => Self::Str(const_param.ty().get_name()),
+            Self::Match { .. } => Self::Str(self.get_type().get_name()),
             Self::DataStructure { name, .. } => Self::Str(name.to_owned()),
+            Self::StructLiteral { name, .. } | Self::TupleStructLiteral { name, .. } => {
+                Self::Str(name.to_string())
+            }
+            Self::VariantConstruct { path, .. } => {
+                Self::Str(TypeNode::Path(path.clone()).get_name())
+            }
             Self::Reference { value, .. } => value.get_type_name(),
+            Self::MacroInvocation(invoke_ref) => {
+                Self::Str(MACROS.with_borrow(|macros| macros[invoke_ref.0].output.get_name()))
+            }
+            Self::Try(value) => Self::Str(value.get_type().try_output().unwrap().get_name()),
+            Self::Await(value) => Self::Str(value.await_output().unwrap().get_name()),
             Self::Binding { ty, .. } => Self::Str(ty.get_name()),
             Self::Destructure {
                 parent,
@@ -92,11 +241,38 @@ impl ValueNode {
     }
 }
 
+fn literal_type(name: &Ident, data: Struct<TypeNode>) -> TypeNode {
+    TypeNode::DataStructure(Box::new(DataStructure {
+        name: name.clone(),
+        generics: Generics::default(),
+        data: Data::Struct(data),
+    }))
+}
+
 impl ValueRef {
     pub(crate) fn get_type(self) -> TypeNode {
         VALUES.with_borrow(|values| values[self.0].get_type())
     }
 
+    /// The type produced by awaiting this value. The output of an
+    /// `async fn` call is already recorded in its signature; any other future
+    /// is expected to spell out its `Output` type, or gives `None`.
+    pub(crate) fn await_output(self) -> Option<TypeNode> {
+        VALUES
+            .with_borrow(|values| match &values[self.0] {
+                ValueNode::Invoke(invoke_ref) => INVOKES.with_borrow(|invokes| {
+                    let function = &invokes[invoke_ref.0].function;
+                    if function.sig.is_async {
+                        Some(function.sig.output.clone())
+                    } else {
+                        None
+                    }
+                }),
+                _ => None,
+            })
+            .or_else(|| self.get_type().future_output())
+    }
+
     pub(crate) fn get_type_name(self) -> ValueNode {
         VALUES.with_borrow(|values| values[self.0].get_type_name())
     }
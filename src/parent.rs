@@ -1,5 +1,6 @@
-use crate::{Generics, ParamMap, Path, SynParamMap};
+use crate::{Function, Generics, ParamMap, Path, ReflectError, SynParamMap, TypeNode};
 use std::default::Default;
+use std::rc::Rc;
 
 #[derive(Debug, Clone)]
 pub struct Parent {
@@ -59,15 +60,93 @@ impl ParentBuilder {
         <Self as SetPath<'a, P>>::set_path(self, into_path);
     }
 
-    pub fn set_generic_params(&mut self, params: &[&str]) {
+    pub fn set_generic_params(&mut self, params: &[&str]) -> Result<(), ReflectError> {
         self.generics.set_generic_params(params)
     }
 
-    pub fn set_generic_constraints(&mut self, constraints: &[&str]) {
+    pub fn set_generic_constraints(&mut self, constraints: &[&str]) -> Result<(), ReflectError> {
         self.generics.set_generic_constraints(constraints)
     }
 }
 
+/// Describes the impl block whose methods generated code calls. Methods of an
+/// inherent impl are called through the type, as in `Type::f(...)`, and
+/// methods of a trait impl through the trait, as in `Trait::f(...)`.
+pub struct ImplBuilder {
+    ty: Path,
+    trait_path: Option<Path>,
+    generics: Generics,
+    methods: Vec<Rc<Function>>,
+}
+
+impl ImplBuilder {
+    /// An inherent impl of `ty`, which must be a path or a data structure.
+    /// Gives `ReflectError::UnexpectedType` for any other type.
+    pub fn for_type(ty: TypeNode) -> Result<Self, ReflectError> {
+        let ty = match ty {
+            TypeNode::Path(path) => path,
+            TypeNode::DataStructure(data) => Path::ident_to_path(data.name),
+            other => {
+                return Err(ReflectError::UnexpectedType {
+                    expected: "a path or a data structure",
+                    found: other.to_string(),
+                })
+            }
+        };
+        Ok(Self::for_path(ty))
+    }
+
+    /// An inherent impl of the type at `ty`
+    pub(crate) fn for_path(ty: Path) -> Self {
+        Self {
+            ty,
+            trait_path: None,
+            generics: Generics::default(),
+            methods: Vec::new(),
+        }
+    }
+
+    /// Makes it an impl of the trait for the type
+    pub fn with_trait(mut self, trait_path: Path) -> Self {
+        self.trait_path = Some(trait_path);
+        self
+    }
+
+    pub fn with_generics(mut self, generics: Generics) -> Self {
+        self.generics = generics;
+        self
+    }
+
+    pub fn add_method(mut self, function: Rc<Function>) -> Self {
+        self.methods.push(function);
+        self
+    }
+
+    /// The parent, along with the added methods with the parent set. All the
+    /// methods share the same parent, as trait inference requires.
+    pub fn build(self) -> (Rc<Parent>, Vec<Rc<Function>>) {
+        let (path, parent_kind) = match self.trait_path {
+            Some(trait_path) => (trait_path, ParentKind::Trait),
+            None => (self.ty, ParentKind::Impl),
+        };
+        let parent = Rc::new(Parent {
+            path,
+            generics: self.generics,
+            parent_kind,
+        });
+        let methods = self
+            .methods
+            .into_iter()
+            .map(|method| {
+                let mut method = Rc::unwrap_or_clone(method);
+                method.set_parent(parent.clone());
+                Rc::new(method)
+            })
+            .collect();
+        (parent, methods)
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum ParentKind {
     Trait,
@@ -1,12 +1,20 @@
-use crate::{GenericArgument, GenericArguments, Ident, ParamMap, SynParamMap, TypeNode};
+use crate::{
+    print, FreshGenerics, GenericArgument, GenericArguments, Ident, ParamMap, Print, ReflectError,
+    SynParamMap, TypeFolder, TypeNode,
+};
+use quote::ToTokens;
 use ref_cast::RefCast;
+use std::hash::{Hash, Hasher};
 use syn::parse::{Parse, ParseStream, Result};
 use syn::{parse_str, ReturnType, Token};
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Path {
     pub(crate) global: bool,
     pub(crate) path: Vec<PathSegment>,
+    /// The `T` of a qualified path `<T as Trait>::item`, in which case all
+    /// segments but the last are the path of the trait
+    pub(crate) qself: Option<Box<TypeNode>>,
 }
 
 #[derive(RefCast)]
@@ -15,8 +23,9 @@ pub(crate) struct SimplePath {
     pub(crate) path: Path,
 }
 
+/// A segment of a `Path`: an identifier along with any generic arguments
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub(crate) struct PathSegment {
+pub struct PathSegment {
     pub(crate) ident: Ident,
     pub(crate) args: PathArguments,
 }
@@ -42,11 +51,51 @@ pub(crate) struct ParenthesizedGenericArguments {
     pub(crate) output: Option<TypeNode>,
 }
 
+// Unqualified paths hash the same as their segments alone, which keeps the
+// order of inferred where clauses independent of `qself`
+impl Hash for Path {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.global.hash(state);
+        self.path.hash(state);
+        if let Some(qself) = &self.qself {
+            qself.hash(state);
+        }
+    }
+}
+
+impl PathSegment {
+    pub fn ident(&self) -> &Ident {
+        &self.ident
+    }
+
+    /// The arguments in angle brackets, or `None` if there are none or the
+    /// segment has parenthesized arguments as in `Fn(A) -> B`
+    pub fn generic_args(&self) -> Option<&GenericArguments> {
+        match &self.args {
+            PathArguments::AngleBracketed(args) => Some(&args.args),
+            _ => None,
+        }
+    }
+}
+
 impl Path {
     pub(crate) fn root() -> Self {
         Self {
             global: true,
             path: Vec::new(),
+            qself: None,
+        }
+    }
+
+    /// The `crate` path, which is relative rather than global
+    pub(crate) fn crate_root() -> Self {
+        Self {
+            global: false,
+            path: vec![PathSegment {
+                ident: Ident::new("crate"),
+                args: PathArguments::None,
+            }],
+            qself: None,
         }
     }
 
@@ -54,6 +103,7 @@ impl Path {
         Self {
             global: false,
             path: Vec::new(),
+            qself: None,
         }
     }
 
@@ -69,40 +119,191 @@ impl Path {
         path
     }
 
-    pub(crate) fn get_path(&self, segment: &str, param_map: &mut SynParamMap) -> Self {
+    /// Get this path followed by the single segment `segment`, with the
+    /// given generic arguments in place of any that `segment` is written
+    /// with, as in going from `::std::vec` to `::std::vec::Vec<T>`
+    pub fn with_arguments(
+        &self,
+        segment: &str,
+        args: GenericArguments,
+        param_map: &mut SynParamMap,
+    ) -> std::result::Result<Self, ReflectError> {
+        let mut path = self.get_path(segment, param_map)?;
+        path.path.last_mut().unwrap().args =
+            PathArguments::AngleBracketed(AngleBracketedGenericArguments { args });
+        Ok(path)
+    }
+
+    pub fn first_segment(&self) -> Option<&PathSegment> {
+        self.path.first()
+    }
+
+    pub fn last_segment(&self) -> Option<&PathSegment> {
+        self.path.last()
+    }
+
+    /// The identifier of the last segment, such as `Option` in
+    /// `::std::option::Option<T>`
+    pub fn last_ident(&self) -> Option<&Ident> {
+        self.last_segment().map(PathSegment::ident)
+    }
+
+    /// Whether the last segments of the path have the given identifiers,
+    /// ignoring generic arguments. Both `["Option"]` and
+    /// `["std", "option", "Option"]` match `::std::option::Option<T>`.
+    pub fn ends_with(&self, idents: &[&str]) -> bool {
+        idents.len() <= self.path.len()
+            && self.path[self.path.len() - idents.len()..]
+                .iter()
+                .zip(idents)
+                .all(|(segment, ident)| segment.ident == *ident)
+    }
+
+    /// Whether the path starts with `::`
+    pub fn is_absolute(&self) -> bool {
+        self.global
+    }
+
+    /// Whether the path does not start with `::`, including paths starting
+    /// with `crate`, `self` or `super`
+    pub fn is_relative(&self) -> bool {
+        !self.global
+    }
+
+    /// The same path starting with `::`. A path starting with `crate`,
+    /// `self` or `super`, which can not follow `::`, gives
+    /// `ReflectError::Unsupported`.
+    pub fn make_absolute(&self) -> std::result::Result<Self, ReflectError> {
+        if let Some(first) = self.path.first() {
+            if ["crate", "self", "super"]
+                .iter()
+                .any(|keyword| first.ident == *keyword)
+            {
+                return Err(ReflectError::Unsupported(
+                    "`::` before `crate`, `self` or `super`",
+                ));
+            }
+        }
+        Ok(Self {
+            global: true,
+            path: self.path.clone(),
+            qself: self.qself.clone(),
+        })
+    }
+
+    /// Get the path with one more type argument on its last segment, as in
+    /// going from `HashMap<K>` to `HashMap<K, V>`
+    pub fn append_type_arg(&self, ty: TypeNode) -> Self {
         let mut path = self.clone();
-        path.path.push(Self::syn_to_path_segment(
-            parse_str(segment).expect("Path::get_path: Not a PathSegment"),
-            param_map,
-        ));
+        let segment = path
+            .path
+            .last_mut()
+            .expect("Path::append_type_arg: Empty path");
+        match &mut segment.args {
+            PathArguments::None => {
+                let mut args = GenericArguments::new();
+                args.push_type(ty);
+                segment.args =
+                    PathArguments::AngleBracketed(AngleBracketedGenericArguments { args });
+            }
+            PathArguments::AngleBracketed(angle_bracketed) => angle_bracketed.args.push_type(ty),
+            PathArguments::Parenthesized(_) => {
+                panic!("Path::append_type_arg: Parenthesized arguments")
+            }
+        }
         path
     }
 
-    pub fn path_from_str(path: &str, param_map: &mut SynParamMap) -> Self {
-        Self::syn_to_path(
-            parse_str(path).expect("Path::path_from_str: Not a Path"),
-            param_map,
-        )
+    /// The qualified path `<self_ty as Trait>::method` of a trait method, for
+    /// calling it on `self_ty` without ambiguity
+    ///
+    /// # Panics
+    ///
+    /// If `trait_path` is empty or already qualified
+    pub fn for_trait_method(trait_path: Path, method: Ident, self_ty: TypeNode) -> Self {
+        if trait_path.path.is_empty() || trait_path.qself.is_some() {
+            panic!("Path::for_trait_method: Not a trait path: {}", trait_path);
+        }
+        let mut path = trait_path;
+        path.path.push(PathSegment {
+            ident: method,
+            args: PathArguments::None,
+        });
+        path.qself = Some(Box::new(self_ty));
+        path
+    }
+
+    pub(crate) fn get_path(
+        &self,
+        segment: &str,
+        param_map: &mut SynParamMap,
+    ) -> std::result::Result<Self, ReflectError> {
+        let mut path = self.clone();
+        path.path
+            .push(Self::syn_to_path_segment(parse_str(segment)?, param_map)?);
+        Ok(path)
+    }
+
+    /// Converts the path back to syn, with params under their generated
+    /// names such as `__T0`. A qualified path such as `<T as Trait>::method`
+    /// is not a `syn::Path` and gives `ReflectError::Unsupported`.
+    pub fn to_syn(&self) -> std::result::Result<syn::Path, ReflectError> {
+        self.check_unqualified()?;
+        Ok(syn::parse2(Print::ref_cast(self).to_token_stream()).expect("Path::to_syn"))
+    }
+
+    /// Converts the path back to syn, with params under the names they are
+    /// given in `param_map`, as in `to_syn`
+    pub fn to_syn_with_names(
+        &self,
+        param_map: &SynParamMap,
+    ) -> std::result::Result<syn::Path, ReflectError> {
+        self.check_unqualified()?;
+        let tokens = print::with_param_names(Print::ref_cast(self).to_token_stream(), param_map);
+        Ok(syn::parse2(tokens).expect("Path::to_syn_with_names"))
     }
 
-    pub(crate) fn syn_to_path(path: syn::Path, param_map: &mut SynParamMap) -> Self {
+    fn check_unqualified(&self) -> std::result::Result<(), ReflectError> {
+        match self.qself {
+            Some(_) => Err(ReflectError::Unsupported("qualified paths")),
+            None => Ok(()),
+        }
+    }
+
+    /// Parses `path`, adding any params it declares to `param_map`. Gives
+    /// `ReflectError::Parse` if it is not a path.
+    pub fn path_from_str(
+        path: &str,
+        param_map: &mut SynParamMap,
+    ) -> std::result::Result<Self, ReflectError> {
+        Self::syn_to_path(parse_str(path)?, param_map)
+    }
+
+    pub(crate) fn syn_to_path(
+        path: syn::Path,
+        param_map: &mut SynParamMap,
+    ) -> std::result::Result<Self, ReflectError> {
         let global = path.leading_colon.is_some();
-        let path: Vec<_> = path
+        let path = path
             .segments
             .into_iter()
             .map(|segment| Self::syn_to_path_segment(segment, param_map))
-            .collect();
-        Self { global, path }
+            .collect::<std::result::Result<_, _>>()?;
+        Ok(Self {
+            global,
+            path,
+            qself: None,
+        })
     }
 
     pub(crate) fn syn_to_path_segment(
         path_segment: syn::PathSegment,
         param_map: &mut SynParamMap,
-    ) -> PathSegment {
+    ) -> std::result::Result<PathSegment, ReflectError> {
         let syn::PathSegment { ident, arguments } = path_segment;
         let ident = Ident::from(ident);
 
-        match arguments {
+        Ok(match arguments {
             syn::PathArguments::None => PathSegment {
                 ident,
                 args: PathArguments::None,
@@ -115,7 +316,7 @@ impl Path {
                             .args
                             .into_iter()
                             .map(|arg| GenericArgument::syn_to_generic_argument(arg, param_map))
-                            .collect(),
+                            .collect::<std::result::Result<_, _>>()?,
                     },
                 }),
             },
@@ -127,14 +328,14 @@ impl Path {
                         .inputs
                         .into_iter()
                         .map(|input| TypeNode::syn_to_type(input, param_map))
-                        .collect(),
+                        .collect::<std::result::Result<_, _>>()?,
                     output: match parenthesized.output {
                         ReturnType::Default => None,
-                        ReturnType::Type(_, ty) => Some(TypeNode::syn_to_type(*ty, param_map)),
+                        ReturnType::Type(_, ty) => Some(TypeNode::syn_to_type(*ty, param_map)?),
                     },
                 }),
             },
-        }
+        })
     }
 
     pub(crate) fn ident_to_path(ident: Ident) -> Self {
@@ -144,61 +345,12 @@ impl Path {
                 ident,
                 args: PathArguments::None,
             }],
+            qself: None,
         }
     }
 
     pub(crate) fn clone_with_fresh_generics(&self, param_map: &ParamMap) -> Self {
-        Self {
-            global: self.global,
-            path: self
-                .path
-                .iter()
-                .map(|segment| match segment.args {
-                    PathArguments::None => PathSegment {
-                        ident: segment.ident.clone(),
-                        args: PathArguments::None,
-                    },
-
-                    PathArguments::AngleBracketed(ref args) => PathSegment {
-                        ident: segment.ident.clone(),
-                        args: PathArguments::AngleBracketed(
-                            args.clone_with_fresh_generics(param_map),
-                        ),
-                    },
-
-                    PathArguments::Parenthesized(ref args) => PathSegment {
-                        ident: segment.ident.clone(),
-                        args: PathArguments::Parenthesized(
-                            args.clone_with_fresh_generics(param_map),
-                        ),
-                    },
-                })
-                .collect(),
-        }
-    }
-}
-
-impl AngleBracketedGenericArguments {
-    pub(crate) fn clone_with_fresh_generics(&self, param_map: &ParamMap) -> Self {
-        Self {
-            args: self.args.clone_with_fresh_generics(param_map),
-        }
-    }
-}
-
-impl ParenthesizedGenericArguments {
-    pub(crate) fn clone_with_fresh_generics(&self, param_map: &ParamMap) -> Self {
-        Self {
-            inputs: self
-                .inputs
-                .iter()
-                .map(|ty| ty.clone_with_fresh_generics(param_map))
-                .collect(),
-            output: self
-                .output
-                .as_ref()
-                .map(|ty| ty.clone_with_fresh_generics(param_map)),
-        }
+        FreshGenerics(param_map).fold_path(self)
     }
 }
 
@@ -218,7 +370,11 @@ impl Parse for SimplePath {
             });
         }
         Ok(Self {
-            path: Path { global, path },
+            path: Path {
+                global,
+                path,
+                qself: None,
+            },
         })
     }
 }
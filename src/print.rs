@@ -1,8 +1,10 @@
 use crate::generics::*;
-use crate::{path, Accessor, Lifetime, SimplePath, TypeNode, TypeParam};
-use proc_macro2::{Punct, Spacing, Span, TokenStream};
+use crate::{path, Accessor, Lifetime, SimplePath, SynParamMap, TypeNode, TypeParam};
+use proc_macro2::{Group, Punct, Spacing, Span, TokenStream, TokenTree};
 use quote::{quote, ToTokens, TokenStreamExt};
 use ref_cast::RefCast;
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
 use syn::{Ident, LitInt, Token};
 
 #[derive(RefCast)]
@@ -34,12 +36,13 @@ impl ToTokens for Print<TypeNode> {
                 }
             }
             PrimitiveStr => quote!(str),
+            Never => quote!(!),
             Reference {
                 is_mut,
                 lifetime,
                 inner,
             } => {
-                let lifetime = lifetime.as_ref().map(|lifetime| Print::ref_cast(lifetime));
+                let lifetime = lifetime.as_ref().map(Print::ref_cast);
                 let inner = Print::ref_cast(&**inner);
                 let token_mut = if *is_mut {
                     Some(Token![mut](Span::call_site()))
@@ -49,16 +52,32 @@ impl ToTokens for Print<TypeNode> {
                 quote!(&#lifetime #token_mut #inner)
             }
 
-            Dereference(inner) => panic!("Type::Dereference::to_tokens"),
-            DataStructure(data) => {
+            // The type that `*inner` would have
+            Dereference(inner) => {
+                let inner = Print::ref_cast(&**inner);
+                quote!(<#inner as ::core::ops::Deref>::Target)
+            }
+            DataStructure(data) if data.generics.is_empty() => {
                 let name = &data.name;
                 quote!(#name)
             }
+            // The data structure applied to its own params, as in `Point<__T0>`
+            DataStructure(data) => {
+                let name = &data.name;
+                let args = data.generics.params.iter().map(|param| match param {
+                    GenericParam::Type(type_param) => Print::ref_cast(type_param).to_token_stream(),
+                    GenericParam::Lifetime(lifetime) => Print::ref_cast(lifetime).to_token_stream(),
+                    GenericParam::Const(const_param) => {
+                        Print::ref_cast(const_param).to_token_stream()
+                    }
+                });
+                quote!(#name<#(#args),*>)
+            }
             TraitObject(bounds) => {
                 let bounds = bounds.iter().map(Print::ref_cast);
                 quote!((dyn #(#bounds)+*))
             }
-            Path(path) => {
+            Path(path) | Associated(path) => {
                 let path = Print::ref_cast(path);
                 quote!(#path)
             }
@@ -66,6 +85,20 @@ impl ToTokens for Print<TypeNode> {
                 let type_param = Print::ref_cast(type_param);
                 quote!(#type_param)
             }
+            // `&impl A + B` is ambiguous, so only a single bound goes without
+            // parentheses
+            ImplTrait(bounds) if bounds.len() == 1 => {
+                let bound = Print::ref_cast(&bounds[0]);
+                quote!(impl #bound)
+            }
+            ImplTrait(bounds) => {
+                let bounds = bounds.iter().map(Print::ref_cast);
+                quote!((impl #(#bounds)+*))
+            }
+            Slice(elem) => {
+                let elem = Print::ref_cast(&**elem);
+                quote!([#elem])
+            }
         });
     }
 }
@@ -77,12 +110,27 @@ impl ToTokens for Print<Generics> {
     }
 }
 
+impl ToTokens for Print<WhereClause> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let constraints = &self.0.generics.constraints;
+        if !constraints.is_empty() {
+            let constraints = constraints.iter().map(Print::ref_cast);
+            tokens.append_all(quote!(where #(#constraints,)*))
+        }
+    }
+}
+
 impl ToTokens for Print<GenericParam> {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         match &self.0 {
             GenericParam::Type(type_param) => Print::ref_cast(type_param).to_tokens(tokens),
             GenericParam::Lifetime(lifetime) => Print::ref_cast(lifetime).to_tokens(tokens),
-            GenericParam::Const(_const) => unimplemented!("const generics"),
+            GenericParam::Const(const_param) => {
+                let ty = const_param.ty();
+                let ty = Print::ref_cast(&ty);
+                let const_param = Print::ref_cast(const_param);
+                tokens.append_all(quote!(const #const_param: #ty))
+            }
         }
     }
 }
@@ -102,6 +150,21 @@ impl ToTokens for Print<TypeParam> {
     }
 }
 
+impl ToTokens for Print<ConstParam> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        Ident::new(&format!("__C{}", (self.0).0), Span::call_site()).to_tokens(tokens);
+    }
+}
+
+impl ToTokens for Print<Expr> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        match &self.0 {
+            Expr::Param(const_param) => Print::ref_cast(const_param).to_tokens(tokens),
+            Expr::Value(expr) => expr.to_tokens(tokens),
+        }
+    }
+}
+
 impl ToTokens for Print<TypeParamBound> {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         match &self.0 {
@@ -119,7 +182,7 @@ impl ToTokens for Print<TraitBound> {
         let lifetimes = if self.0.lifetimes.is_empty() {
             None
         } else {
-            Some(quote!(for <#(#lifetimes)+*>))
+            Some(quote!(for <#(#lifetimes),*>))
         };
         tokens.append_all(quote!(#lifetimes #path))
     }
@@ -131,7 +194,7 @@ impl ToTokens for Print<PredicateType> {
         let lifetimes = if self.0.lifetimes.is_empty() {
             None
         } else {
-            Some(quote!(for <#(#lifetimes)+*>))
+            Some(quote!(for <#(#lifetimes),*>))
         };
         let ty = Print::ref_cast(&self.0.bounded_ty);
         let bounds = self.0.bounds.iter().map(Print::ref_cast);
@@ -173,7 +236,7 @@ impl ToTokens for Print<Binding> {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         let ident = &self.0.ident;
         let ty = Print::ref_cast(&self.0.ty);
-        tokens.append_all(quote!(#ident : ty))
+        tokens.append_all(quote!(#ident = #ty))
     }
 }
 
@@ -203,7 +266,7 @@ impl ToTokens for Print<GenericArgument> {
                 Print::ref_cast(constraint).to_tokens(tokens)
             }
 
-            GenericArgument::Const(_expr) => unimplemented!(),
+            GenericArgument::Const(expr) => Print::ref_cast(expr).to_tokens(tokens),
         }
     }
 }
@@ -228,14 +291,14 @@ impl ToTokens for Print<path::Path> {
                     let inputs = args.inputs.iter().map(Print::ref_cast);
                     let output = args.output.as_ref().map(|output| {
                         let output = Print::ref_cast(output);
-                        quote! {-> output}
+                        quote! {-> #output}
                     });
                     Some(quote! { (#(#inputs),*) #output})
                 }
             };
             quote!(#ident #args)
         });
-        tokens.append_all(quote!(#leading #(#path)::*));
+        tokens.append_all(qualify(&self.0, leading, path.collect()));
     }
 }
 
@@ -247,6 +310,93 @@ impl ToTokens for Print<SimplePath> {
             None
         };
         let path = self.0.path.path.iter().map(|segment| &segment.ident);
-        tokens.append_all(quote!(#leading #(#path)::*));
+        let path = path.map(ToTokens::to_token_stream).collect();
+        tokens.append_all(qualify(&self.0.path, leading, path));
+    }
+}
+
+/// Joins the printed segments of `path`, as `<T as Trait>::item` if the path
+/// is qualified
+fn qualify(
+    path: &path::Path,
+    leading: Option<TokenStream>,
+    mut segments: Vec<TokenStream>,
+) -> TokenStream {
+    match &path.qself {
+        Some(qself) => {
+            let qself = Print::ref_cast(&**qself);
+            let item = segments.pop();
+            quote!(<#qself as #leading #(#segments)::*>::#item)
+        }
+        None => quote!(#leading #(#segments)::*),
+    }
+}
+
+impl Display for TypeNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Display::fmt(&Print::ref_cast(self).to_token_stream(), f)
+    }
+}
+
+impl Display for path::Path {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Display::fmt(&Print::ref_cast(self).to_token_stream(), f)
+    }
+}
+
+/// Prints the params in angle brackets followed by the where clause, if any
+impl Display for Generics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let params = Print::ref_cast(self);
+        let where_clause = Print::ref_cast(WhereClause::ref_cast(self));
+        Display::fmt(&quote!(<#params> #where_clause), f)
     }
 }
+
+/// Replaces the generated names of params, such as `__T0` and `'__a1`, with
+/// the names they are given in `param_map`
+pub(crate) fn with_param_names(tokens: TokenStream, param_map: &SynParamMap) -> TokenStream {
+    let names = param_names(param_map)
+        .map(|(name, generated)| (generated, name))
+        .collect();
+    rename(tokens, &names)
+}
+
+/// Replaces the names of the params in `param_map` with their generated
+/// names, the inverse of `with_param_names`
+pub(crate) fn with_generated_names(tokens: TokenStream, param_map: &SynParamMap) -> TokenStream {
+    rename(tokens, &param_names(param_map).collect())
+}
+
+/// The name of each param in `param_map` along with its generated name,
+/// without the apostrophe of lifetimes
+fn param_names(param_map: &SynParamMap) -> impl Iterator<Item = (String, String)> + '_ {
+    param_map.iter().map(|(name, param)| {
+        let generated = match param {
+            GenericParam::Const(const_param) => Print::ref_cast(const_param).to_token_stream(),
+            param => Print::ref_cast(param).to_token_stream(),
+        };
+        (
+            name.trim_start_matches('\'').to_owned(),
+            generated.to_string().trim_start_matches('\'').to_owned(),
+        )
+    })
+}
+
+fn rename(tokens: TokenStream, names: &BTreeMap<String, String>) -> TokenStream {
+    tokens
+        .into_iter()
+        .map(|token| match token {
+            TokenTree::Ident(ident) => match names.get(&ident.to_string()) {
+                Some(name) => TokenTree::Ident(Ident::new(name, ident.span())),
+                None => TokenTree::Ident(ident),
+            },
+            TokenTree::Group(group) => {
+                let mut renamed = Group::new(group.delimiter(), rename(group.stream(), names));
+                renamed.set_span(group.span());
+                TokenTree::Group(renamed)
+            }
+            other => other,
+        })
+        .collect()
+}
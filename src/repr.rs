@@ -0,0 +1,100 @@
+use crate::attr;
+use syn::{Attribute, Lit, Meta, NestedMeta};
+
+/// A modifier of a `#[repr(...)]` attribute
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Repr {
+    /// `repr(C)`
+    C,
+    /// `repr(transparent)`
+    Transparent,
+    /// `repr(packed)` or `repr(packed(N))`
+    Packed(Option<u64>),
+    /// `repr(align(N))`
+    Align(u64),
+    /// A primitive representation such as `repr(u8)`
+    Int(IntRepr),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IntRepr {
+    U8,
+    U16,
+    U32,
+    U64,
+    U128,
+    Usize,
+    I8,
+    I16,
+    I32,
+    I64,
+    I128,
+    Isize,
+}
+
+impl IntRepr {
+    fn from_ident(ident: &str) -> Option<Self> {
+        use IntRepr::*;
+        Some(match ident {
+            "u8" => U8,
+            "u16" => U16,
+            "u32" => U32,
+            "u64" => U64,
+            "u128" => U128,
+            "usize" => Usize,
+            "i8" => I8,
+            "i16" => I16,
+            "i32" => I32,
+            "i64" => I64,
+            "i128" => I128,
+            "isize" => Isize,
+            _ => return None,
+        })
+    }
+}
+
+/// Collects the modifiers of every `#[repr(...)]` attribute in order.
+/// Modifiers that are not recognized are skipped, since the compiler reports
+/// them itself.
+pub(crate) fn reprs(attrs: &[Attribute]) -> Vec<Repr> {
+    attr::filter(attrs, "repr")
+        .into_iter()
+        .filter_map(|attr| match attr.parse_meta() {
+            Ok(Meta::List(list)) => Some(list.nested),
+            _ => None,
+        })
+        .flatten()
+        .filter_map(|nested| match nested {
+            NestedMeta::Meta(meta) => repr(&meta),
+            NestedMeta::Lit(_) => None,
+        })
+        .collect()
+}
+
+fn repr(meta: &Meta) -> Option<Repr> {
+    match meta {
+        Meta::Path(path) => {
+            let ident = path.get_ident()?.to_string();
+            match ident.as_str() {
+                "C" => Some(Repr::C),
+                "transparent" => Some(Repr::Transparent),
+                "packed" => Some(Repr::Packed(None)),
+                other => IntRepr::from_ident(other).map(Repr::Int),
+            }
+        }
+        Meta::List(list) => {
+            let value = match list.nested.first()? {
+                NestedMeta::Lit(Lit::Int(int)) => int.base10_parse().ok()?,
+                _ => return None,
+            };
+            if list.path.is_ident("packed") {
+                Some(Repr::Packed(Some(value)))
+            } else if list.path.is_ident("align") {
+                Some(Repr::Align(value))
+            } else {
+                None
+            }
+        }
+        Meta::NameValue(_) => None,
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{Function, Parent, Path, TypeNode};
+use crate::{Function, Module, Parent, Path, TypeNode};
 use std::rc::Rc;
 
 pub trait RuntimeType {
@@ -26,6 +26,12 @@ impl RuntimeType for TypeNode {
     }
 }
 
+impl RuntimeFunction for Rc<Function> {
+    fn SELF(self) -> Rc<Function> {
+        self
+    }
+}
+
 impl RuntimeType for Path {
     fn SELF(self) -> TypeNode {
         TypeNode::Path(self)
@@ -44,4 +50,84 @@ pub mod prelude {
             TypeNode::new_primitive_str()
         }
     }
+
+    macro_rules! primitive_types {
+        ($($name:ident)*) => {
+            $(
+                #[allow(non_camel_case_types)]
+                #[derive(Copy, Clone)]
+                pub struct $name;
+
+                impl RuntimeType for $name {
+                    fn SELF(self) -> TypeNode {
+                        TypeNode::Path(Path::empty().get_simple_path(stringify!($name)))
+                    }
+                }
+            )*
+        };
+    }
+
+    primitive_types! {
+        i8 i16 i32 i64 i128 isize
+        u8 u16 u32 u64 u128 usize
+        f32 f64
+        bool char
+    }
+
+    /// `&T`
+    #[derive(Copy, Clone)]
+    pub struct Ref<T>(pub T);
+
+    /// `&mut T`
+    #[derive(Copy, Clone)]
+    pub struct RefMut<T>(pub T);
+
+    impl<T: RuntimeType> RuntimeType for Ref<T> {
+        fn SELF(self) -> TypeNode {
+            self.0.SELF().new_reference()
+        }
+    }
+
+    impl<T: RuntimeType> RuntimeType for RefMut<T> {
+        fn SELF(self) -> TypeNode {
+            self.0.SELF().new_reference_mut()
+        }
+    }
+}
+
+/// Runtime types of generic std types. They are kept out of the prelude,
+/// which `library!` modules glob import, so that they do not shadow the std
+/// prelude there.
+pub mod std_types {
+    use super::*;
+
+    macro_rules! std_generic_types {
+        ($($module:ident::$name:ident<$($param:ident),*>;)*) => {
+            $(
+                #[derive(Copy, Clone)]
+                pub struct $name<$($param),*>($(pub $param),*);
+
+                impl<$($param: RuntimeType),*> RuntimeType for $name<$($param),*> {
+                    #[allow(non_snake_case)]
+                    fn SELF(self) -> TypeNode {
+                        let $name($($param),*) = self;
+                        let path = Module::std_module()
+                            .get_module(stringify!($module))
+                            .get_simple_path(stringify!($name));
+                        $(let path = path.append_type_arg($param.SELF());)*
+                        TypeNode::Path(path)
+                    }
+                }
+            )*
+        };
+    }
+
+    // Each type wraps the runtime types of its arguments, so `Option(u8)` is
+    // the runtime type of `::std::option::Option<u8>`.
+    std_generic_types! {
+        option::Option<T>;
+        vec::Vec<T>;
+        result::Result<T, E>;
+        boxed::Box<T>;
+    }
 }
@@ -1,6 +1,6 @@
 use crate::{
     GenericArgument, GenericParam, Generics, GlobalCounter, Lifetime, ParamMap, Path,
-    PathArguments, SynParamMap,
+    PathArguments, ReflectError, SynParamMap,
     TypeNode::{self, *},
     TypeParamBound, LIFETIMES,
 };
@@ -12,6 +12,12 @@ pub struct Signature {
     pub(crate) receiver: Receiver,
     pub(crate) inputs: Vec<TypeNode>,
     pub(crate) output: TypeNode,
+    /// Whether this is an `async fn`. The output is the type produced by
+    /// awaiting the call.
+    pub(crate) is_async: bool,
+    /// Whether this is an `unsafe fn`, whose calls are wrapped in an
+    /// `unsafe` block
+    pub(crate) is_unsafe: bool,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -89,9 +95,50 @@ impl Signature {
             receiver: Receiver::NoSelf,
             inputs: Vec::new(),
             output: TypeNode::new_unit(),
+            is_async: false,
+            is_unsafe: false,
         }
     }
 
+    /// Appends an argument of type `ty`, as in `add_input`. The arguments of
+    /// generated functions are named by position, so inputs have no name of
+    /// their own.
+    pub fn input(mut self, ty: TypeNode) -> Self {
+        self.add_input(ty);
+        self
+    }
+
+    /// The return type, as in `set_output`
+    pub fn output(mut self, ty: TypeNode) -> Self {
+        self.set_output(ty);
+        self
+    }
+
+    /// Takes `&self`, as in `set_self_by_reference`
+    pub fn with_self_ref(mut self) -> Self {
+        self.set_self_by_reference();
+        self
+    }
+
+    /// Takes `&mut self`, as in `set_self_by_reference_mut`
+    pub fn with_self_mut_ref(mut self) -> Self {
+        self.set_self_by_reference_mut();
+        self
+    }
+
+    pub fn with_generics(mut self, generics: Generics) -> Self {
+        self.generics = generics;
+        self
+    }
+
+    pub fn set_async(&mut self) {
+        self.is_async = true;
+    }
+
+    pub fn set_unsafe(&mut self) {
+        self.is_unsafe = true;
+    }
+
     pub fn set_self_by_value(&mut self) {
         self.receiver = Receiver::SelfByValue;
     }
@@ -128,7 +175,7 @@ impl Signature {
         <Self as SetOutput<'a, T>>::set_output(self, into_output);
     }
 
-    pub fn set_generic_params(&mut self, params: &[&str]) {
+    pub fn set_generic_params(&mut self, params: &[&str]) -> Result<(), ReflectError> {
         self.generics.set_generic_params(params)
     }
 
@@ -136,8 +183,8 @@ impl Signature {
         self.generics.param_map.append(param_map);
     }
 
-    pub fn set_generic_constraints(&mut self, constraints: &[&str]) {
-        self.generics.set_generic_constraints(constraints);
+    pub fn set_generic_constraints(&mut self, constraints: &[&str]) -> Result<(), ReflectError> {
+        self.generics.set_generic_constraints(constraints)
     }
 
     /// Explicitly insert elided lifetimes
@@ -148,7 +195,7 @@ impl Signature {
         // We need to insert the elided lifetimes first in the params so we
         // temporarily swap the params with an empty Vec, and then extend that
         // Vec with the old params in the end
-        let params = std::mem::replace(&mut generics.params, Vec::new());
+        let params = std::mem::take(&mut generics.params);
 
         match &mut self.receiver {
             NoSelf => {
@@ -219,15 +266,15 @@ impl TypeNode {
                     ty.insert_new_lifetimes(params);
                 }
             }
-            Dereference(node) => node.insert_new_lifetimes(params),
-            TraitObject(bounds) => {
+            Dereference(node) | Slice(node) => node.insert_new_lifetimes(params),
+            TraitObject(bounds) | ImplTrait(bounds) => {
                 for bound in bounds.iter_mut() {
                     if let TypeParamBound::Trait(bound) = bound {
                         bound.path.insert_new_lifetimes(params);
                     }
                 }
             }
-            Path(path) => path.insert_new_lifetimes(params),
+            Path(path) | Associated(path) => path.insert_new_lifetimes(params),
             _ => {}
         }
     }
@@ -247,15 +294,15 @@ impl TypeNode {
                     ty.insert_new_lifetimes2(new_lifetime, params);
                 }
             }
-            Dereference(node) => node.insert_new_lifetimes2(new_lifetime, params),
-            TraitObject(bounds) => {
+            Dereference(node) | Slice(node) => node.insert_new_lifetimes2(new_lifetime, params),
+            TraitObject(bounds) | ImplTrait(bounds) => {
                 for bound in bounds.iter_mut() {
                     if let TypeParamBound::Trait(bound) = bound {
                         bound.path.insert_new_lifetimes2(new_lifetime, params);
                     }
                 }
             }
-            Path(path) => path.insert_new_lifetimes2(new_lifetime, params),
+            Path(path) | Associated(path) => path.insert_new_lifetimes2(new_lifetime, params),
             _ => {}
         }
     }
@@ -264,12 +311,12 @@ impl TypeNode {
         match self {
             Reference { .. } => true,
             Tuple(types) => types.iter().any(Self::has_lifetimes),
-            Dereference(node) => node.has_lifetimes(),
-            TraitObject(bounds) => bounds.iter().any(|bound| match bound {
+            Dereference(node) | Slice(node) => node.has_lifetimes(),
+            TraitObject(bounds) | ImplTrait(bounds) => bounds.iter().any(|bound| match bound {
                 TypeParamBound::Trait(bound) => bound.path.has_lifetimes(),
                 TypeParamBound::Lifetime(_) => true,
             }),
-            Path(path) => path.has_lifetimes(),
+            Path(path) | Associated(path) => path.has_lifetimes(),
             _ => false,
         }
     }
@@ -277,6 +324,9 @@ impl TypeNode {
 
 impl Path {
     fn insert_new_lifetimes(&mut self, params: &mut Vec<GenericParam>) {
+        if let Some(qself) = &mut self.qself {
+            qself.insert_new_lifetimes(params);
+        }
         for segment in &mut self.path {
             match &mut segment.args {
                 PathArguments::None => {}
@@ -295,6 +345,9 @@ impl Path {
     }
 
     fn insert_new_lifetimes2(&mut self, lifetime: Lifetime, params: &mut Vec<GenericParam>) {
+        if let Some(qself) = &mut self.qself {
+            qself.insert_new_lifetimes2(lifetime, params);
+        }
         for segment in &mut self.path {
             match &mut segment.args {
                 PathArguments::None => {}
@@ -313,16 +366,21 @@ impl Path {
     }
 
     pub(crate) fn has_lifetimes(&self) -> bool {
-        self.path.iter().any(|segment| match &segment.args {
-            PathArguments::None => false,
-            PathArguments::AngleBracketed(args) => args.args.args.iter().any(|arg| match arg {
-                GenericArgument::Type(ty) => ty.has_lifetimes(),
-                GenericArgument::Lifetime(_) => true,
-                _ => unimplemented!(),
-            }),
-            PathArguments::Parenthesized(args) => {
-                unimplemented!("Path::has_lifetimes: PathArguments::Parenthesized")
-            }
-        })
+        let qself_has_lifetimes = self
+            .qself
+            .as_ref()
+            .is_some_and(|qself| qself.has_lifetimes());
+        qself_has_lifetimes
+            || self.path.iter().any(|segment| match &segment.args {
+                PathArguments::None => false,
+                PathArguments::AngleBracketed(args) => args.args.args.iter().any(|arg| match arg {
+                    GenericArgument::Type(ty) => ty.has_lifetimes(),
+                    GenericArgument::Lifetime(_) => true,
+                    _ => unimplemented!(),
+                }),
+                PathArguments::Parenthesized(args) => {
+                    unimplemented!("Path::has_lifetimes: PathArguments::Parenthesized")
+                }
+            })
     }
 }
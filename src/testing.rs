@@ -0,0 +1,240 @@
+//! Helpers for tests of reflect and of derive macros built on it.
+
+use crate::{global_data, Generics, ReflectError, TypeNode};
+use proc_macro2::TokenStream;
+use quote::quote;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// Clears the values, invocations, macro invocations and module items
+/// recorded on this thread, so that values built outside of a derive do not
+/// carry over into the next test.
+///
+/// The numbering of type params, lifetimes and const params is not restarted.
+/// Functions declared with `library!` cache their signatures per thread, and
+/// params numbered again from the start would collide with the ones in those
+/// signatures.
+pub fn reset_global_state() {
+    global_data::clear();
+}
+
+/// Converts `ty` to a `TypeNode`, with the params of `generics` in scope, and
+/// back to syn with the params under their original names. Types that reflect
+/// does not support give `ReflectError::Unsupported`.
+pub fn round_trip_type(ty: syn::Type, generics: syn::Generics) -> Result<syn::Type, ReflectError> {
+    let mut param_map = Generics::syn_to_generics(generics)?.param_map().clone();
+    let node = TypeNode::syn_to_type(ty, &mut param_map)?;
+    node.to_syn_with_names(&param_map)
+}
+
+/// Generates random types that reflect supports, for checking properties such
+/// as the one of `round_trip_type` over many types. The same seed always gives
+/// the same sequence of types.
+///
+/// Trait objects, and `impl Trait` types with more than one bound, are
+/// generated inside parentheses, as reflect prints them, so every generated
+/// type round trips to the same tokens.
+pub struct TypeGenerator {
+    state: u64,
+}
+
+impl TypeGenerator {
+    const MAX_DEPTH: usize = 3;
+
+    pub fn new(seed: u64) -> Self {
+        // Scramble the seed with splitmix64 so that every seed, including
+        // the golden ratio constant, gives a well mixed state. xorshift gets
+        // stuck on zero, so that one output is skipped.
+        let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        TypeGenerator {
+            state: (z ^ (z >> 31)).max(1),
+        }
+    }
+
+    /// The generics in scope of the generated types, `<'a, T, U>`
+    pub fn generics() -> syn::Generics {
+        syn::parse_quote!(<'a, T, U>)
+    }
+
+    pub fn generate(&mut self) -> syn::Type {
+        self.generate_at(Self::MAX_DEPTH)
+    }
+
+    fn generate_at(&mut self, depth: usize) -> syn::Type {
+        let kinds = if depth == 0 { 3 } else { 7 };
+        match self.below(kinds) {
+            0 => self.pick(&[syn::parse_quote!(T), syn::parse_quote!(U)]),
+            1 => self.pick(&[
+                syn::parse_quote!(u8),
+                syn::parse_quote!(str),
+                syn::parse_quote!(::std::string::String),
+            ]),
+            2 => syn::parse_quote!(()),
+            3 => {
+                let lifetime = self.lifetime();
+                let mutability = if self.below(2) == 0 {
+                    Some(quote!(mut))
+                } else {
+                    None
+                };
+                let inner = self.generate_at(depth - 1);
+                syn::parse_quote!(&#lifetime #mutability #inner)
+            }
+            4 => {
+                let first = self.generate_at(depth - 1);
+                if self.below(3) == 0 {
+                    syn::parse_quote!((#first,))
+                } else {
+                    let second = self.generate_at(depth - 1);
+                    syn::parse_quote!((#first, #second))
+                }
+            }
+            5 => {
+                let inner = self.generate_at(depth - 1);
+                match self.below(3) {
+                    0 => syn::parse_quote!(::std::vec::Vec<#inner>),
+                    1 => {
+                        let error = self.generate_at(depth - 1);
+                        syn::parse_quote!(::std::result::Result<#inner, #error>)
+                    }
+                    _ => syn::parse_quote!(::std::boxed::Box<#inner>),
+                }
+            }
+            _ => {
+                let inner = self.generate_at(depth - 1);
+                let (bounds, several) = match self.lifetime() {
+                    Some(lifetime) => (
+                        quote!(::std::iter::Iterator<Item = #inner> + #lifetime),
+                        true,
+                    ),
+                    None => (quote!(::std::convert::AsRef<#inner>), false),
+                };
+                if self.below(2) == 0 {
+                    syn::parse_quote!((dyn #bounds))
+                } else if several {
+                    syn::parse_quote!((impl #bounds))
+                } else {
+                    syn::parse_quote!(impl #bounds)
+                }
+            }
+        }
+    }
+
+    fn lifetime(&mut self) -> Option<syn::Lifetime> {
+        match self.below(3) {
+            0 => Some(syn::parse_quote!('a)),
+            1 => Some(syn::parse_quote!('static)),
+            _ => None,
+        }
+    }
+
+    fn pick(&mut self, types: &[syn::Type]) -> syn::Type {
+        types[self.below(types.len())].clone()
+    }
+
+    /// A random number in `0..n`
+    fn below(&mut self, n: usize) -> usize {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        (self.state % n as u64) as usize
+    }
+}
+
+/// Asserts that `actual` is the same token sequence as the Rust code in
+/// `expected`. Whitespace and formatting of `expected` do not matter. On
+/// failure the panic message holds a line diff of the two, with one
+/// statement or brace per line.
+pub fn assert_tokens_eq(actual: TokenStream, expected: &str) {
+    let expected: TokenStream = expected
+        .parse()
+        .expect("assert_tokens_eq: Expected output is not valid tokens");
+    let actual = actual.to_string();
+    let expected = expected.to_string();
+    if actual != expected {
+        panic!(
+            "generated tokens differ from the expected tokens\n{}",
+            diff(&lines(&expected), &lines(&actual))
+        );
+    }
+}
+
+/// Asserts that `actual` matches the golden file
+/// `tests/snapshots/<snapshot_name>.rs` of the crate under test, as in
+/// `assert_tokens_eq`
+pub fn assert_tokens_eq_snapshot(actual: TokenStream, snapshot_name: &str) {
+    let manifest_dir = env::var_os("CARGO_MANIFEST_DIR")
+        .expect("assert_tokens_eq_snapshot: CARGO_MANIFEST_DIR is not set");
+    let path = PathBuf::from(manifest_dir)
+        .join("tests")
+        .join("snapshots")
+        .join(snapshot_name)
+        .with_extension("rs");
+    match fs::read_to_string(&path) {
+        Ok(expected) => assert_tokens_eq(actual, &expected),
+        Err(err) => panic!(
+            "assert_tokens_eq_snapshot: Failed to read {}: {}\nactual output:\n{}",
+            path.display(),
+            err,
+            lines(&actual.to_string()).join("\n")
+        ),
+    }
+}
+
+/// Breaks the tokens into lines after every `;` and around braces
+fn lines(tokens: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = Vec::new();
+    for token in tokens.split(' ') {
+        if token == "}" && !line.is_empty() {
+            lines.push(line.join(" "));
+            line.clear();
+        }
+        line.push(token);
+        if token.ends_with(['{', '}', ';']) {
+            lines.push(line.join(" "));
+            line.clear();
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line.join(" "));
+    }
+    lines
+}
+
+/// A colored line diff, based on the longest common subsequence of the lines
+fn diff(expected: &[String], actual: &[String]) -> String {
+    let (n, m) = (expected.len(), actual.len());
+    // common[i][j] is the length of the longest common subsequence of
+    // expected[i..] and actual[j..]
+    let mut common = vec![vec![0; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            common[i][j] = if expected[i] == actual[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut out = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && expected[i] == actual[j] {
+            out += &format!("  {}\n", expected[i]);
+            i += 1;
+            j += 1;
+        } else if j == m || (i < n && common[i + 1][j] >= common[i][j + 1]) {
+            out += &format!("\x1b[31m- {}\x1b[0m\n", expected[i]);
+            i += 1;
+        } else {
+            out += &format!("\x1b[32m+ {}\x1b[0m\n", actual[j]);
+            j += 1;
+        }
+    }
+    out
+}
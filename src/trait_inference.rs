@@ -1,8 +1,9 @@
+use crate::generics::Expr;
 use crate::{
     AngleBracketedGenericArguments, CompleteFunction, CompleteImpl, Function, GenericArgument,
-    GenericArguments, GenericConstraint, GenericParam, GlobalBorrow, Lifetime, LifetimeDef, Parent,
-    ParentKind, Path, PathArguments, PredicateType, Push, Receiver, TraitBound, TypeEqualitySetRef,
-    TypeNode, TypeParamBound, WipFunction, WipImpl, INVOKES, STATIC_LIFETIME, VALUES,
+    GenericArguments, GenericConstraint, GenericParam, Lifetime, LifetimeDef, Parent, ParentKind,
+    Path, PathArguments, PredicateType, Push, Receiver, TraitBound, TypeEqualitySetRef, TypeNode,
+    TypeParamBound, WipFunction, WipImpl, INVOKES, STATIC_LIFETIME, VALUES,
 };
 // FxHasher is used because it is a faster hashing algorithm than the
 // default one, but most importantly because it has a hasher with a default
@@ -11,7 +12,6 @@ use crate::{
 use fxhash::{FxHashMap, FxHashSet};
 use std::cmp::Ordering;
 use std::collections::{btree_map, BTreeMap, BTreeSet, HashMap, HashSet};
-use std::convert::identity;
 use std::hash::Hash;
 use std::iter::Extend;
 use std::ops::{Index, IndexMut};
@@ -130,7 +130,7 @@ impl SupertypeMap {
         self.map.get(lifetime)
     }
 
-    fn entry(&mut self, lifetime: Lifetime) -> btree_map::Entry<Lifetime, Vec<Lifetime>> {
+    fn entry(&mut self, lifetime: Lifetime) -> btree_map::Entry<'_, Lifetime, Vec<Lifetime>> {
         self.map.entry(lifetime)
     }
 }
@@ -355,7 +355,7 @@ impl ConstraintSet {
             set: self
                 .set
                 .into_iter()
-                .map(|mut constraint| {
+                .filter_map(|mut constraint| {
                     let is_relevant = constraint.make_relevant(
                         concrete_maps_and_sets,
                         relevant_generic_params,
@@ -367,7 +367,6 @@ impl ConstraintSet {
                         None
                     }
                 })
-                .filter_map(identity)
                 .collect(),
         }
     }
@@ -553,6 +552,15 @@ impl TypeEqualitySets {
                     bounds,
                 }));
             }
+            // Like a type param, `impl Trait` stands for any type meeting its
+            // bounds
+            (ImplTrait(bounds), other) | (other, ImplTrait(bounds)) => {
+                constraints.insert(GenericConstraint::Type(PredicateType {
+                    lifetimes: Vec::new(),
+                    bounded_ty: other,
+                    bounds,
+                }));
+            }
             // A reference and a mutable reference are not equal, but a mutable reference may conform to a
             // normal reference, so the inner types may be considered equal
             (
@@ -593,7 +601,7 @@ impl TypeEqualitySets {
                 if types1.len() == types2.len() {
                     types1
                         .into_iter()
-                        .zip(types2.into_iter())
+                        .zip(types2)
                         .for_each(|(subtype, supertype)| {
                             self.insert_as_subtype_or_equal(
                                 subtype,
@@ -632,6 +640,15 @@ impl TypeEqualitySets {
                     bounds,
                 }));
             }
+            // Like a type param, `impl Trait` stands for any type meeting its
+            // bounds
+            (ImplTrait(bounds), other) | (other, ImplTrait(bounds)) => {
+                constraints.insert(GenericConstraint::Type(PredicateType {
+                    lifetimes: Vec::new(),
+                    bounded_ty: other,
+                    bounds,
+                }));
+            }
             (
                 Reference {
                     is_mut: is_mut1,
@@ -648,7 +665,7 @@ impl TypeEqualitySets {
                     if let (Some(subtype), Some(supertype)) = (lifetime1, lifetime2) {
                         subtypes.insert(subtype, supertype);
                     }
-                    self.insert_inner_type_as_equal(&*inner1, &*inner2, constraints, subtypes);
+                    self.insert_inner_type_as_equal(&inner1, &inner2, constraints, subtypes);
                     self.insert_as_equal(*inner1, *inner2);
                 } else if !is_mut1 && !is_mut2 {
                     if let (Some(subtype), Some(supertype)) = (lifetime1, lifetime2) {
@@ -713,6 +730,9 @@ impl TypeEqualitySets {
                 }
                 self.insert_types_as_equal(*inner1.clone(), *inner2.clone(), constraints, subtypes)
             }
+            (Slice(elem1), Slice(elem2)) => {
+                self.insert_types_as_equal(*elem1.clone(), *elem2.clone(), constraints, subtypes)
+            }
             (Path(path1), Path(path2)) => {
                 self.insert_path_arguments_as_equal(path1, path2, constraints, subtypes);
             }
@@ -782,6 +802,8 @@ impl TypeEqualitySets {
                         ) => {
                             subtypes.insert_as_equal(*lifetime1, *lifetime2);
                         }
+                        // Const arguments hold no types to infer
+                        (GenericArgument::Const(_), GenericArgument::Const(_)) => {}
                         _ => unimplemented!("TypeEqualitySets::insert_inner_type_as_equal: Path"),
                     })
             }
@@ -927,7 +949,7 @@ impl WipFunction {
         let mut supertype_map = SupertypeMap::new();
 
         INVOKES.with_borrow(|invokes| {
-            for invoke in invokes[self.invokes.start.0..self.invokes.end.unwrap().0].iter() {
+            for invoke in &invokes[self.invokes.start.0..self.invokes.end.unwrap().0] {
                 let parent = &invoke.function.parent;
                 let sig = &invoke.function.sig;
                 let args_iter = match sig.receiver {
@@ -1086,7 +1108,8 @@ impl WipFunction {
                         .most_concrete_lifetime_map
                         .insert(*param, *param);
                 }
-                _ => unimplemented!("WipFunction::make_concrete_function: Const"),
+                // A const param stays as it is declared
+                GenericParam::Const(_) => {}
             }
         }
 
@@ -1149,7 +1172,9 @@ fn params_to_args(params: &[GenericParam]) -> PathArguments {
                 .map(|param| match param {
                     GenericParam::Type(ty) => GenericArgument::Type(TypeNode::TypeParam(*ty)),
                     GenericParam::Lifetime(lifetime) => GenericArgument::Lifetime(*lifetime),
-                    GenericParam::Const(_) => unimplemented!(),
+                    GenericParam::Const(const_param) => {
+                        GenericArgument::Const(Expr::Param(*const_param))
+                    }
                 })
                 .collect(),
         },
@@ -1177,7 +1202,7 @@ fn get_relevant_generic_params(
         type_equality_sets,
     };
 
-    for param in original_generic_params.iter() {
+    for param in original_generic_params {
         match param {
             GenericParam::Type(type_param) => {
                 let type_param = param.type_param().unwrap();
@@ -1204,7 +1229,9 @@ fn get_relevant_generic_params(
                 relevant_generic_params.insert(GenericParam::Lifetime(lifetime));
             }
 
-            GenericParam::Const(_) => unimplemented!(),
+            GenericParam::Const(_) => {
+                relevant_generic_params.insert(*param);
+            }
         }
     }
 
@@ -1229,7 +1256,9 @@ fn get_args(
                     lifetime.make_most_concrete(transitive_closure);
                     GenericArgument::Lifetime(lifetime)
                 }
-                GenericParam::Const(_) => unimplemented!(),
+                GenericParam::Const(const_param) => {
+                    GenericArgument::Const(Expr::Param(const_param))
+                }
             })
             .collect(),
     }
@@ -1345,6 +1374,12 @@ impl TypeNode {
             Reference {
                 lifetime, inner, ..
             } => inner.is_relevant_for_constraint(type_equality_sets, relevant_generic_params),
+            Slice(elem) => {
+                elem.is_relevant_for_constraint(type_equality_sets, relevant_generic_params)
+            }
+            Associated(path) => path.qself.as_ref().is_some_and(|base| {
+                base.is_relevant_for_constraint(type_equality_sets, relevant_generic_params)
+            }),
 
             _ => false,
         }
@@ -1373,7 +1408,8 @@ impl TypeNode {
     ) -> Self {
         use TypeNode::*;
         match (ty1, ty2) {
-            (Infer, mut node) | (mut node, Infer) => {
+            // `!` coerces to any type, so the other type is the concrete one
+            (Infer | Never, mut node) | (mut node, Infer | Never) => {
                 node.make_most_concrete_inner(concrete_maps_and_sets, transitive_closure);
                 node
             }
@@ -1391,7 +1427,7 @@ impl TypeNode {
             (Tuple(types1), Tuple(types2)) if types1.len() == types2.len() => Tuple(
                 types1
                     .into_iter()
-                    .zip(types2.into_iter())
+                    .zip(types2)
                     .map(|(ty1, ty2)| {
                         Self::make_most_concrete_from_pair(
                             ty1,
@@ -1427,7 +1463,16 @@ impl TypeNode {
                     .or(lifetime1)
                     .or(lifetime2),
             },
-            (TraitObject(_), mut node) | (mut node, TraitObject(_)) => {
+            (Slice(elem1), Slice(elem2)) => Slice(Box::new(Self::make_most_concrete_from_pair(
+                *elem1,
+                *elem2,
+                concrete_maps_and_sets,
+                transitive_closure,
+            ))),
+            (TraitObject(_), mut node)
+            | (mut node, TraitObject(_))
+            | (ImplTrait(_), mut node)
+            | (mut node, ImplTrait(_)) => {
                 node.make_most_concrete_inner(concrete_maps_and_sets, transitive_closure);
                 node
             }
@@ -1436,6 +1481,12 @@ impl TypeNode {
                 node.make_most_concrete_inner(concrete_maps_and_sets, transitive_closure);
                 node
             }
+            // The associated type is only known once the impl is chosen, so
+            // the other type is the concrete one
+            (Associated { .. }, mut node) | (mut node, Associated { .. }) => {
+                node.make_most_concrete_inner(concrete_maps_and_sets, transitive_closure);
+                node
+            }
             (node1, node2) => panic!(
                 "TypeNode: make_most_concrete_pair: incompatible types \n{:#?}\nand\n{:#?}",
                 node1, node2
@@ -1462,7 +1513,10 @@ impl TypeNode {
                     lifetime.make_most_concrete(transitive_closure);
                 }
             }
-            Path(path) => path.make_most_concrete_inner(concrete_maps_and_sets, transitive_closure),
+            Slice(elem) => elem.make_most_concrete(concrete_maps_and_sets, transitive_closure),
+            Path(path) | Associated(path) => {
+                path.make_most_concrete_inner(concrete_maps_and_sets, transitive_closure)
+            }
             node => {}
         }
     }
@@ -1475,14 +1529,14 @@ impl TypeNode {
         use TypeNode::*;
         match self {
             Tuple(types) => {
-                for ty in types.iter() {
+                for ty in types {
                     ty.inner_params(type_equality_sets, relevant_generic_params)
                 }
             }
-            Reference { inner, .. } => {
+            Reference { inner, .. } | Slice(inner) => {
                 inner.inner_params(type_equality_sets, relevant_generic_params)
             }
-            Path(path) => {
+            Path(path) | Associated(path) => {
                 path.inner_params(type_equality_sets, relevant_generic_params);
             }
             TypeParam(type_param) => {
@@ -1582,6 +1636,12 @@ impl Path {
                     lifetime.is_relevant_for_constraint(relevant_generic_params)
                 }
 
+                GenericArgument::Const(Expr::Param(const_param)) => {
+                    relevant_generic_params.contains(&GenericParam::Const(*const_param))
+                }
+
+                GenericArgument::Const(Expr::Value(_)) => true,
+
                 _ => unimplemented!("is_relevant_for_constraint: PathArguments::AngleBracketed"),
             }),
 
@@ -1596,6 +1656,9 @@ impl Path {
         type_equality_sets: &mut TypeEqualitySets,
         relevant_generic_params: &mut BTreeSet<GenericParam>,
     ) {
+        if let Some(qself) = &self.qself {
+            qself.inner_params(type_equality_sets, relevant_generic_params);
+        }
         for segment in &self.path {
             match &segment.args {
                 PathArguments::None => {}
@@ -1608,6 +1671,10 @@ impl Path {
                             GenericArgument::Lifetime(lifetime) => {
                                 relevant_generic_params.insert(GenericParam::Lifetime(*lifetime));
                             }
+                            GenericArgument::Const(Expr::Param(const_param)) => {
+                                relevant_generic_params.insert(GenericParam::Const(*const_param));
+                            }
+                            GenericArgument::Const(Expr::Value(_)) => {}
                             _ => unimplemented!(),
                         }
                     }
@@ -1624,7 +1691,11 @@ impl Path {
         concrete_maps_and_sets: &mut ConcreteMapAndSets,
         transitive_closure: &mut TransitiveClosure,
     ) {
-        let path_len = self.path.len();
+        if let Some(qself) = &mut self.qself {
+            qself.make_most_concrete(concrete_maps_and_sets, transitive_closure);
+        }
+        // The arguments of a qualified path are on its trait, before the item
+        let path_len = self.path.len() - usize::from(self.qself.is_some());
         let segment = &mut self.path[path_len - 1];
         match &mut segment.args {
             PathArguments::None => {}
@@ -1637,6 +1708,7 @@ impl Path {
                     GenericArgument::Lifetime(lifetime) => {
                         lifetime.make_most_concrete(transitive_closure)
                     }
+                    GenericArgument::Const(_) => {}
                     _ => unimplemented!(),
                 });
             }
@@ -1721,6 +1793,9 @@ impl Path {
                                     GenericArgument::Lifetime(lifetime1),
                                     GenericArgument::Lifetime(lifetime2),
                                 ) => GenericArgument::Lifetime((*lifetime1).min(*lifetime2)),
+                                (GenericArgument::Const(expr), GenericArgument::Const(_)) => {
+                                    GenericArgument::Const(expr.clone())
+                                }
                                 _ => unimplemented!(
                                     "Path::make_most_concrete_from_pair: GenericArgument"
                                 ),
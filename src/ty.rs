@@ -1,10 +1,14 @@
+use crate::generics::{Canonicalize, RenameTypeParams};
+use crate::path::PathArguments;
+use crate::visit::{walk_type_node, FreeParams, TypeVisitor};
 use crate::{
-    generics, Data, GenericParam, Generics, Ident, Lifetime, ParamMap, Path, Print, Struct,
-    SynParamMap, TupleStruct, TypeParam, TypeParamBound,
+    generics, print, Data, FreshGenerics, GenericArgument, Generics, Ident, Lifetime, ParamMap,
+    Path, Print, ReflectError, Struct, SynParamMap, TupleStruct, TypeFolder, TypeParam,
+    TypeParamBound,
 };
-use proc_macro2::TokenStream;
 use quote::{quote, ToTokens};
 use ref_cast::RefCast;
+use std::collections::BTreeSet;
 use std::fmt::Debug;
 use syn::TypePath;
 
@@ -24,6 +28,15 @@ pub enum TypeNode {
     DataStructure(Box<DataStructure>),
     Path(Path),
     TypeParam(TypeParam),
+    /// The never type `!`
+    Never,
+    /// An associated type `<base as Trait>::Assoc`, written as the qualified
+    /// path that `Path::for_trait_method` builds
+    Associated(Path),
+    /// An `impl Trait` type, in argument or return position
+    ImplTrait(Vec<TypeParamBound>),
+    /// A slice type `[T]`
+    Slice(Box<TypeNode>),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -33,6 +46,12 @@ pub struct DataStructure {
     pub data: Data<TypeNode>,
 }
 
+impl DataStructure {
+    pub fn name(&self) -> &Ident {
+        &self.name
+    }
+}
+
 impl TypeNode {
     pub fn new_unit() -> Self {
         TypeNode::Tuple(Vec::new())
@@ -42,10 +61,28 @@ impl TypeNode {
         TypeNode::Tuple(types.to_vec())
     }
 
+    /// The tuple `(elem, elem, ...)` with `n` elements
+    pub fn new_tuple_of_arity(elem: &Self, n: usize) -> Self {
+        TypeNode::Tuple(vec![elem.clone(); n])
+    }
+
     pub fn new_primitive_str() -> Self {
         TypeNode::PrimitiveStr
     }
 
+    pub fn new_never() -> Self {
+        TypeNode::Never
+    }
+
+    /// The associated type `<base as trait_path>::assoc`
+    ///
+    /// # Panics
+    ///
+    /// If `trait_path` is empty or already qualified
+    pub fn new_associated(base: Self, trait_path: Path, assoc: Ident) -> Self {
+        TypeNode::Associated(Path::for_trait_method(trait_path, assoc, base))
+    }
+
     pub fn new_reference(&self) -> Self {
         TypeNode::Reference {
             is_mut: false,
@@ -54,14 +91,20 @@ impl TypeNode {
         }
     }
 
-    pub fn new_reference_with_lifetime(&self, lifetime: &str, param_map: &SynParamMap) -> Self {
-        let lifetime = param_map.get_lifetime(lifetime);
+    /// The reference `&'a self` to the lifetime named `lifetime` in
+    /// `param_map`. Gives `ReflectError::NotALifetime` if there is none.
+    pub fn new_reference_with_lifetime(
+        &self,
+        lifetime: &str,
+        param_map: &SynParamMap,
+    ) -> Result<Self, ReflectError> {
+        let lifetime = param_map.get_lifetime(lifetime)?;
 
-        TypeNode::Reference {
+        Ok(TypeNode::Reference {
             is_mut: false,
             lifetime: Some(lifetime),
             inner: Box::new(self.clone()),
-        }
+        })
     }
 
     pub fn new_reference_mut(&self) -> Self {
@@ -72,14 +115,20 @@ impl TypeNode {
         }
     }
 
-    pub fn new_reference_mut_with_lifetime(&self, lifetime: &str, param_map: &SynParamMap) -> Self {
-        let lifetime = param_map.get_lifetime(lifetime);
+    /// The mutable reference `&'a mut self`, as in
+    /// `new_reference_with_lifetime`
+    pub fn new_reference_mut_with_lifetime(
+        &self,
+        lifetime: &str,
+        param_map: &SynParamMap,
+    ) -> Result<Self, ReflectError> {
+        let lifetime = param_map.get_lifetime(lifetime)?;
 
-        TypeNode::Reference {
+        Ok(TypeNode::Reference {
             is_mut: true,
             lifetime: Some(lifetime),
             inner: Box::new(self.clone()),
-        }
+        })
     }
 
     pub fn dereference(&self) -> Self {
@@ -120,51 +169,177 @@ impl TypeNode {
         }
     }
 
-    pub fn new_trait_object(type_param_bounds: &[&str], param_map: &mut SynParamMap) -> Self {
-        TypeNode::TraitObject(
+    /// The trait object `dyn Bound + ...`. Gives `ReflectError::Parse` if a
+    /// bound does not parse.
+    pub fn new_dyn_trait(
+        type_param_bounds: &[&str],
+        param_map: &mut SynParamMap,
+    ) -> Result<Self, ReflectError> {
+        Ok(TypeNode::TraitObject(
             type_param_bounds
                 .iter()
                 .map(|bound| TypeParamBound::get_type_param_bound(bound, param_map))
-                .collect(),
-        )
+                .collect::<Result<_, _>>()?,
+        ))
     }
 
-    pub fn new_type_param_from_str(type_param: &str, param_map: &mut SynParamMap) -> Self {
-        if let Some(&param) = param_map.get(type_param) {
-            TypeNode::TypeParam(
-                param
-                    .type_param()
-                    .expect("Type::type_param_from_str: Not a type param"),
-            )
+    #[deprecated(note = "renamed to `new_dyn_trait`")]
+    pub fn new_trait_object(
+        type_param_bounds: &[&str],
+        param_map: &mut SynParamMap,
+    ) -> Result<Self, ReflectError> {
+        Self::new_dyn_trait(type_param_bounds, param_map)
+    }
+
+    /// The type `impl Bound + ...`, for use in argument or return position,
+    /// as in `new_dyn_trait`
+    pub fn new_impl_trait(
+        type_param_bounds: &[&str],
+        param_map: &mut SynParamMap,
+    ) -> Result<Self, ReflectError> {
+        Ok(TypeNode::ImplTrait(
+            type_param_bounds
+                .iter()
+                .map(|bound| TypeParamBound::get_type_param_bound(bound, param_map))
+                .collect::<Result<_, _>>()?,
+        ))
+    }
+
+    pub fn new_type_param_from_str(
+        type_param: &str,
+        param_map: &mut SynParamMap,
+    ) -> Result<Self, ReflectError> {
+        param_map
+            .get_type_param(type_param)
+            .map(TypeNode::TypeParam)
+    }
+
+    /// Converts the type back to syn. Params use their generated names and a
+    /// data structure is written as its name. A type containing a
+    /// `Dereference`, which has no syntax of its own, gives
+    /// `ReflectError::Unsupported`.
+    pub fn to_syn(&self) -> Result<syn::Type, ReflectError> {
+        self.check_has_syntax()?;
+        Ok(syn::parse2(Print::ref_cast(self).to_token_stream()).expect("Type::to_syn"))
+    }
+
+    /// Converts the type back to syn, with params under the names they are
+    /// given in `param_map`, as in `to_syn`
+    pub fn to_syn_with_names(&self, param_map: &SynParamMap) -> Result<syn::Type, ReflectError> {
+        self.check_has_syntax()?;
+        let tokens = print::with_param_names(Print::ref_cast(self).to_token_stream(), param_map);
+        Ok(syn::parse2(tokens).expect("Type::to_syn_with_names"))
+    }
+
+    fn check_has_syntax(&self) -> Result<(), ReflectError> {
+        struct FindDereference(bool);
+
+        impl TypeVisitor for FindDereference {
+            fn visit_type(&mut self, node: &TypeNode) {
+                self.0 |= matches!(node, TypeNode::Dereference(_));
+            }
+        }
+
+        let mut find = FindDereference(false);
+        walk_type_node(&mut find, self);
+        if find.0 {
+            Err(ReflectError::Unsupported("dereferenced types"))
         } else {
-            panic!("Type::type_param_from_str: Not a type param")
+            Ok(())
         }
     }
 
-    pub(crate) fn syn_to_type(ty: syn::Type, param_map: &mut SynParamMap) -> Self {
-        match ty {
-            syn::Type::Path(TypePath {
-                //FIXME: add qself to Path
-                qself: None,
-                path,
-            }) => {
+    /// The type with its params renumbered in the order they first appear,
+    /// as in `Generics::canonical`. Types that are the same up to renaming
+    /// their params have equal canonical forms.
+    pub fn canonical(&self) -> Self {
+        Canonicalize::default().fold_type(self)
+    }
+
+    /// Returns true if the types are equal once each of `this_params` in this
+    /// type is taken to be the param in the same position of `other_params`
+    /// in the other type. Params that are in neither list, and lifetimes,
+    /// must be the same in both types. The names of params do not matter.
+    pub fn is_same_shape(
+        &self,
+        other: &Self,
+        this_params: &[TypeParam],
+        other_params: &[TypeParam],
+    ) -> bool {
+        assert_eq!(
+            this_params.len(),
+            other_params.len(),
+            "Type::is_same_shape: Different numbers of params"
+        );
+
+        // Both lists are renamed to the same params, numbered past every
+        // param of the two types so that they can not clash with the others
+        let first_unused = self
+            .free_type_params()
+            .into_iter()
+            .chain(other.free_type_params())
+            .chain(this_params.iter().copied())
+            .chain(other_params.iter().copied())
+            .map(|param| param.0 + 1)
+            .max()
+            .unwrap_or(0);
+        let rename = |params: &[TypeParam]| {
+            RenameTypeParams(
+                params
+                    .iter()
+                    .enumerate()
+                    .map(|(i, param)| (*param, TypeParam(first_unused + i)))
+                    .collect(),
+            )
+        };
+
+        rename(this_params).fold_type(self) == rename(other_params).fold_type(other)
+    }
+
+    /// The type params that appear in the type
+    pub fn free_type_params(&self) -> BTreeSet<TypeParam> {
+        self.free_params().type_params
+    }
+
+    /// The lifetimes other than `'static` that appear in the type
+    pub fn free_lifetimes(&self) -> BTreeSet<Lifetime> {
+        self.free_params().lifetimes
+    }
+
+    pub(crate) fn free_params(&self) -> FreeParams {
+        let mut free = FreeParams::default();
+        walk_type_node(&mut free, self);
+        free
+    }
+
+    /// Converts a syn type, looking up the params it refers to in
+    /// `param_map`. Qualified paths other than associated types, arrays,
+    /// raw pointers, function pointers and macros in type position
+    /// are not supported and give `ReflectError::Unsupported`. Converting
+    /// back with `to_syn_with_names` gives the same type, except that
+    /// parentheses are dropped, or added around trait objects and around
+    /// `impl Trait` types with more than one bound.
+    pub(crate) fn syn_to_type(
+        ty: syn::Type,
+        param_map: &mut SynParamMap,
+    ) -> Result<Self, ReflectError> {
+        Ok(match ty {
+            syn::Type::Path(TypePath { qself: None, path }) => {
                 if let Some(ident) = path.get_ident() {
-                    if let Some(&param) = param_map.get(&ident.to_string()) {
-                        return TypeNode::TypeParam(
-                            param
-                                .type_param()
-                                .expect("syn_to_type: Not a type param ref"),
-                        );
+                    let ident = ident.to_string();
+                    if param_map.get(&ident).is_some() {
+                        return param_map.get_type_param(&ident).map(TypeNode::TypeParam);
                     }
                 }
-                TypeNode::Path(Path::syn_to_path(path, param_map))
+                TypeNode::Path(Path::syn_to_path(path, param_map)?)
             }
 
             syn::Type::Reference(reference) => {
-                let inner = Box::new(Self::syn_to_type(*reference.elem, param_map));
+                let inner = Box::new(Self::syn_to_type(*reference.elem, param_map)?);
                 let lifetime = reference
                     .lifetime
-                    .map(|lifetime| param_map.get_lifetime(&lifetime.to_string()));
+                    .map(|lifetime| param_map.get_lifetime(&lifetime.to_string()))
+                    .transpose()?;
 
                 TypeNode::Reference {
                     is_mut: reference.mutability.is_some(),
@@ -174,7 +349,7 @@ impl TypeNode {
             }
 
             syn::Type::TraitObject(type_trait_object) => TypeNode::TraitObject(
-                generics::syn_to_type_param_bounds(type_trait_object.bounds, param_map).collect(),
+                generics::syn_to_type_param_bounds(type_trait_object.bounds, param_map)?,
             ),
 
             syn::Type::Tuple(type_tuple) => {
@@ -183,96 +358,183 @@ impl TypeNode {
                 } else if type_tuple.elems.len() == 1 && !type_tuple.elems.trailing_punct() {
                     // It is not a tuple. The parentheses were just used to
                     // disambiguate the type.
-                    Self::syn_to_type(type_tuple.elems.into_iter().next().unwrap(), param_map)
+                    Self::syn_to_type(type_tuple.elems.into_iter().next().unwrap(), param_map)?
                 } else {
                     TypeNode::Tuple(
                         type_tuple
                             .elems
                             .into_iter()
                             .map(|elem| Self::syn_to_type(elem, param_map))
-                            .collect(),
+                            .collect::<Result<_, _>>()?,
                     )
                 }
             }
-            _ => unimplemented!("Type::syn_to_type"),
-        }
+            syn::Type::Never(_) => TypeNode::Never,
+            syn::Type::Infer(_) => TypeNode::Infer,
+            syn::Type::Paren(paren) => Self::syn_to_type(*paren.elem, param_map)?,
+            syn::Type::Group(group) => Self::syn_to_type(*group.elem, param_map)?,
+            syn::Type::Path(TypePath {
+                qself: Some(qself),
+                mut path,
+            }) if qself.position > 0 && path.segments.len() == qself.position + 1 => {
+                let assoc = path.segments.pop().unwrap().into_value();
+                if !assoc.arguments.is_empty() {
+                    return Err(ReflectError::Unsupported("generic associated types"));
+                }
+                // Drop the trailing `::` left behind by the associated type
+                let trait_path = syn::Path {
+                    leading_colon: path.leading_colon,
+                    segments: path
+                        .segments
+                        .into_pairs()
+                        .map(|pair| pair.into_value())
+                        .collect(),
+                };
+                TypeNode::new_associated(
+                    Self::syn_to_type(*qself.ty, param_map)?,
+                    Path::syn_to_path(trait_path, param_map)?,
+                    Ident::from(assoc.ident),
+                )
+            }
+            syn::Type::Path(_) => return Err(ReflectError::Unsupported("qualified paths")),
+            syn::Type::Array(_) => return Err(ReflectError::Unsupported("array types")),
+            syn::Type::Slice(slice) => {
+                TypeNode::Slice(Box::new(Self::syn_to_type(*slice.elem, param_map)?))
+            }
+            syn::Type::Ptr(_) => return Err(ReflectError::Unsupported("raw pointer types")),
+            syn::Type::BareFn(_) => {
+                return Err(ReflectError::Unsupported("function pointer types"))
+            }
+            syn::Type::ImplTrait(type_impl_trait) => TypeNode::ImplTrait(
+                generics::syn_to_type_param_bounds(type_impl_trait.bounds, param_map)?,
+            ),
+            syn::Type::Macro(_) => return Err(ReflectError::Unsupported("macro types")),
+            _ => return Err(ReflectError::Unsupported("this kind of type")),
+        })
     }
 }
 
 impl TypeNode {
     pub(crate) fn get_name(&self) -> String {
         match self {
-            //FIXME: Add more TypeNode branches
             Self::Tuple(types) => {
                 let types = types.iter().map(Print::ref_cast);
                 quote!((#(#types),*)).to_string()
             }
             Self::PrimitiveStr => String::from("str"),
             Self::DataStructure(data) => data.name.to_string(),
-            Self::Reference { inner, .. } => (&**inner).get_name(),
-            Self::Path(path) => {
-                let mut tokens = TokenStream::new();
-                Print::ref_cast(path).to_tokens(&mut tokens);
-                tokens.to_string()
-            }
-            Self::TypeParam(type_param) => {
-                let mut tokens = TokenStream::new();
-                Print::ref_cast(type_param).to_tokens(&mut tokens);
-                tokens.to_string()
-            }
-
-            _ => panic!("Type::get_name"),
+            Self::Reference { inner, .. } => inner.get_name(),
+            other => other.to_string(),
         }
     }
 
-    pub(crate) fn clone_with_fresh_generics(&self, param_map: &ParamMap) -> Self {
-        use super::TypeNode::*;
+    /// The name of the struct or enum, looking through references
+    pub(crate) fn data_structure_ident(&self) -> Result<Ident, ReflectError> {
         match self {
-            Infer => Infer,
+            Self::DataStructure(data) => Ok(data.name.clone()),
+            Self::Reference { inner, .. } => inner.data_structure_ident(),
+            Self::Path(path) if !path.path.is_empty() => {
+                Ok(path.path.last().unwrap().ident.clone())
+            }
+            _ => Err(ReflectError::UnexpectedType {
+                expected: "a data structure or a path",
+                found: self.to_string(),
+            }),
+        }
+    }
 
-            Tuple(types) => Tuple(
-                types
-                    .iter()
-                    .map(|ty| ty.clone_with_fresh_generics(param_map))
-                    .collect(),
-            ),
+    /// The `T` of a `Result<T, E>` or `Option<T>`, which is the type of the
+    /// value that `?` produces, or `None` for any other type
+    pub(crate) fn try_output(&self) -> Option<Self> {
+        let segment = match self {
+            Self::Path(path) => path.path.last()?,
+            _ => return None,
+        };
+        if !["Result", "Option"].contains(&&*segment.ident.to_string()) {
+            return None;
+        }
+        match segment.generic_args()?.args.first() {
+            Some(GenericArgument::Type(ty)) => Some(ty.clone()),
+            _ => None,
+        }
+    }
 
-            PrimitiveStr => PrimitiveStr,
+    /// Whether this is an `Option`, written as `Option`, `option::Option` or a
+    /// longer path such as `::std::option::Option`
+    pub fn is_option(&self) -> bool {
+        self.is_path_to(&["option", "Option"])
+    }
 
-            Reference {
-                is_mut,
-                lifetime,
-                inner,
-            } => Reference {
-                is_mut: *is_mut,
+    /// Whether this is a `Vec`, written as `Vec`, `vec::Vec` or a longer path
+    /// such as `::std::vec::Vec`
+    pub fn is_vec(&self) -> bool {
+        self.is_path_to(&["vec", "Vec"])
+    }
 
-                lifetime: lifetime.map(|lifetime| lifetime.clone_with_fresh_generics(param_map)),
-                inner: Box::new(inner.clone_with_fresh_generics(param_map)),
-            },
+    /// Whether this is a path made up of a trailing part of `idents`, or a
+    /// longer path ending in all of them
+    fn is_path_to(&self, idents: &[&str]) -> bool {
+        match self {
+            Self::Path(path) => (1..=idents.len()).any(|start| {
+                path.ends_with(&idents[idents.len() - start..])
+                    && (start == idents.len() || path.path.len() == start)
+            }),
+            _ => false,
+        }
+    }
 
-            Dereference(dereference) => {
-                Dereference(Box::new(dereference.clone_with_fresh_generics(param_map)))
+    /// The `T` of a `Vec<T>`, or `None` if this is not a `Vec`
+    pub(crate) fn vec_element(&self) -> Option<Self> {
+        match self {
+            Self::Path(path) if self.is_vec() => {
+                path.last_segment()?.generic_args()?.types().next().cloned()
             }
+            _ => None,
+        }
+    }
 
-            TraitObject(bounds) => TraitObject(
-                bounds
-                    .iter()
-                    .map(|bound| bound.clone_with_fresh_generics(param_map))
-                    .collect(),
-            ),
+    /// The `Output` type of a future, found in an `Output = T` binding such as
+    /// the one in `Pin<Box<dyn Future<Output = T>>>`, or `None` if there is no
+    /// such binding
+    pub(crate) fn future_output(&self) -> Option<Self> {
+        fn find_in_path(path: &Path) -> Option<TypeNode> {
+            path.path
+                .iter()
+                .rev()
+                .find_map(|segment| match &segment.args {
+                    PathArguments::AngleBracketed(args) => {
+                        args.args.args.iter().find_map(|arg| match arg {
+                            GenericArgument::Binding(binding)
+                                if binding.ident == Ident::new("Output") =>
+                            {
+                                Some(binding.ty.clone())
+                            }
+                            GenericArgument::Type(ty) => find(ty),
+                            _ => None,
+                        })
+                    }
+                    _ => None,
+                })
+        }
 
-            DataStructure { .. } => {
-                unimplemented!("Type::clone_with_fresh_generics: DataStructure")
+        fn find(ty: &TypeNode) -> Option<TypeNode> {
+            match ty {
+                TypeNode::Path(path) => find_in_path(path),
+                TypeNode::Reference { inner, .. } => find(inner),
+                TypeNode::TraitObject(bounds) | TypeNode::ImplTrait(bounds) => {
+                    bounds.iter().find_map(|bound| match bound {
+                        TypeParamBound::Trait(bound) => find_in_path(&bound.path),
+                        TypeParamBound::Lifetime(_) => None,
+                    })
+                }
+                _ => None,
             }
+        }
 
-            Path(path) => Path(path.clone_with_fresh_generics(param_map)),
+        find(self)
+    }
 
-            TypeParam(type_param) => TypeParam(
-                param_map
-                    .get(&GenericParam::Type(*type_param))
-                    .and_then(|param| param.type_param())
-                    .unwrap(),
-            ),
-        }
+    pub(crate) fn clone_with_fresh_generics(&self, param_map: &ParamMap) -> Self {
+        FreshGenerics(param_map).fold_type(self)
     }
 }
@@ -1,14 +1,452 @@
 use crate::{
-    ty::DataStructure, Accessor, Data, GlobalBorrow, GlobalPush, Struct, TupleStruct, TypeNode,
-    ValueNode, ValueRef, VALUES,
+    ty::DataStructure, Accessor, Data, Function, FunctionBuilder, GlobalPush, Ident, ImplBuilder,
+    Module, Path, ReflectError, Signature, Struct, SynParamMap, TupleStruct, TypeNode, ValueNode,
+    ValueRef, VALUES,
 };
+use std::rc::Rc;
 
 #[derive(Debug, Clone, Copy)]
 pub struct Value {
     pub(crate) index: ValueRef,
 }
 
+/// The fields of an enum variant being constructed
+#[derive(Debug, Clone)]
+pub enum VariantPayload {
+    /// `Variant`
+    Unit,
+    /// `Variant(value, ...)`
+    Tuple(Vec<Value>),
+    /// `Variant { field: value, ... }`
+    Struct(Vec<(Ident, Value)>),
+}
+
+impl VariantPayload {
+    pub(crate) fn values(&self) -> Vec<ValueRef> {
+        match self {
+            Self::Unit => Vec::new(),
+            Self::Tuple(values) => values.iter().map(|value| value.index).collect(),
+            Self::Struct(fields) => fields.iter().map(|(_, value)| value.index).collect(),
+        }
+    }
+}
+
 impl Value {
+    /// The unit value `()`, the value counterpart of `TypeNode::new_unit`
+    pub fn unit() -> Self {
+        Self::new_tuple(&[])
+    }
+
+    /// A `&'static str` that is emitted as a string literal
+    pub fn new_str_literal(s: &str) -> Self {
+        let node = ValueNode::StrLit(s.to_owned());
+        Self {
+            index: VALUES.index_push(node),
+        }
+    }
+
+    /// A named item of type `ty`, emitted as its path. This can refer to a
+    /// `static`, a `const`, an associated constant or a function item, which
+    /// have no more specific constructor.
+    pub fn new_path_value(path: Path, ty: TypeNode) -> Self {
+        let node = ValueNode::PathValue { path, ty };
+        Self {
+            index: VALUES.index_push(node),
+        }
+    }
+
+    /// The value of the const param named `name` in `param_map`, such as the
+    /// `N` of `struct Buf<const N: usize>`, whose type is the type of the
+    /// param
+    pub fn const_param(name: &str, param_map: &SynParamMap) -> Result<Self, ReflectError> {
+        let node = ValueNode::ConstValue(param_map.get_const_param(name)?);
+        Ok(Self {
+            index: VALUES.index_push(node),
+        })
+    }
+
+    /// A `::std::format!` invocation with `template` as its format string,
+    /// producing a `::std::string::String`
+    pub fn format_string(template: &str, args: &[Self]) -> Self {
+        let std = Module::std_module();
+        let string = TypeNode::Path(std.get_module("string").get_simple_path("String"));
+        let mut values = vec![Self::new_str_literal(template)];
+        values.extend_from_slice(args);
+        std.invoke_macro_typed("format", &values, string)
+    }
+
+    /// A `::core::panic!` invocation with `message` as its format string
+    pub fn panic_with(message: &str) -> Self {
+        Module::core_module().invoke_macro_typed(
+            "panic",
+            &[Self::new_str_literal(message)],
+            TypeNode::new_never(),
+        )
+    }
+
+    /// A `::core::unreachable!()` invocation
+    pub fn unreachable_value() -> Self {
+        Module::core_module().invoke_macro_typed("unreachable", &[], TypeNode::new_never())
+    }
+
+    /// The statement `let name: ty = init;` together with a value referring
+    /// to the binding, which can be used any number of times without
+    /// evaluating `init` again. The statement is always emitted, before any
+    /// use of the binding.
+    ///
+    /// # Panics
+    ///
+    /// If `name` is a keyword without the `r#` prefix
+    pub fn let_binding(name: &str, ty: TypeNode, init: Self) -> (Self, Self) {
+        Self::new_let(name, false, ty, init)
+    }
+
+    /// Like `let_binding`, but emits `let mut name: ty = init;`, so that the
+    /// binding can be assigned to and borrowed mutably
+    pub fn mut_let_binding(name: &str, ty: TypeNode, init: Self) -> (Self, Self) {
+        Self::new_let(name, true, ty, init)
+    }
+
+    fn new_let(name: &str, is_mut: bool, ty: TypeNode, init: Self) -> (Self, Self) {
+        let name = Ident::from_str(name);
+        let stmt = ValueNode::Let {
+            name: name.clone(),
+            is_mut,
+            ty: ty.clone(),
+            init: init.index,
+        };
+        let stmt = Self {
+            index: VALUES.index_push(stmt),
+        };
+        let local = ValueNode::Local { name, ty };
+        let local = Self {
+            index: VALUES.index_push(local),
+        };
+        (stmt, local)
+    }
+
+    /// The statement `self = value;`, where `self` is a place such as a
+    /// binding from `mut_let_binding`
+    pub fn assign(&self, value: Self) -> Self {
+        let node = ValueNode::Assign {
+            place: self.index,
+            value: value.index,
+        };
+        Self {
+            index: VALUES.index_push(node),
+        }
+    }
+
+    /// Calls `Option::and_then` on this value with a closure whose body is
+    /// built by `f` from the value inside the option. `f` returns the
+    /// `Option` produced by the closure.
+    /// Gives `ReflectError::UnexpectedType` if the type of this value is not
+    /// an `Option`.
+    pub fn and_then<F>(&self, f: F) -> Result<Self, ReflectError>
+    where
+        F: Fn(Self) -> Self,
+    {
+        let (option, inner) = self.option_type()?;
+        let (closure, ret) = Self::closure(&[inner], |params| f(params[0]));
+        Ok(Self::call_option_method(
+            "and_then",
+            option,
+            &[*self, closure],
+            ret.get_type(),
+        ))
+    }
+
+    /// Calls `Option::map` on this value with a closure whose body is built
+    /// by `f` from the value inside the option
+    /// Gives `ReflectError::UnexpectedType` if the type of this value is not
+    /// an `Option`.
+    pub fn map_option<F>(&self, f: F) -> Result<Self, ReflectError>
+    where
+        F: Fn(Self) -> Self,
+    {
+        let (option, inner) = self.option_type()?;
+        let (closure, ret) = Self::closure(&[inner], |params| f(params[0]));
+        let output = Module::std_module()
+            .get_module("option")
+            .get_simple_path("Option")
+            .append_type_arg(ret.get_type());
+        Ok(Self::call_option_method(
+            "map",
+            option,
+            &[*self, closure],
+            TypeNode::Path(output),
+        ))
+    }
+
+    /// Calls `Option::unwrap_or`, producing the value inside the option or
+    /// `default`
+    /// Gives `ReflectError::UnexpectedType` if the type of this value is not
+    /// an `Option`.
+    pub fn unwrap_or(&self, default: Self) -> Result<Self, ReflectError> {
+        let (option, inner) = self.option_type()?;
+        Ok(Self::call_option_method(
+            "unwrap_or",
+            option,
+            &[*self, default],
+            inner,
+        ))
+    }
+
+    /// Calls `Option::unwrap_or_default`, producing the value inside the
+    /// option or the default value of its type
+    /// Gives `ReflectError::UnexpectedType` if the type of this value is not
+    /// an `Option`.
+    pub fn unwrap_or_default(&self) -> Result<Self, ReflectError> {
+        let (option, inner) = self.option_type()?;
+        Ok(Self::call_option_method(
+            "unwrap_or_default",
+            option,
+            &[*self],
+            inner,
+        ))
+    }
+
+    /// Calls `Iterator::collect::<Vec<elem_type>>` on this iterator,
+    /// producing a `Vec<elem_type>`
+    pub fn collect_to_vec(&self, elem_type: TypeNode) -> Self {
+        let std = Module::std_module();
+        let vec = std
+            .get_module("vec")
+            .get_simple_path("Vec")
+            .append_type_arg(elem_type);
+        let vec = TypeNode::Path(vec);
+        let collect = std
+            .get_module("iter")
+            .get_simple_path("Iterator")
+            .get_simple_path("collect")
+            .append_type_arg(vec.clone());
+        let sig = Signature::new().input(self.index.get_type()).output(vec);
+        Self::call(Function::new_extern(collect, sig), &[*self])
+    }
+
+    /// Calls `<ty as ::core::default::Default>::default()`, producing a value
+    /// of type `ty`
+    pub fn default_of(ty: TypeNode) -> Self {
+        let default_trait = Module::core_module()
+            .get_module("default")
+            .get_simple_path("Default");
+        let path = Path::for_trait_method(default_trait, Ident::new("default"), ty.clone());
+        Self::call(Function::new_extern(path, Signature::new().output(ty)), &[])
+    }
+
+    /// Calls `::core::clone::Clone::clone` on this value, producing an owned
+    /// copy of it. A reference such as a field of `&self` is cloned into the
+    /// type it refers to; any other value is passed by reference and keeps
+    /// its type. No `Clone` bound is inferred for the cloned type.
+    pub fn clone_generated(&self) -> Self {
+        let (receiver, output) = self.by_reference();
+        let clone_trait = Module::core_module()
+            .get_module("clone")
+            .get_simple_path("Clone");
+        // The lifetime of the receiver is left to be elided, since it is not
+        // a param of the signature
+        let clone = FunctionBuilder::new()
+            .name(Ident::new("clone"))
+            .input(output.new_reference())
+            .output(output)
+            .build();
+        let (_, methods) = ImplBuilder::for_path(clone_trait).add_method(clone).build();
+        Self::call(methods[0].clone(), &[receiver])
+    }
+
+    /// Calls `::core::hash::Hash::hash` on this value with `hasher` as the
+    /// state, such as the `&mut H` argument of a `Hash` impl. A value that is
+    /// not already a reference is passed by reference.
+    pub fn hash_into(&self, hasher: Self) -> Self {
+        let (receiver, hashed) = self.by_reference();
+        let hash_trait = Module::core_module()
+            .get_module("hash")
+            .get_simple_path("Hash");
+        let hash = FunctionBuilder::new()
+            .name(Ident::new("hash"))
+            .input(hashed.new_reference())
+            .input(TypeNode::Infer)
+            .build();
+        let (_, methods) = ImplBuilder::for_path(hash_trait).add_method(hash).build();
+        Self::call(methods[0].clone(), &[receiver, hasher])
+    }
+
+    /// Calls `::core::cmp::PartialEq::eq` on this value and `other`,
+    /// producing a `bool`. Values that are not already references are passed
+    /// by reference, so fields of `&self` and `other` compare directly.
+    pub fn partial_eq(&self, other: Self) -> Self {
+        let (lhs, lhs_type) = self.by_reference();
+        let (rhs, rhs_type) = other.by_reference();
+        let partial_eq_trait = Module::core_module()
+            .get_module("cmp")
+            .get_simple_path("PartialEq");
+        let eq = FunctionBuilder::new()
+            .name(Ident::new("eq"))
+            .input(lhs_type.new_reference())
+            .input(rhs_type.new_reference())
+            .output(TypeNode::Path(Path::empty().get_simple_path("bool")))
+            .build();
+        let (_, methods) = ImplBuilder::for_path(partial_eq_trait)
+            .add_method(eq)
+            .build();
+        Self::call(methods[0].clone(), &[lhs, rhs])
+    }
+
+    /// This value if it is a reference, otherwise a reference to it, along
+    /// with the type being referred to
+    fn by_reference(&self) -> (Self, TypeNode) {
+        let ty = self.index.get_type();
+        match &ty {
+            TypeNode::Reference { inner, .. } => (*self, (**inner).clone()),
+            _ => (self.new_reference(), ty),
+        }
+    }
+
+    /// Iterates by reference over this `Vec` or slice, or a reference to
+    /// one, producing a `std::slice::Iter` over its elements. Gives
+    /// `ReflectError::UnexpectedType` for any other type.
+    pub fn iter(&self) -> Result<Self, ReflectError> {
+        let ty = self.index.get_type();
+        let (receiver, collection) = match &ty {
+            TypeNode::Reference { inner, .. } => (*self, (**inner).clone()),
+            _ => (self.new_reference(), ty.clone()),
+        };
+        let elem = match &collection {
+            TypeNode::Slice(elem) => (**elem).clone(),
+            _ => collection
+                .vec_element()
+                .ok_or_else(|| ReflectError::UnexpectedType {
+                    expected: "a Vec or a slice",
+                    found: ty.to_string(),
+                })?,
+        };
+        let std = Module::std_module();
+        let into_iterator = std.get_module("iter").get_simple_path("IntoIterator");
+        let iter = std
+            .get_module("slice")
+            .get_simple_path("Iter")
+            .append_type_arg(elem);
+        // The lifetime of the receiver is left to be elided, since it is not
+        // a param of the signature
+        let into_iter = FunctionBuilder::new()
+            .name(Ident::new("into_iter"))
+            .input(collection.new_reference())
+            .output(TypeNode::Path(iter))
+            .build();
+        let (_, methods) = ImplBuilder::for_path(into_iterator)
+            .add_method(into_iter)
+            .build();
+        Ok(Self::call(methods[0].clone(), &[receiver]))
+    }
+
+    /// Calls `Iterator::fold` on this iterator, starting from `init` and
+    /// combining the accumulator with each element in a closure whose body is
+    /// built by `f`. The result has the type of `init`; the elements are
+    /// given an inferred type.
+    pub fn fold<F>(&self, init: Self, f: F) -> Self
+    where
+        F: Fn(Self, Self) -> Self,
+    {
+        let acc = init.index.get_type();
+        let (closure, _) = Self::closure(&[acc.clone(), TypeNode::Infer], |params| {
+            f(params[0], params[1])
+        });
+        let iterator = Module::std_module()
+            .get_module("iter")
+            .get_simple_path("Iterator");
+        let fold = FunctionBuilder::new()
+            .name(Ident::new("fold"))
+            .input(self.index.get_type())
+            .input(acc.clone())
+            .input(TypeNode::Infer)
+            .output(acc)
+            .build();
+        let (_, methods) = ImplBuilder::for_path(iterator).add_method(fold).build();
+        Self::call(methods[0].clone(), &[*self, init, closure])
+    }
+
+    /// The type of this value and the type inside it, panicking in `method`
+    /// if it is not an `Option`
+    /// The type of this `Option` and the type inside it
+    fn option_type(&self) -> Result<(TypeNode, TypeNode), ReflectError> {
+        let option = self.index.get_type();
+        match option.try_output() {
+            Some(inner) if option.is_option() => Ok((option, inner)),
+            _ => Err(ReflectError::UnexpectedType {
+                expected: "an Option",
+                found: option.to_string(),
+            }),
+        }
+    }
+
+    /// A closure taking params of the types in `param_tys`, with the values
+    /// created by `f` as its body. Returns the closure and the value it
+    /// returns.
+    fn closure<F>(param_tys: &[TypeNode], f: F) -> (Self, ValueRef)
+    where
+        F: FnOnce(&[Self]) -> Self,
+    {
+        let start = VALUES.with_borrow(|values| ValueRef(values.len()));
+        let params: Vec<Self> = param_tys
+            .iter()
+            .map(|ty| {
+                let index = VALUES.with_borrow(|values| ValueRef(values.len()));
+                let param = ValueNode::Local {
+                    name: index.binding(),
+                    ty: ty.clone(),
+                };
+                Self {
+                    index: VALUES.index_push(param),
+                }
+            })
+            .collect();
+        let ret = f(&params);
+        let end = VALUES.with_borrow(|values| ValueRef(values.len()));
+        let closure = ValueNode::Closure {
+            params: params.iter().map(|param| param.index).collect(),
+            body: start..end,
+            ret: ret.index,
+        };
+        let closure = Self {
+            index: VALUES.index_push(closure),
+        };
+        (closure, ret.index)
+    }
+
+    /// A call of the method `name` of `Option`. The first of `args` is the
+    /// option, of type `option`, and the types of the others are inferred.
+    fn call_option_method(name: &str, option: TypeNode, args: &[Self], output: TypeNode) -> Self {
+        let option_type = Module::std_module()
+            .get_module("option")
+            .get_simple_path("Option");
+        let mut method = FunctionBuilder::new().name(Ident::new(name)).input(option);
+        for _ in &args[1..] {
+            method = method.input(TypeNode::Infer);
+        }
+        let method = method.output(output).build();
+        let (_, methods) = ImplBuilder::for_path(option_type)
+            .add_method(method)
+            .build();
+        Self::call(methods[0].clone(), args)
+    }
+
+    /// An expression that is emitted as written
+    pub(crate) fn new_expr(expr: syn::Expr) -> Self {
+        let node = ValueNode::Expr(expr);
+        Self {
+            index: VALUES.index_push(node),
+        }
+    }
+
+    /// A call of `function` with the given arguments. The type of the
+    /// resulting value is the output type of the function's signature.
+    pub fn call(function: Rc<Function>, args: &[Self]) -> Self {
+        function.invoke(args)
+    }
+
+    /// A tuple of the given values. A single value becomes a one element
+    /// tuple `(v,)`, as in `TypeNode::new_tuple`, rather than a parenthesized
+    /// value.
     pub fn new_tuple(values: &[Self]) -> Self {
         let node = ValueNode::Tuple(values.iter().map(|v| v.index).collect());
         Self {
@@ -16,6 +454,46 @@ impl Value {
         }
     }
 
+    /// The tuple `(elem, elem, ...)` with `n` elements, all referring to the
+    /// same value. The value is used `n` times, so its type must be `Copy`
+    /// for the generated code to compile, and no bound is inferred for it.
+    pub fn new_tuple_of_arity(elem: Self, n: usize) -> Self {
+        Self::new_tuple(&vec![elem; n])
+    }
+
+    /// Applies the `?` operator to a value of type `Result<T, E>` or
+    /// `Option<T>`, producing a value of type `T`. Gives
+    /// `ReflectError::UnexpectedType` for any other type.
+    pub fn try_propagate(&self) -> Result<Self, ReflectError> {
+        let ty = self.index.get_type();
+        if ty.try_output().is_none() {
+            return Err(ReflectError::UnexpectedType {
+                expected: "a Result or an Option",
+                found: ty.to_string(),
+            });
+        }
+        let node = ValueNode::Try(self.index);
+        Ok(Self {
+            index: VALUES.index_push(node),
+        })
+    }
+
+    /// Awaits a future, producing a value of its `Output` type. The future
+    /// is either the call of an `async fn` or of a type with an `Output = T`
+    /// binding, and anything else gives `ReflectError::UnexpectedType`.
+    pub fn await_value(&self) -> Result<Self, ReflectError> {
+        if self.index.await_output().is_none() {
+            return Err(ReflectError::UnexpectedType {
+                expected: "a future with a known `Output` type",
+                found: self.index.get_type().to_string(),
+            });
+        }
+        let node = ValueNode::Await(self.index);
+        Ok(Self {
+            index: VALUES.index_push(node),
+        })
+    }
+
     pub fn new_reference(&self) -> Self {
         let node = ValueNode::Reference {
             is_mut: false,
@@ -36,6 +514,43 @@ impl Value {
         }
     }
 
+    /// Builds a struct literal `Name { field: value, ... }`
+    pub fn construct_struct(type_name: Ident, fields: &[(Ident, Self)]) -> Self {
+        let node = ValueNode::StructLiteral {
+            name: type_name,
+            fields: fields
+                .iter()
+                .map(|(ident, value)| (ident.clone(), value.index))
+                .collect(),
+        };
+        Self {
+            index: VALUES.index_push(node),
+        }
+    }
+
+    /// Builds a tuple struct literal `Name(value, ...)`
+    pub fn construct_tuple_struct(name: Ident, fields: &[Self]) -> Self {
+        let node = ValueNode::TupleStructLiteral {
+            name,
+            fields: fields.iter().map(|v| v.index).collect(),
+        };
+        Self {
+            index: VALUES.index_push(node),
+        }
+    }
+
+    /// Builds an enum variant `EnumPath::Variant` with the given payload
+    pub fn construct_variant(enum_path: Path, variant: Ident, payload: VariantPayload) -> Self {
+        let node = ValueNode::VariantConstruct {
+            path: enum_path,
+            variant,
+            payload,
+        };
+        Self {
+            index: VALUES.index_push(node),
+        }
+    }
+
     pub fn dereference(&self) -> Self {
         match self.node() {
             ValueNode::Reference { value, .. } => Self { index: value },
@@ -55,9 +570,24 @@ impl Value {
         }
     }
 
+    /// Returns the name of the struct or enum that this value is an instance
+    /// of, or of the last segment of its path type. Gives
+    /// `ReflectError::UnexpectedType` for any other type, such as a tuple.
+    pub fn type_ident(&self) -> Result<Ident, ReflectError> {
+        match self.node() {
+            ValueNode::DataStructure { name, .. } => Ok(Ident::new(name)),
+            ValueNode::StructLiteral { name, .. } | ValueNode::TupleStructLiteral { name, .. } => {
+                Ok(name)
+            }
+            node => node.get_type().data_structure_ident(),
+        }
+    }
+
+    /// The fields of this struct or enum value. The fields of an enum are
+    /// only bound inside the arms of `Enum::match_variant`.
     pub fn as_data(&self) -> Data<Self> {
         use crate::ValueNode::*;
-        match self.node() {
+        let mut data = match self.node() {
             DataStructure { data, .. } => data.map(|value_ref| Self {
                 index: value_ref.element,
             }),
@@ -69,19 +599,24 @@ impl Value {
             Reference { is_mut, value } if is_mut => {
                 Self { index: value }.as_data().map(|v| v.element.new_reference_mut())
             },
-            // FIXME generate match and propagate the binding
-            Binding { name, ty } => ty.as_data().map(|field| {
-                let node = ValueNode::Destructure {
-                    parent: self.index,
-                    accessor: field.accessor.clone(),
-                    ty: field.element,
-                };
-                Self {
-                    index: VALUES.index_push(node),
-                }
-            }),
+            Binding { .. } | Local { .. } | Invoke(_) | Destructure { .. } => {
+                self.index.get_type().as_data().map(|field| {
+                    let node = ValueNode::Destructure {
+                        parent: self.index,
+                        accessor: field.accessor.clone(),
+                        ty: field.element,
+                    };
+                    Self {
+                        index: VALUES.index_push(node),
+                    }
+                })
+            }
             _ => panic!("Value::data"),
+        };
+        if let Data::Enum(data) = &mut data {
+            data.scrutinee = Some(self.index);
         }
+        data
     }
 
     /// Returns a `Value` from a `Tuple` or `TupleStruct`
@@ -93,6 +628,10 @@ impl Value {
             ValueNode::Binding {
                 ty: TypeNode::Tuple(types),
                 ..
+            }
+            | ValueNode::Local {
+                ty: TypeNode::Tuple(types),
+                ..
             } => {
                 if index >= types.len() {
                     panic!("Value:get_index: Out of bounds")
@@ -123,7 +662,11 @@ impl Value {
             ValueNode::Binding {
                 ty: TypeNode::DataStructure(data),
                 ..
-            } if is_tuple_struct(&*data) => {
+            }
+            | ValueNode::Local {
+                ty: TypeNode::DataStructure(data),
+                ..
+            } if is_tuple_struct(&data) => {
                 if let Data::Struct(Struct::Tuple(TupleStruct { fields, .. })) = data.data {
                     let field = &fields[index];
                     let node = ValueNode::Destructure {
@@ -153,11 +696,7 @@ impl Value {
 }
 
 fn is_tuple_struct(data: &DataStructure) -> bool {
-    if let Data::Struct(Struct::Tuple(_)) = data.data {
-        true
-    } else {
-        false
-    }
+    matches!(data.data, Data::Struct(Struct::Tuple(_)))
 }
 
 impl Value {
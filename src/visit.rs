@@ -0,0 +1,192 @@
+use crate::generics::Expr;
+use crate::{
+    ConstParam, Data, DataStructure, GenericArgument, Ident, Lifetime, Path, PathArguments,
+    TypeNode, TypeParam, TypeParamBound, Variant, STATIC_LIFETIME,
+};
+use std::collections::BTreeSet;
+
+/// Hooks called by [`walk_type_node`] for each node of a type tree.
+///
+/// Every method does nothing by default, so an implementation only needs to
+/// override the nodes it is interested in. The traversal itself is done by
+/// `walk_type_node`, which calls the hook for a node before walking into its
+/// children.
+pub trait TypeVisitor {
+    fn visit_type(&mut self, _node: &TypeNode) {}
+
+    fn visit_type_param(&mut self, _type_param: TypeParam) {}
+
+    fn visit_lifetime(&mut self, _lifetime: Lifetime) {}
+
+    fn visit_const_param(&mut self, _const_param: ConstParam) {}
+
+    fn visit_path(&mut self, _path: &Path) {}
+
+    fn visit_reference(&mut self, _is_mut: bool, _lifetime: Option<Lifetime>, _inner: &TypeNode) {}
+
+    fn visit_tuple(&mut self, _types: &[TypeNode]) {}
+
+    fn visit_trait_object(&mut self, _bounds: &[TypeParamBound]) {}
+
+    fn visit_impl_trait(&mut self, _bounds: &[TypeParamBound]) {}
+
+    fn visit_type_param_bound(&mut self, _bound: &TypeParamBound) {}
+
+    fn visit_data_structure(&mut self, _data: &DataStructure) {}
+}
+
+/// Walks every node of `node` in depth first order, calling the matching
+/// `TypeVisitor` hooks on `v`
+///
+/// A data structure nested inside a data structure of the same name is
+/// visited but not walked into, so that a self-referential type does not
+/// recurse forever.
+pub fn walk_type_node<V: TypeVisitor + ?Sized>(v: &mut V, node: &TypeNode) {
+    walk(v, node, &mut Vec::new());
+}
+
+/// `walking` holds the names of the data structures whose fields are being
+/// walked
+fn walk<V: TypeVisitor + ?Sized>(v: &mut V, node: &TypeNode, walking: &mut Vec<Ident>) {
+    v.visit_type(node);
+    match node {
+        TypeNode::Infer | TypeNode::PrimitiveStr | TypeNode::Never => {}
+        TypeNode::Tuple(types) => {
+            v.visit_tuple(types);
+            for ty in types {
+                walk(v, ty, walking);
+            }
+        }
+        TypeNode::Reference {
+            is_mut,
+            lifetime,
+            inner,
+        } => {
+            v.visit_reference(*is_mut, *lifetime, inner);
+            if let Some(lifetime) = lifetime {
+                v.visit_lifetime(*lifetime);
+            }
+            walk(v, inner, walking);
+        }
+        TypeNode::Dereference(inner) | TypeNode::Slice(inner) => walk(v, inner, walking),
+        TypeNode::TraitObject(bounds) => {
+            v.visit_trait_object(bounds);
+            for bound in bounds {
+                walk_type_param_bound(v, bound, walking);
+            }
+        }
+        TypeNode::DataStructure(data) => {
+            v.visit_data_structure(data);
+            if !walking.contains(&data.name) {
+                walking.push(data.name.clone());
+                walk_data(v, &data.data, walking);
+                walking.pop();
+            }
+        }
+        TypeNode::Path(path) | TypeNode::Associated(path) => walk_path(v, path, walking),
+        TypeNode::TypeParam(type_param) => v.visit_type_param(*type_param),
+        TypeNode::ImplTrait(bounds) => {
+            v.visit_impl_trait(bounds);
+            for bound in bounds {
+                walk_type_param_bound(v, bound, walking);
+            }
+        }
+    }
+}
+
+fn walk_type_param_bound<V: TypeVisitor + ?Sized>(
+    v: &mut V,
+    bound: &TypeParamBound,
+    walking: &mut Vec<Ident>,
+) {
+    v.visit_type_param_bound(bound);
+    match bound {
+        TypeParamBound::Trait(bound) => walk_path(v, &bound.path, walking),
+        TypeParamBound::Lifetime(lifetime) => v.visit_lifetime(*lifetime),
+    }
+}
+
+fn walk_path<V: TypeVisitor + ?Sized>(v: &mut V, path: &Path, walking: &mut Vec<Ident>) {
+    v.visit_path(path);
+    if let Some(qself) = &path.qself {
+        walk(v, qself, walking);
+    }
+    for segment in &path.path {
+        match &segment.args {
+            PathArguments::None => {}
+            PathArguments::AngleBracketed(args) => {
+                for arg in &args.args.args {
+                    match arg {
+                        GenericArgument::Type(ty) => walk(v, ty, walking),
+                        GenericArgument::Lifetime(lifetime) => v.visit_lifetime(*lifetime),
+                        GenericArgument::Binding(binding) => walk(v, &binding.ty, walking),
+                        GenericArgument::Constraint(constraint) => {
+                            for bound in &constraint.bounds {
+                                walk_type_param_bound(v, bound, walking);
+                            }
+                        }
+                        GenericArgument::Const(Expr::Param(const_param)) => {
+                            v.visit_const_param(*const_param)
+                        }
+                        GenericArgument::Const(Expr::Value(_)) => {}
+                    }
+                }
+            }
+            PathArguments::Parenthesized(args) => {
+                for ty in args.inputs.iter().chain(&args.output) {
+                    walk(v, ty, walking);
+                }
+            }
+        }
+    }
+}
+
+fn walk_data<V: TypeVisitor + ?Sized>(v: &mut V, data: &Data<TypeNode>, walking: &mut Vec<Ident>) {
+    let fields = match data {
+        Data::Struct(data) => data.fields().iter().collect(),
+        Data::Enum(data) => data
+            .variants
+            .iter()
+            .flat_map(|variant| match variant {
+                Variant::Unit(_) => &[],
+                Variant::Tuple(variant) => variant.fields(),
+                Variant::Struct(variant) => variant.fields(),
+            })
+            .collect::<Vec<_>>(),
+    };
+    for field in fields {
+        walk(v, &field.element, walking);
+    }
+}
+
+/// Collects the type params, lifetimes and const params that a type refers
+/// to. The params of a data structure count as referred to, as the data
+/// structure is written with them.
+#[derive(Default)]
+pub(crate) struct FreeParams {
+    pub(crate) type_params: BTreeSet<TypeParam>,
+    pub(crate) lifetimes: BTreeSet<Lifetime>,
+    pub(crate) const_params: BTreeSet<ConstParam>,
+}
+
+impl TypeVisitor for FreeParams {
+    fn visit_type_param(&mut self, type_param: TypeParam) {
+        self.type_params.insert(type_param);
+    }
+
+    fn visit_lifetime(&mut self, lifetime: Lifetime) {
+        if lifetime != STATIC_LIFETIME {
+            self.lifetimes.insert(lifetime);
+        }
+    }
+
+    fn visit_const_param(&mut self, const_param: ConstParam) {
+        self.const_params.insert(const_param);
+    }
+
+    fn visit_data_structure(&mut self, data: &DataStructure) {
+        self.type_params.extend(data.generics.type_params());
+        self.lifetimes.extend(data.generics.lifetime_params());
+        self.const_params.extend(data.generics.const_params());
+    }
+}
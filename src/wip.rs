@@ -1,6 +1,6 @@
 use crate::{
-    Function, GlobalBorrow, GlobalPush, Ident, InvokeRef, MacroInvokeRef, Parent, Path,
-    RuntimeFunction, TypeNode, Value, ValueNode, ValueRef, INVOKES, MACROS, VALUES,
+    Function, GlobalPush, Ident, InvokeRef, MacroInvokeRef, Parent, Path, RuntimeFunction,
+    TypeNode, Value, ValueNode, ValueRef, INVOKES, MACROS, VALUES,
 };
 use std::cell::RefCell;
 use std::ops::Range;
@@ -44,6 +44,8 @@ pub(crate) struct Invoke {
 pub(crate) struct MacroInvoke {
     pub(crate) macro_path: Path,
     pub(crate) args: Vec<ValueRef>,
+    /// The type of the expanded macro, as given by the caller
+    pub(crate) output: TypeNode,
 }
 
 /// A range where the end may not yet have been determined
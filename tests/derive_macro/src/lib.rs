@@ -0,0 +1,10 @@
+//! A derive macro declared with `#[reflect::derive_macro]`, which the tests of
+//! this crate apply to real types to check that the expansion compiles.
+
+#[path = "../../debug/mod.rs"]
+mod debug;
+
+#[reflect::derive_macro(MyDebug)]
+fn derive_debug(ex: reflect::Execution) {
+    debug::derive(ex);
+}
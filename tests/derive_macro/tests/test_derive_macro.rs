@@ -0,0 +1,22 @@
+use reflect_derive_macro_test::MyDebug;
+
+#[derive(MyDebug)]
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+#[derive(MyDebug)]
+struct Generic<'a, T: ::std::fmt::Debug> {
+    t: T,
+    name: &'a str,
+}
+
+#[test]
+fn test_derive_macro() {
+    let point = Point { x: 1, y: -2 };
+    assert_eq!(format!("{:?}", point), "Point { x: 1, y: -2 }");
+
+    let generic = Generic { t: 'c', name: "n" };
+    assert_eq!(format!("{:?}", generic), "Generic { t: 'c', name: \"n\" }");
+}
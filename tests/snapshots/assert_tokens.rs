@@ -0,0 +1,5 @@
+impl Unit {
+    fn unit(&self) -> () {
+        let __v0 = self;
+    }
+}
@@ -0,0 +1,45 @@
+// Not every test uses every helper
+#![allow(dead_code)]
+
+use std::any::Any;
+use std::cell::RefCell;
+
+// The functions passed to reflect are `fn` pointers, which can not capture
+// anything, so values they need to share are kept here instead
+thread_local! {
+    static STASH: RefCell<Vec<Box<dyn Any>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Keeps `value` for a later `stashed` or `take_stashed` on this thread
+pub fn stash<T: 'static>(value: T) {
+    STASH.with(|stash| stash.borrow_mut().push(Box::new(value)));
+}
+
+/// A copy of the last stashed `T`
+pub fn stashed<T: Clone + 'static>() -> T {
+    STASH.with(|stash| {
+        stash
+            .borrow()
+            .iter()
+            .rev()
+            .find_map(|value| value.downcast_ref::<T>())
+            .cloned()
+            .expect("nothing of this type is stashed")
+    })
+}
+
+/// Removes every stashed `T`, returning them in the order they were stashed
+pub fn take_stashed<T: 'static>() -> Vec<T> {
+    STASH.with(|stash| {
+        let mut taken = Vec::new();
+        let mut kept = Vec::new();
+        for value in stash.borrow_mut().drain(..) {
+            match value.downcast::<T>() {
+                Ok(value) => taken.push(*value),
+                Err(value) => kept.push(value),
+            }
+        }
+        *stash.borrow_mut() = kept;
+        taken
+    })
+}
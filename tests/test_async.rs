@@ -0,0 +1,52 @@
+use quote::quote;
+use reflect::*;
+
+library! {
+    use remote {
+        type Point;
+
+        trait Fetch {
+            async fn fetch(&self) -> Point;
+            async fn refetch(&self) -> Point;
+        }
+    }
+}
+
+fn derive(ex: Execution) {
+    ex.make_trait_impl(RUNTIME::remote::Fetch, ex.target_type(), |block| {
+        block.make_function(RUNTIME::remote::Fetch::refetch, |make_function| {
+            let receiver = make_function.arg(0);
+            assert!(matches!(
+                receiver.await_value(),
+                Err(ReflectError::UnexpectedType { .. })
+            ));
+            let point = RUNTIME::remote::Fetch::fetch
+                .INVOKE(receiver)
+                .await_value()
+                .unwrap();
+            assert_eq!(point.type_ident().unwrap(), Ident::new("Point"));
+            point
+        });
+    });
+}
+
+#[test]
+fn test_await() {
+    let input = quote! {
+        struct Remote;
+    };
+
+    let expected = quote! {
+        impl ::remote::Fetch for Remote {
+            async fn refetch<'__a1>(&'__a1 self) -> ::remote::Point {
+                let __v0 = self;
+                let __v1 = ::remote::Fetch::fetch(__v0);
+                let __v2 = __v1.await;
+                __v2
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
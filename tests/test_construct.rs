@@ -0,0 +1,970 @@
+use quote::quote;
+use reflect::*;
+use std::rc::Rc;
+
+library! {
+    use construct {
+        type Point;
+        type Shape;
+
+        trait Make {
+            fn make(&self) -> Point;
+        }
+
+        trait Flip {
+            fn flip(&self) -> Point;
+        }
+
+        trait MakeShape {
+            fn shape(&self) -> Shape;
+        }
+
+        trait Remake {
+            fn remake(&self) -> Point;
+        }
+
+        trait Name {
+            fn name(&self) -> &::std::primitive::str;
+        }
+
+        trait Lookup {
+            fn lookup(&self) -> ::std::option::Option<Point>;
+        }
+
+        trait Same {
+            fn same(&self, &Point) -> ::std::primitive::bool;
+        }
+    }
+}
+
+/// Derives `Remake` for `struct Point;`, with `remake` made by `$run`
+macro_rules! remake_point {
+    ($run:expr) => {
+        reflect::derive(
+            quote!(
+                struct Point;
+            ),
+            |ex| {
+                ex.make_trait_impl(RUNTIME::construct::Remake, ex.target_type(), |block| {
+                    block.make_function(RUNTIME::construct::Remake::remake, $run);
+                });
+            },
+        )
+    };
+}
+
+/// The `::construct::Point` type
+fn point() -> TypeNode {
+    RUNTIME::construct::MODULE()
+        .get_path_type("Point", &mut SynParamMap::default())
+        .unwrap()
+}
+
+/// `::construct::Make::make`, which makes a `Point` from a reference
+fn make() -> Rc<Function> {
+    runtime::RuntimeFunction::SELF(RUNTIME::construct::Make::make)
+}
+
+/// A function `name(&Point) -> output`
+fn from_point(name: &str, output: TypeNode) -> Rc<Function> {
+    FunctionBuilder::new()
+        .name(Ident::new(name))
+        .input(point().new_reference())
+        .output(output)
+        .build()
+}
+
+/// `::core::convert::Into::into`, converting the derived `Point` into a
+/// `::construct::Point`
+fn into_point() -> Rc<Function> {
+    let into = Path::path_from_str("::core::convert::Into::into", &mut SynParamMap::default());
+    let sig = Signature::new().input(TypeNode::Infer).output(point());
+    Function::new_extern(into.unwrap(), sig)
+}
+
+/// Derives `Flip` for a `Point` with two `i32` fields, building a `Point` with
+/// the fields swapped
+fn derive(ex: Execution) {
+    ex.make_trait_impl(RUNTIME::construct::Flip, ex.target_type(), |block| {
+        block.make_function(RUNTIME::construct::Flip::flip, |make_function| {
+            let receiver = make_function.arg(0);
+            // The fields are `i32`, so they are copied out of the reference
+            let flipped = match receiver.as_data() {
+                Data::Struct(Struct::Struct(data)) => {
+                    let x = data.field_by_name("x").unwrap().get_value();
+                    let y = data.field_by_name("y").unwrap().get_value();
+                    Value::construct_struct(
+                        Ident::new("Point"),
+                        &[
+                            (Ident::new("x"), y.dereference()),
+                            (Ident::new("y"), x.dereference()),
+                        ],
+                    )
+                }
+                Data::Struct(Struct::Tuple(data)) => {
+                    let fields = data.fields();
+                    Value::construct_tuple_struct(
+                        Ident::new("Point"),
+                        &[
+                            fields[1].get_value().dereference(),
+                            fields[0].get_value().dereference(),
+                        ],
+                    )
+                }
+                _ => unreachable!(),
+            };
+            Value::call(into_point(), &[flipped])
+        });
+    });
+}
+
+#[test]
+fn test_construct_struct() {
+    let input = quote! {
+        struct Point {
+            x: i32,
+            y: i32,
+        }
+    };
+
+    let expected = quote! {
+        impl ::construct::Flip for Point {
+            fn flip<'__a1>(&'__a1 self) -> ::construct::Point {
+                let __v0 = self;
+                let __v1 = &__v0.x;
+                let __v2 = &__v0.y;
+                let __v3 = *__v2;
+                let __v4 = *__v1;
+                let __v5 = Point { x: __v3, y: __v4 };
+                let __v6 = ::core::convert::Into::into(__v5);
+                __v6
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_construct_tuple_struct() {
+    let input = quote! {
+        struct Point(i32, i32);
+    };
+
+    let expected = quote! {
+        impl ::construct::Flip for Point {
+            fn flip<'__a1>(&'__a1 self) -> ::construct::Point {
+                let __v0 = self;
+                let __v1 = &__v0.0;
+                let __v2 = &__v0.1;
+                let __v3 = *__v2;
+                let __v4 = *__v1;
+                let __v5 = Point(__v3, __v4);
+                let __v6 = ::core::convert::Into::into(__v5);
+                __v6
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+fn derive_variant(ex: Execution) {
+    ex.make_trait_impl(RUNTIME::construct::MakeShape, ex.target_type(), |block| {
+        block.make_function(RUNTIME::construct::MakeShape::shape, |make_function| {
+            let receiver = make_function.arg(0);
+            let path =
+                Path::path_from_str("::construct::Shape", &mut SynParamMap::default()).unwrap();
+            let empty =
+                Value::construct_variant(path.clone(), Ident::new("Empty"), VariantPayload::Unit);
+            let square = Value::construct_variant(
+                path.clone(),
+                Ident::new("Square"),
+                VariantPayload::Tuple(vec![receiver]),
+            );
+            Value::construct_variant(
+                path,
+                Ident::new("Pair"),
+                VariantPayload::Struct(vec![
+                    (Ident::new("first"), empty),
+                    (Ident::new("second"), square),
+                ]),
+            )
+        });
+    });
+}
+
+#[test]
+fn test_construct_variant() {
+    let input = quote! {
+        struct Point;
+    };
+
+    let expected = quote! {
+        impl ::construct::MakeShape for Point {
+            fn shape<'__a1>(&'__a1 self) -> ::construct::Shape {
+                let __v0 = self;
+                let __v1 = ::construct::Shape::Empty;
+                let __v2 = ::construct::Shape::Square(__v0);
+                let __v3 = ::construct::Shape::Pair { first: __v1, second: __v2 };
+                __v3
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive_variant);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_call() {
+    let expected = quote! {
+        impl ::construct::Remake for Point {
+            fn remake<'__a1>(&'__a1 self) -> ::construct::Point {
+                let __v0 = self;
+                let __v1 = ::construct::Make::make(__v0);
+                __v1
+            }
+        }
+    };
+
+    let output = remake_point!(derive_call_body);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_str_literal() {
+    let input = quote! {
+        struct Unit;
+    };
+
+    let expected = quote! {
+        impl ::construct::Name for Unit {
+            fn name<'__a1>(&'__a1 self) -> &'__a1 ::std::primitive::str {
+                "a \"quoted\" name"
+            }
+        }
+    };
+
+    let output = reflect::derive(input, |ex| {
+        ex.make_trait_impl(RUNTIME::construct::Name, ex.target_type(), |block| {
+            block.make_function(RUNTIME::construct::Name::name, |_make_function| {
+                Value::new_str_literal("a \"quoted\" name")
+            });
+        });
+    });
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_function_builder() {
+    let expected = quote! {
+        impl ::construct::Remake for Point {
+            fn remake<'__a1>(&'__a1 self) -> ::construct::Point {
+                let __v0 = self;
+                let __v1 = unsafe { make_point(__v0) };
+                __v1
+            }
+        }
+    };
+
+    let output = remake_point!(|make_function| {
+        let point = point();
+        let make_point = FunctionBuilder::new()
+            .name(Ident::new("make_point"))
+            .input(point.new_reference())
+            .output(point)
+            .is_unsafe(true)
+            .build();
+        Value::call(make_point, &[make_function.arg(0)])
+    });
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_raw_function_name() {
+    let expected = quote! {
+        impl ::construct::Remake for Point {
+            fn remake<'__a1>(&'__a1 self) -> ::construct::Point {
+                let __v0 = self;
+                let __v1 = r#type(__v0);
+                __v1
+            }
+        }
+    };
+
+    let output = remake_point!(|make_function| {
+        let point = point();
+        let function = FunctionBuilder::new()
+            .name(Ident::new_raw("type"))
+            .input(point.new_reference())
+            .output(point)
+            .build();
+        Value::call(function, &[make_function.arg(0)])
+    });
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_impl_builder() {
+    let expected = quote! {
+        impl ::construct::Remake for Point {
+            fn remake<'__a1>(&'__a1 self) -> ::construct::Point {
+                let __v0 = self;
+                let _ = ::construct::Point::mirror(__v0);
+                let __v2 = ::construct::Make::make(__v0);
+                __v2
+            }
+        }
+    };
+
+    let output = remake_point!(|make_function| {
+        let module = RUNTIME::construct::MODULE();
+        let point = point();
+        let method = |name| from_point(name, point.clone());
+
+        let (_, inherent) = ImplBuilder::for_type(point.clone())
+            .unwrap()
+            .add_method(method("mirror"))
+            .build();
+        let (_, trait_methods) = ImplBuilder::for_type(point.clone())
+            .unwrap()
+            .with_trait(
+                module
+                    .get_path("Make", &mut SynParamMap::default())
+                    .unwrap(),
+            )
+            .add_method(method("make"))
+            .build();
+
+        let receiver = make_function.arg(0);
+        Value::call(inherent[0].clone(), &[receiver]);
+        Value::call(trait_methods[0].clone(), &[receiver])
+    });
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_let_binding() {
+    let expected = quote! {
+        impl ::construct::Remake for Point {
+            fn remake<'__a1>(&'__a1 self) -> ::construct::Point {
+                let __v0 = self;
+                let __v1 = ::construct::Make::make(__v0);
+                let point: ::construct::Point = __v1;
+                let __v4 = &point;
+                let _ = check(__v4);
+                point
+            }
+        }
+    };
+
+    let output = remake_point!(|make_function| {
+        let point = point();
+        let check = FunctionBuilder::new()
+            .name(Ident::new("check"))
+            .input(point.new_reference())
+            .build();
+        let init = Value::call(make(), &[make_function.arg(0)]);
+        let (_, point) = Value::let_binding("point", point, init);
+        Value::call(check, &[point.new_reference()]);
+        point
+    });
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_mut_let_binding() {
+    let expected = quote! {
+        impl ::construct::Remake for Point {
+            fn remake<'__a1>(&'__a1 self) -> ::construct::Point {
+                let __v0 = self;
+                let __v1 = ::construct::Make::make(__v0);
+                let mut point: ::construct::Point = __v1;
+                let __v4 = &mut point;
+                let _ = update(__v4);
+                let __v6 = ::construct::Make::make(__v0);
+                point = __v6;
+                point
+            }
+        }
+    };
+
+    let output = remake_point!(|make_function| {
+        let point = point();
+        let update = FunctionBuilder::new()
+            .name(Ident::new("update"))
+            .input(point.new_reference_mut())
+            .build();
+
+        let receiver = make_function.arg(0);
+        let init = Value::call(make(), &[receiver]);
+        let (_, point) = Value::mut_let_binding("point", point, init);
+        Value::call(update, &[point.new_reference_mut()]);
+        point.assign(Value::call(make(), &[receiver]));
+        point
+    });
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_and_then() {
+    let input = quote! {
+        struct Point;
+    };
+
+    let expected = quote! {
+        impl ::construct::Lookup for Point {
+            fn lookup<'__a1>(&'__a1 self) -> ::std::option::Option<::construct::Point> {
+                let __v0 = self;
+                let __v1 = first(__v0);
+                let __v6 = ::std::option::Option::and_then(__v1, |__v2| {
+                    let __v3 = &__v2;
+                    let __v4 = next(__v3);
+                    __v4
+                });
+                __v6
+            }
+        }
+    };
+
+    let output = reflect::derive(input, |ex| {
+        ex.make_trait_impl(RUNTIME::construct::Lookup, ex.target_type(), |block| {
+            block.make_function(RUNTIME::construct::Lookup::lookup, |make_function| {
+                let mut param_map = SynParamMap::default();
+                let point = point();
+                let option = Module::root()
+                    .get_module("std")
+                    .get_module("option")
+                    .get_path("Option", &mut param_map)
+                    .unwrap()
+                    .append_type_arg(point.clone());
+                let lookup = |name| from_point(name, TypeNode::Path(option.clone()));
+
+                let first = Value::call(lookup("first"), &[make_function.arg(0)]);
+                let next = lookup("next");
+                first
+                    .and_then(|point| Value::call(next.clone(), &[point.new_reference()]))
+                    .unwrap()
+            });
+        });
+    });
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_map_option() {
+    let input = quote! {
+        struct Point;
+    };
+
+    let expected = quote! {
+        impl ::construct::Lookup for Point {
+            fn lookup<'__a1>(&'__a1 self) -> ::std::option::Option<::construct::Point> {
+                let __v0 = self;
+                let __v1 = first(__v0);
+                let __v6 = ::std::option::Option::map(__v1, |__v2| {
+                    let __v3 = &__v2;
+                    let __v4 = rebuild(__v3);
+                    __v4
+                });
+                __v6
+            }
+        }
+    };
+
+    let output = reflect::derive(input, |ex| {
+        ex.make_trait_impl(RUNTIME::construct::Lookup, ex.target_type(), |block| {
+            block.make_function(RUNTIME::construct::Lookup::lookup, |make_function| {
+                let mut param_map = SynParamMap::default();
+                let point = point();
+                let option = Module::root()
+                    .get_module("std")
+                    .get_module("option")
+                    .get_path("Option", &mut param_map)
+                    .unwrap()
+                    .append_type_arg(point.clone());
+                let first = from_point("first", TypeNode::Path(option));
+                let rebuild = from_point("rebuild", point);
+
+                let first = Value::call(first, &[make_function.arg(0)]);
+                first
+                    .map_option(|point| Value::call(rebuild.clone(), &[point.new_reference()]))
+                    .unwrap()
+            });
+        });
+    });
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_option_methods_not_option() {
+    remake_point!(|make_function| {
+        // `&Point` rather than `Option<Point>`
+        let receiver = make_function.arg(0);
+        let results = [
+            receiver.and_then(|point| point),
+            receiver.map_option(|point| point),
+            receiver.unwrap_or(receiver),
+            receiver.unwrap_or_default(),
+        ];
+        for result in results {
+            match result {
+                Err(ReflectError::UnexpectedType { expected, found }) => {
+                    assert_eq!(expected, "an Option");
+                    assert_eq!(found, "& '__a1 Point");
+                }
+                _ => panic!("expected ReflectError::UnexpectedType"),
+            }
+        }
+        Value::call(make(), &[receiver])
+    });
+}
+
+#[test]
+fn test_unwrap_or() {
+    let expected = quote! {
+        impl ::construct::Remake for Point {
+            fn remake<'__a1>(&'__a1 self) -> ::construct::Point {
+                let __v0 = self;
+                let __v1 = first(__v0);
+                let __v2 = ::construct::Make::make(__v0);
+                let __v3 = ::std::option::Option::unwrap_or(__v1, __v2);
+                let __v4 = first(__v0);
+                let _ = ::std::option::Option::unwrap_or_default(__v4);
+                __v3
+            }
+        }
+    };
+
+    let output = remake_point!(|make_function| {
+        let mut param_map = SynParamMap::default();
+        let point = point();
+        let option = Module::root()
+            .get_module("std")
+            .get_module("option")
+            .get_path("Option", &mut param_map)
+            .unwrap()
+            .append_type_arg(point.clone());
+        let first = from_point("first", TypeNode::Path(option));
+
+        // Each call consumes its option, so each gets its own
+        let receiver = make_function.arg(0);
+        let point = Value::call(first.clone(), &[receiver])
+            .unwrap_or(Value::call(make(), &[receiver]))
+            .unwrap();
+        Value::call(first, &[receiver]).unwrap_or_default().unwrap();
+        point
+    });
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_collect_to_vec() {
+    let expected = quote! {
+        impl ::construct::Remake for Point {
+            fn remake<'__a1>(&'__a1 self) -> ::construct::Point {
+                let __v0 = self;
+                let __v1 = points(__v0);
+                let __v2 = ::std::iter::Iterator::collect::<::std::vec::Vec<::construct::Point> >(__v1);
+                let __v3 = &__v2;
+                let _ = check(__v3);
+                let __v5 = ::construct::Make::make(__v0);
+                __v5
+            }
+        }
+    };
+
+    let output = remake_point!(|make_function| {
+        let mut param_map = SynParamMap::default();
+        let point = point();
+        let into_iter = Module::root()
+            .get_module("std")
+            .get_module("vec")
+            .get_path("IntoIter", &mut param_map)
+            .unwrap()
+            .append_type_arg(point.clone());
+        let points = from_point("points", TypeNode::Path(into_iter));
+
+        let receiver = make_function.arg(0);
+        let vec = Value::call(points, &[receiver]).collect_to_vec(point);
+        let check = FunctionBuilder::new()
+            .name(Ident::new("check"))
+            .input(TypeNode::Infer)
+            .build();
+        Value::call(check, &[vec.new_reference()]);
+        Value::call(make(), &[receiver])
+    });
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_iter() {
+    let expected = quote! {
+        impl ::construct::Remake for Point {
+            fn remake<'__a1>(&'__a1 self) -> ::construct::Point {
+                let __v0 = self;
+                let __v1 = points(__v0);
+                let __v2 = &__v1;
+                let __v3 = ::std::iter::IntoIterator::into_iter(__v2);
+                let _ = ::std::iter::Iterator::collect::<::std::vec::Vec<&::construct::Point> >(__v3);
+                let __v5 = ::construct::Make::make(__v0);
+                __v5
+            }
+        }
+    };
+
+    let output = remake_point!(|make_function| {
+        let mut param_map = SynParamMap::default();
+        let point = point();
+        let vec = Module::root()
+            .get_module("std")
+            .get_module("vec")
+            .get_path("Vec", &mut param_map)
+            .unwrap()
+            .append_type_arg(point.clone());
+        let points = from_point("points", TypeNode::Path(vec));
+
+        let receiver = make_function.arg(0);
+        let points = Value::call(points, &[receiver]);
+        points.iter().unwrap().collect_to_vec(point.new_reference());
+        Value::call(make(), &[receiver])
+    });
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_iter_slice() {
+    let expected = quote! {
+        impl ::construct::Remake for Point {
+            fn remake<'__a1>(&'__a1 self) -> ::construct::Point {
+                let __v0 = self;
+                let __v1 = points(__v0);
+                let __v2 = ::std::iter::IntoIterator::into_iter(__v1);
+                let _ = ::std::iter::Iterator::collect::<::std::vec::Vec<&::construct::Point> >(__v2);
+                let __v4 = ::construct::Make::make(__v0);
+                __v4
+            }
+        }
+    };
+
+    let output = remake_point!(|make_function| {
+        let point = point();
+        let points = from_point(
+            "points",
+            TypeNode::Slice(Box::new(point.clone())).new_reference(),
+        );
+
+        let receiver = make_function.arg(0);
+        let points = Value::call(points, &[receiver]);
+        points.iter().unwrap().collect_to_vec(point.new_reference());
+        Value::call(make(), &[receiver])
+    });
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_iter_not_vec() {
+    remake_point!(|make_function| {
+        let receiver = make_function.arg(0);
+        let err = receiver.iter().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "expected a Vec or a slice, found `& '__a1 Point`",
+        );
+        Value::call(make(), &[receiver])
+    });
+}
+
+#[test]
+fn test_fold() {
+    let expected = quote! {
+        impl ::construct::Remake for Point {
+            fn remake<'__a1>(&'__a1 self) -> ::construct::Point {
+                let __v0 = self;
+                let __v1 = points(__v0);
+                let __v2 = &__v1;
+                let __v3 = ::std::iter::IntoIterator::into_iter(__v2);
+                let __v4 = ::construct::Make::make(__v0);
+                let __v9 = ::std::iter::Iterator::fold(__v3, __v4, |__v5, __v6| {
+                    let __v7 = combine(__v5, __v6);
+                    __v7
+                });
+                __v9
+            }
+        }
+    };
+
+    let output = remake_point!(|make_function| {
+        let mut param_map = SynParamMap::default();
+        let point = point();
+        let vec = Module::root()
+            .get_module("std")
+            .get_module("vec")
+            .get_path("Vec", &mut param_map)
+            .unwrap()
+            .append_type_arg(point.clone());
+        let points = from_point("points", TypeNode::Path(vec));
+        let combine = FunctionBuilder::new()
+            .name(Ident::new("combine"))
+            .input(point.clone())
+            .input(point.new_reference())
+            .output(point)
+            .build();
+
+        let receiver = make_function.arg(0);
+        let points = Value::call(points, &[receiver]).iter().unwrap();
+        let init = Value::call(make(), &[receiver]);
+        points.fold(init, |acc, point| {
+            Value::call(combine.clone(), &[acc, point])
+        })
+    });
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_path_value() {
+    let expected = quote! {
+        impl ::construct::Remake for Point {
+            fn remake<'__a1>(&'__a1 self) -> ::construct::Point {
+                let __v2 = &::construct::ORIGINS;
+                let _ = check(__v2);
+                let _ = check(::std::primitive::u8::MAX);
+                ::construct::ORIGIN
+            }
+        }
+    };
+
+    let output = remake_point!(|_make_function| {
+        let module = RUNTIME::construct::MODULE();
+        let point = point();
+        let origin = module.get_path_value("ORIGIN", point.clone()).unwrap();
+        let origins = module
+            .get_path_value("ORIGINS", TypeNode::new_tuple_of_arity(&point, 2))
+            .unwrap();
+        let check = FunctionBuilder::new()
+            .name(Ident::new("check"))
+            .input(TypeNode::Infer)
+            .build();
+        Value::call(check.clone(), &[origins.new_reference()]);
+        let mut param_map = SynParamMap::default();
+        let max = Path::path_from_str("::std::primitive::u8::MAX", &mut param_map).unwrap();
+        let u8_type =
+            TypeNode::Path(Path::path_from_str("::std::primitive::u8", &mut param_map).unwrap());
+        Value::call(check, &[Value::new_path_value(max, u8_type)]);
+        origin
+    });
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_extern_function() {
+    let expected = quote! {
+        impl ::construct::Remake for Point {
+            fn remake<'__a1>(&'__a1 self) -> ::construct::Point {
+                let __v0 = self;
+                let __v1 = ::construct::Make::make(__v0);
+                let __v2 = ::std::convert::identity(__v1);
+                __v2
+            }
+        }
+    };
+
+    let output = remake_point!(|make_function| {
+        let mut param_map = SynParamMap::default();
+        let point = point();
+        let path = Path::path_from_str("::std::convert::identity", &mut param_map).unwrap();
+        let mut sig = Signature::new();
+        sig.add_input(point.clone());
+        sig.set_output(point);
+        let identity = Function::new_extern(path, sig);
+        let point = Value::call(make(), &[make_function.arg(0)]);
+        Value::call(identity, &[point])
+    });
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_trait_method_path() {
+    let expected = quote! {
+        impl ::construct::Remake for Point {
+            fn remake<'__a1>(&'__a1 self) -> ::construct::Point {
+                let __v0 = self;
+                let __v1 = <::construct::Point as ::construct::Make>::make(__v0);
+                __v1
+            }
+        }
+    };
+
+    let output = remake_point!(|make_function| {
+        let mut param_map = SynParamMap::default();
+        let point = point();
+        let make = RUNTIME::construct::MODULE()
+            .get_path("Make", &mut param_map)
+            .unwrap();
+        let path = Path::for_trait_method(make, Ident::new("make"), point.clone());
+        assert!(matches!(path.to_syn(), Err(ReflectError::Unsupported(_))));
+        assert!(matches!(
+            path.to_syn_with_names(&param_map),
+            Err(ReflectError::Unsupported(_))
+        ));
+        let sig = Signature::new().with_self_ref().output(point);
+        let make = Function::new_extern(path, sig);
+        Value::call(make, &[make_function.arg(0)])
+    });
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_signature_builder() {
+    let expected = quote! {
+        impl ::construct::Remake for Point {
+            fn remake<'__a1>(&'__a1 self) -> ::construct::Point {
+                let __v0 = self;
+                let __v1 = ::construct::Make::make(__v0);
+                __v1
+            }
+        }
+    };
+
+    let output = remake_point!(|make_function| {
+        let mut param_map = SynParamMap::default();
+        let point = point();
+        let path = Path::path_from_str("::construct::Make::make", &mut param_map).unwrap();
+        let sig = Signature::new()
+            .with_generics(Generics::default())
+            .with_self_ref()
+            .output(point);
+        let make = Function::new_extern(path, sig);
+        Value::call(make, &[make_function.arg(0)])
+    });
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_clone_generated() {
+    let expected = quote! {
+        impl ::construct::Remake for Point {
+            fn remake<'__a1>(&'__a1 self) -> ::construct::Point {
+                let __v0 = self;
+                let __v1 = ::construct::Make::make(__v0);
+                let __v2 = &__v1;
+                let __v3 = ::core::clone::Clone::clone(__v2);
+                let _ = check(__v3);
+                let __v5 = ::core::clone::Clone::clone(__v0);
+                __v5
+            }
+        }
+    };
+
+    let output = remake_point!(|make_function| {
+        let receiver = make_function.arg(0);
+        let point = Value::call(make(), &[receiver]);
+        let check = FunctionBuilder::new()
+            .name(Ident::new("check"))
+            .input(TypeNode::Infer)
+            .build();
+        Value::call(check, &[point.clone_generated()]);
+        receiver.clone_generated()
+    });
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_partial_eq() {
+    let input = quote! {
+        struct Point {
+            x: i32,
+        }
+    };
+
+    let expected = quote! {
+        impl ::construct::Same for Point {
+            fn same<'__a1, '__a2>(&'__a1 self, __arg0: &'__a2 ::construct::Point) -> ::std::primitive::bool {
+                let __v0 = self;
+                let __v1 = &__v0.x;
+                let __v2 = ::construct::Make::make(__v0);
+                let __v3 = &__v2;
+                let __v4 = ::core::cmp::PartialEq::eq(__v1, __v3);
+                __v4
+            }
+        }
+    };
+
+    let output = reflect::derive(input, |ex| {
+        ex.make_trait_impl(RUNTIME::construct::Same, ex.target_type(), |block| {
+            block.make_function(RUNTIME::construct::Same::same, |make_function| {
+                let receiver = make_function.arg(0);
+                let x = match receiver.as_data() {
+                    Data::Struct(Struct::Struct(data)) => {
+                        data.field_by_name("x").unwrap().get_value()
+                    }
+                    _ => unreachable!(),
+                };
+                x.partial_eq(Value::call(make(), &[receiver]))
+            });
+        });
+    });
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_default_of() {
+    let expected = quote! {
+        impl ::construct::Remake for Point {
+            fn remake<'__a1>(&'__a1 self) -> ::construct::Point {
+                let __v0 = <::construct::Point as ::core::default::Default>::default();
+                __v0
+            }
+        }
+    };
+
+    let output = remake_point!(|_make_function| {
+        let point = point();
+        Value::default_of(point)
+    });
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_allow_lints() {
+    let input = quote! {
+        struct Point;
+    };
+
+    let expected = quote! {
+        impl ::construct::Remake for Point {
+            #[allow(unused_variables)]
+            #[allow(clippy::needless_return)]
+            fn remake<'__a1>(&'__a1 self) -> ::construct::Point {
+                let __v0 = self;
+                let __v1 = ::construct::Make::make(__v0);
+                __v1
+            }
+        }
+    };
+
+    let output = reflect::derive(input, |ex| {
+        ex.make_trait_impl(RUNTIME::construct::Remake, ex.target_type(), |block| {
+            let remake = runtime::RuntimeFunction::SELF(RUNTIME::construct::Remake::remake);
+            let mut remake = (*remake).clone();
+            remake
+                .allow("unused_variables")
+                .unwrap()
+                .allow("clippy::needless_return")
+                .unwrap();
+            assert!(matches!(
+                remake.allow("unused variables"),
+                Err(ReflectError::Parse(_))
+            ));
+            block.make_function(std::rc::Rc::new(remake), derive_call_body);
+        });
+    });
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+fn derive_call_body(make_function: MakeFunction) -> Value {
+    Value::call(make(), &[make_function.arg(0)])
+}
@@ -77,13 +77,11 @@ fn display_fmt(f: MakeFunction) -> Value {
 fn extract_doc_comment(attrs: &[Attribute]) -> Option<String> {
     for attr in attrs {
         if attr.path.is_ident("doc") {
-            if let Ok(meta) = attr.parse_meta() {
-                if let Meta::NameValue(MetaNameValue {
-                    lit: Lit::Str(lit), ..
-                }) = meta
-                {
-                    return Some(lit.value().trim().to_owned());
-                }
+            if let Ok(Meta::NameValue(MetaNameValue {
+                lit: Lit::Str(lit), ..
+            })) = attr.parse_meta()
+            {
+                return Some(lit.value().trim().to_owned());
             }
         }
     }
@@ -122,3 +120,112 @@ fn test_displaydoc() {
     let actual = reflect::derive(input, derive_displaydoc);
     assert_eq!(actual.to_string(), expected.to_string());
 }
+
+#[test]
+fn test_typed_macro_invocation() {
+    let input = quote! {
+        struct Typed;
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::std::fmt::Display, ex.target_type(), |block| {
+            block.make_function(RUNTIME::std::fmt::Display::fmt, |f| {
+                let formatter = f.arg(1);
+                let result = RUNTIME::std::write.INVOKE_TYPED(
+                    &[formatter, f.string("typed")],
+                    RUNTIME::std::fmt::MODULE()
+                        .get_path_type("Result", &mut SynParamMap::default())
+                        .unwrap(),
+                );
+                assert_eq!(result.type_ident().unwrap(), Ident::new("Result"));
+                result
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl ::std::fmt::Display for Typed {
+            fn fmt<'__a1, '__a2>(
+                &'__a1 self,
+                __arg0: &'__a2 mut ::std::fmt::Formatter
+            ) -> ::std::fmt::Result {
+                let __v0 = __arg0;
+                let __v2 = ::std::write!(__v0, "typed");
+                __v2
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_format_string() {
+    let input = quote! {
+        struct Formatted;
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::std::fmt::Display, ex.target_type(), |block| {
+            block.make_function(RUNTIME::std::fmt::Display::fmt, |f| {
+                let formatter = f.arg(1);
+                let formatted = Value::format_string("<{}>", &[f.string("formatted")]);
+                assert_eq!(formatted.type_ident().unwrap(), Ident::new("String"));
+                RUNTIME::std::write.INVOKE(&[formatter, f.string("{}"), formatted])
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl ::std::fmt::Display for Formatted {
+            fn fmt<'__a1, '__a2>(
+                &'__a1 self,
+                __arg0: &'__a2 mut ::std::fmt::Formatter
+            ) -> ::std::fmt::Result {
+                let __v0 = __arg0;
+                let __v3 = ::std::format!("<{}>", "formatted");
+                let __v5 = ::std::write!(__v0, "{}", __v3);
+                __v5
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_invoke_format_macro() {
+    let input = quote! {
+        struct Printed;
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::std::fmt::Display, ex.target_type(), |block| {
+            block.make_function(RUNTIME::std::fmt::Display::fmt, |f| {
+                let formatter = f.arg(1);
+                let std = Module::root().get_module("std");
+                std.invoke_format_macro("println", "{}: {}", &[f.string("a"), f.string("b")]);
+                RUNTIME::std::write.INVOKE(&[formatter, f.string("printed")])
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl ::std::fmt::Display for Printed {
+            fn fmt<'__a1, '__a2>(
+                &'__a1 self,
+                __arg0: &'__a2 mut ::std::fmt::Formatter
+            ) -> ::std::fmt::Result {
+                let __v0 = __arg0;
+                let _ = ::std::println!("{}: {}", "a", "b");
+                let __v6 = ::std::write!(__v0, "printed");
+                __v6
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
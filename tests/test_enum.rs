@@ -0,0 +1,444 @@
+use quote::quote;
+use reflect::*;
+
+mod stash;
+use stash::{stash, take_stashed};
+
+library! {
+    use discriminant {
+        trait Discriminant {
+            fn last(&self) -> ::std::primitive::usize;
+        }
+
+        trait Partial {
+            fn partial(&self) -> ::std::primitive::usize;
+            fn impossible(&self) -> ::std::primitive::usize;
+        }
+
+        trait Weigh {
+            fn weigh(&self) -> ::std::primitive::usize;
+        }
+    }
+}
+
+fn derive(ex: Execution) {
+    ex.make_trait_impl(
+        RUNTIME::discriminant::Discriminant,
+        ex.target_type(),
+        |block| {
+            block.make_function(RUNTIME::discriminant::Discriminant::last, |make_function| {
+                let receiver = make_function.arg(0);
+                match receiver.as_data() {
+                    Data::Enum(data) => {
+                        let discriminants: Vec<_> = data
+                            .discriminants()
+                            .unwrap()
+                            .into_iter()
+                            .map(|(variant, discriminant)| {
+                                (variant.name().to_string(), discriminant)
+                            })
+                            .collect();
+                        assert_eq!(
+                            discriminants,
+                            [
+                                ("A".to_owned(), 0),
+                                ("B".to_owned(), 5),
+                                ("C".to_owned(), 6),
+                            ]
+                        );
+                        let last = data.variants().last().unwrap();
+                        data.discriminant_of(last).unwrap()
+                    }
+                    _ => unreachable!(),
+                }
+            });
+        },
+    );
+}
+
+#[test]
+fn test_discriminants() {
+    let input = quote! {
+        enum Discriminants {
+            A,
+            B = 5,
+            C,
+        }
+    };
+
+    let expected = quote! {
+        impl ::discriminant::Discriminant for Discriminants {
+            fn last<'__a1>(&'__a1 self) -> ::std::primitive::usize {
+                6
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_negative_and_constant_discriminants() {
+    type Discriminants = Result<Vec<(String, i128)>, ReflectError>;
+
+    fn discriminants(input: proc_macro2::TokenStream) -> Discriminants {
+        fn derive(ex: Execution) {
+            let discriminants = match ex.target_type() {
+                TypeNode::DataStructure(data) => match data.data {
+                    Data::Enum(data) => data.discriminants().map(|discriminants| {
+                        discriminants
+                            .into_iter()
+                            .map(|(variant, discriminant)| {
+                                (variant.name().to_string(), discriminant)
+                            })
+                            .collect()
+                    }),
+                    Data::Struct(_) => panic!("expected an enum"),
+                },
+                other => panic!("expected a data structure, but {}", other),
+            };
+            stash::<Discriminants>(discriminants);
+        }
+
+        reflect::derive(input, derive);
+        take_stashed::<Discriminants>().pop().unwrap()
+    }
+
+    let negative = discriminants(quote! {
+        enum Negative {
+            A = -2,
+            B,
+            C = (7),
+        }
+    });
+    assert_eq!(
+        negative.unwrap(),
+        [
+            ("A".to_owned(), -2),
+            ("B".to_owned(), -1),
+            ("C".to_owned(), 7),
+        ]
+    );
+
+    let constant = discriminants(quote! {
+        enum Constant {
+            A = 1 << 2,
+            B = CONST,
+        }
+    });
+    assert!(matches!(constant, Err(ReflectError::Unsupported(_))));
+
+    let overflow = discriminants(quote! {
+        enum Overflow {
+            A = 170141183460469231731687303715884105727,
+            B,
+        }
+    });
+    assert!(matches!(overflow, Err(ReflectError::Unsupported(_))));
+}
+
+#[test]
+fn test_repr() {
+    fn derive(ex: Execution) {
+        match ex.target_type() {
+            TypeNode::DataStructure(data) => {
+                assert_eq!(
+                    data.data.repr(),
+                    [
+                        Repr::Int(IntRepr::U8),
+                        Repr::C,
+                        Repr::Packed(None),
+                        Repr::Align(4),
+                    ]
+                );
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    let input = quote! {
+        #[repr(u8)]
+        #[derive(Clone)]
+        #[repr(C, packed, align(4))]
+        enum Repr {
+            A,
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert!(output.is_empty());
+}
+
+#[test]
+fn test_panic_and_unreachable() {
+    let input = quote! {
+        enum Partial {}
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::discriminant::Partial, ex.target_type(), |block| {
+            block.make_function(RUNTIME::discriminant::Partial::partial, |_make_function| {
+                Value::panic_with("not yet supported")
+            });
+            block.make_function(
+                RUNTIME::discriminant::Partial::impossible,
+                |_make_function| Value::unreachable_value(),
+            );
+        });
+    }
+
+    let expected = quote! {
+        impl ::discriminant::Partial for Partial {
+            fn partial<'__a1>(&'__a1 self) -> ::std::primitive::usize {
+                let __v1 = ::core::panic!("not yet supported");
+                __v1
+            }
+
+            fn impossible<'__a2>(&'__a2 self) -> ::std::primitive::usize {
+                let __v2 = ::core::unreachable!();
+                __v2
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_data_accessors() {
+    let input = quote! {
+        enum Accessed {
+            A,
+            B(u8),
+            r#struct,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        let data = ex.target_type().as_data();
+        assert!(data.is_enum());
+        assert_eq!(data.field_count(), 1);
+        assert!(!data.is_struct());
+        assert!(data.as_struct().is_none());
+        let variants = data.as_enum().unwrap();
+        assert_eq!(variants.variant_count(), 3);
+        assert!(variants.has_unit_variants());
+        assert!(variants.has_tuple_variants());
+        assert!(!variants.has_struct_variants());
+        assert!(variants.has_variant("A"));
+        assert!(!variants.has_variant("C"));
+        let b = variants.find_variant("B").unwrap();
+        assert!(matches!(b, Variant::Tuple(_)));
+        let raw = variants.find_variant("struct").unwrap();
+        assert_eq!(raw.name(), &Ident::new_raw("struct"));
+        assert!(variants.has_variant("r#struct"));
+        assert_eq!(data.unwrap_enum().variants()[1].name(), &Ident::new("B"));
+    }
+
+    let output = reflect::derive(input, derive);
+    assert!(output.is_empty());
+}
+
+#[test]
+fn test_variant_names() {
+    let input = quote! {
+        enum Named {
+            Unit,
+            Tuple(u8),
+            Struct { field: u8 },
+        }
+    };
+
+    fn derive(ex: Execution) {
+        let data = ex.target_type().as_data().unwrap_enum();
+        let names: Vec<_> = data
+            .variants()
+            .iter()
+            .map(|variant| {
+                let kind = match variant {
+                    Variant::Unit(_) => "unit",
+                    Variant::Tuple(_) => "tuple",
+                    Variant::Struct(_) => "struct",
+                };
+                (kind, variant.name().to_string())
+            })
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("unit", "Unit".to_owned()),
+                ("tuple", "Tuple".to_owned()),
+                ("struct", "Struct".to_owned()),
+            ]
+        );
+    }
+
+    let output = reflect::derive(input, derive);
+    assert!(output.is_empty());
+}
+
+#[test]
+fn test_non_exhaustive() {
+    fn derive(ex: Execution) {
+        let data = ex.target_type().as_data();
+        let expected = data.attrs().len() == 1;
+        assert_eq!(data.is_non_exhaustive(), expected);
+        if let Some(variants) = data.as_enum() {
+            assert_eq!(variants.has_non_exhaustive(), expected);
+        }
+    }
+
+    let inputs = [
+        quote!(
+            #[non_exhaustive]
+            enum Open {
+                A,
+            }
+        ),
+        quote!(
+            enum Closed {
+                A,
+            }
+        ),
+        quote!(
+            #[non_exhaustive]
+            struct Open;
+        ),
+    ];
+    for input in inputs {
+        assert!(reflect::derive(input, derive).is_empty());
+    }
+}
+
+#[test]
+fn test_match_variant() {
+    let input = quote! {
+        #[non_exhaustive]
+        enum Shape<T> {
+            Empty,
+            Circle(T, u8),
+            Rect { a: u8, b: T },
+        }
+    };
+
+    let expected = quote! {
+        impl<__T0> ::discriminant::Weigh for Shape<__T0>
+        where
+            __T0: ::discriminant::Weigh,
+        {
+            fn weigh<'__a1>(&'__a1 self) -> ::std::primitive::usize {
+                let __v0 = self;
+                let __v12 = match __v0 {
+                    Shape::Empty => {
+                        let __v5 = <::std::primitive::usize as ::core::default::Default>::default();
+                        __v5
+                    }
+                    Shape::Circle(__v6, __v7) => {
+                        let __v8 = ::discriminant::Weigh::weigh(__v6);
+                        __v8
+                    }
+                    Shape::Rect { a: __v9, b: __v10 } => {
+                        let __v11 = ::discriminant::Weigh::weigh(__v10);
+                        __v11
+                    }
+                    #[allow(unreachable_patterns)]
+                    _ => ::core::unreachable!(),
+                };
+                __v12
+            }
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::discriminant::Weigh, ex.target_type(), |block| {
+            block.make_function(RUNTIME::discriminant::Weigh::weigh, |make_function| {
+                let receiver = make_function.arg(0);
+                let data = match receiver.as_data() {
+                    Data::Enum(data) => data,
+                    Data::Struct(_) => panic!("expected an enum"),
+                };
+                data.match_variant(|variant| match variant {
+                    Variant::Unit(_) => {
+                        let usize = Module::root()
+                            .get_module("std")
+                            .get_module("primitive")
+                            .get_path_type("usize", &mut SynParamMap::default())
+                            .unwrap();
+                        Value::default_of(usize)
+                    }
+                    Variant::Tuple(variant) => {
+                        RUNTIME::discriminant::Weigh::weigh.INVOKE(variant.fields()[0].get_value())
+                    }
+                    Variant::Struct(variant) => RUNTIME::discriminant::Weigh::weigh
+                        .INVOKE(variant.field_by_name("b").unwrap().get_value()),
+                })
+            });
+        });
+    }
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_zip() {
+    fn derive(ex: Execution) {
+        stash(ex.target_type().as_data());
+    }
+
+    let inputs = [
+        quote!(
+            enum Zipped {
+                A,
+                B(u8),
+                C { c: u8 },
+            }
+        ),
+        quote!(
+            enum Reordered {
+                C { c: u16 },
+                A,
+                B(u16),
+            }
+        ),
+        quote!(
+            enum Renamed {
+                A,
+                B(u8),
+                C { d: u8 },
+            }
+        ),
+        quote!(
+            enum Fewer {
+                A,
+                B(u8),
+            }
+        ),
+        quote!(
+            struct Unit;
+        ),
+    ];
+    for input in inputs {
+        assert!(reflect::derive(input, derive).is_empty());
+    }
+    let shapes = take_stashed::<Data<TypeNode>>();
+    let zipped = &shapes[0];
+
+    let reordered = zipped.clone().zip(shapes[1].clone()).unwrap();
+    let variants = reordered.unwrap_enum();
+    let names: Vec<_> = variants
+        .variants()
+        .iter()
+        .map(|variant| variant.name().to_string())
+        .collect();
+    assert_eq!(names, ["A", "B", "C"]);
+
+    for other in &shapes[2..] {
+        assert_eq!(
+            zipped.clone().zip(other.clone()).unwrap_err(),
+            ZipError::ShapeMismatch
+        );
+    }
+}
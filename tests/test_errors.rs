@@ -0,0 +1,55 @@
+use quote::quote;
+use reflect::*;
+
+fn derive(_ex: Execution) {
+    unreachable!()
+}
+
+#[test]
+fn test_unsupported_input() {
+    let input = quote! {
+        union Test {
+            a: u32,
+            b: f32,
+        }
+    };
+
+    let expected = quote! {
+        compile_error! { "reflect does not support unions" }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_unsupported_generic_params() {
+    let mut generics = Generics::default();
+    let err = generics
+        .set_generic_params(&["T: AsRef<[u8; 4]>"])
+        .unwrap_err();
+    assert_eq!(err.to_string(), "reflect does not support array types");
+
+    let err = generics.set_generic_constraints(&["'a: 'b"]).unwrap_err();
+    assert!(matches!(err, ReflectError::NotALifetime(_)));
+}
+
+#[test]
+fn test_invalid_strings() {
+    let mut param_map = SynParamMap::default();
+    let err = Path::path_from_str("::std::", &mut param_map).unwrap_err();
+    assert!(matches!(err, ReflectError::Parse(_)));
+
+    let err = Module::std_module()
+        .get_path("Vec<", &mut param_map)
+        .unwrap_err();
+    assert!(matches!(err, ReflectError::Parse(_)));
+
+    let err = TypeNode::new_dyn_trait(&["::std::fmt::Debug +"], &mut param_map).unwrap_err();
+    assert!(matches!(err, ReflectError::Parse(_)));
+
+    let err = TypeNode::PrimitiveStr
+        .new_reference_with_lifetime("'a", &param_map)
+        .unwrap_err();
+    assert_eq!(err.to_string(), "`'a` is not a lifetime in scope");
+}
@@ -1,6 +1,9 @@
 use quote::quote;
 use reflect::*;
 
+mod stash;
+use stash::{stash, stashed};
+
 fn derive(ex: Execution) {
     // Checks from #[trivial(skip)] attribute on a field
     fn should_skip(field: &Field<Value>) -> bool {
@@ -16,7 +19,7 @@ fn derive(ex: Execution) {
             match receiver.as_data() {
                 Data::Struct(Struct::Struct(receiver)) => {
                     for field in receiver.fields() {
-                        if should_skip(&field) {
+                        if should_skip(field) {
                             continue;
                         }
 
@@ -68,3 +71,499 @@ fn test_field_access() {
     let output = reflect::derive(input, derive);
     assert_eq!(output.to_string(), expected.to_string());
 }
+
+#[test]
+fn test_should_skip() {
+    let input = quote! {
+        struct Test {
+            #[trivial(skip)]
+            skipped: u8,
+            #[trivial(other, skip)]
+            among_others: u8,
+            #[other(skip)]
+            other_derive: u8,
+            #[trivial]
+            no_args: u8,
+            #[trivial = "skip"]
+            name_value: u8,
+            kept: u8,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        let data = ex.target_type().as_data();
+        let fields = match &data {
+            Data::Struct(data) => data.fields(),
+            Data::Enum(_) => panic!("expected a struct"),
+        };
+        let skipped: Vec<_> = fields
+            .iter()
+            .map(|field| field.should_skip("trivial"))
+            .collect();
+        assert_eq!(skipped, [true, true, false, false, false, false]);
+        assert!(fields[1].has_meta_attr("trivial", "other"));
+        assert!(!fields[0].has_meta_attr("trivial", "other"));
+    }
+
+    let output = reflect::derive(input, derive);
+    assert!(output.is_empty());
+}
+
+#[test]
+fn test_field_by_name() {
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::base::Trait, ex.target_type(), |block| {
+            block.make_function(RUNTIME::base::Trait::trivial, |make_function| {
+                let receiver = make_function.arg(0);
+                match receiver.as_data() {
+                    Data::Struct(Struct::Struct(receiver)) => {
+                        assert!(receiver.field_by_name("missing").is_none());
+                        let field = receiver.field_by_name("second").unwrap();
+                        RUNTIME::base::FieldAccessor::access_field.INVOKE(field.get_value());
+                    }
+                    _ => panic!("expected a struct with named fields"),
+                }
+                make_function.unit()
+            });
+        });
+    }
+
+    let input = quote! {
+        struct Test {
+            first: String,
+            second: String,
+        }
+    };
+
+    let expected = quote! {
+        impl ::base::Trait for Test {
+            fn trivial<'__a1>(&'__a1 self) {
+                let __v0 = self;
+                let __v2 = &__v0.second;
+                let _ = ::base::FieldAccessor::access_field(__v2);
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_field_visibility() {
+    fn derive(ex: Execution) {
+        match ex.target_type().as_data() {
+            Data::Struct(Struct::Struct(data)) => {
+                let vis: Vec<_> = data.fields().iter().map(Field::visibility).collect();
+                assert_eq!(vis[0], Visibility::Public);
+                assert_eq!(vis[1], Visibility::Crate);
+                assert!(matches!(vis[2], Visibility::Restricted(_)));
+                assert_eq!(vis[3], Visibility::Inherited);
+                assert!(data.fields()[0].is_public());
+                assert!(!data.fields()[1].is_public());
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    let input = quote! {
+        struct Test {
+            pub a: String,
+            pub(crate) b: String,
+            pub(super) c: String,
+            d: String,
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert!(output.is_empty());
+}
+
+#[test]
+fn test_struct_accessors() {
+    let input = quote! {
+        struct Pair(u8, u16);
+    };
+
+    fn derive(ex: Execution) {
+        let data = ex.target_type().as_data();
+        assert_eq!(data.field_count(), 2);
+        assert_eq!(data.fields().len(), 2);
+        let data = data.unwrap_struct();
+        assert!(data.is_tuple());
+        assert!(!data.is_unit());
+        assert!(!data.is_named());
+        assert!(data.as_unit().is_none());
+        assert!(data.as_named().is_none());
+        assert_eq!(data.as_tuple().unwrap().fields().len(), 2);
+        assert!(*data.name() == "Pair");
+        assert!(*data.as_tuple().unwrap().name() == "Pair");
+    }
+
+    let output = reflect::derive(input, derive);
+    assert!(output.is_empty());
+}
+
+#[test]
+fn test_field_ident_and_index() {
+    let input = quote! {
+        enum Mixed {
+            Tuple(u8, u16),
+            Named { first: u8, second: u16 },
+        }
+    };
+
+    fn derive(ex: Execution) {
+        let data = ex.target_type().as_data().unwrap_enum();
+        let (tuple, named) = match data.variants() {
+            [Variant::Tuple(tuple), Variant::Struct(named)] => (tuple, named),
+            _ => panic!("expected a tuple variant and a struct variant"),
+        };
+
+        let indices: Vec<_> = tuple.fields().iter().map(Field::index).collect();
+        assert_eq!(indices, [Some(0), Some(1)]);
+        assert!(tuple.fields().iter().all(|field| field.ident().is_none()));
+
+        let idents: Vec<_> = named
+            .fields()
+            .iter()
+            .map(|field| field.ident().unwrap().to_string())
+            .collect();
+        assert_eq!(idents, ["first", "second"]);
+        assert!(named.fields().iter().all(|field| field.index().is_none()));
+    }
+
+    let output = reflect::derive(input, derive);
+    assert!(output.is_empty());
+}
+
+#[test]
+fn test_type_ident() {
+    let input = quote! {
+        struct Pair(u8, u16);
+    };
+
+    let expected = quote! {
+        impl ::base::Trait for Pair {
+            fn trivial<'__a1>(&'__a1 self) {}
+        }
+    };
+
+    fn derive(ex: Execution) {
+        match ex.target_type() {
+            TypeNode::DataStructure(data) => assert_eq!(*data.name(), Ident::new("Pair")),
+            other => panic!("expected a data structure, but {}", other),
+        }
+        ex.make_trait_impl(RUNTIME::base::Trait, ex.target_type(), |block| {
+            block.make_function(RUNTIME::base::Trait::trivial, |make_function| {
+                let receiver = make_function.arg(0);
+                assert_eq!(receiver.type_ident().unwrap(), Ident::new("Pair"));
+                let unit = make_function.unit();
+                assert_eq!(
+                    unit.type_ident().unwrap_err().to_string(),
+                    "expected a data structure or a path, found `()`",
+                );
+                unit
+            });
+        });
+    }
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_try_map() {
+    let input = quote! {
+        struct Mapped {
+            first: u8,
+            second: u16,
+            third: u32,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        let data = ex.target_type().as_data();
+        let names = data
+            .clone()
+            .try_map(|field| Ok::<_, ()>(field.get_name().to_string()))
+            .unwrap();
+        assert_eq!(names.field_count(), 3);
+
+        let mut visited = 0;
+        let result = data.try_map(|field| {
+            visited += 1;
+            match field.get_name().to_string().as_str() {
+                "second" => Err("second"),
+                _ => Ok(()),
+            }
+        });
+        assert_eq!(result.unwrap_err(), "second");
+        assert_eq!(visited, 2);
+    }
+
+    let output = reflect::derive(input, derive);
+    assert!(output.is_empty());
+}
+
+#[test]
+fn test_raw_field() {
+    assert_eq!(Ident::new_raw("type").to_string(), "r#type");
+
+    let input = quote! {
+        struct Test {
+            r#type: String,
+        }
+    };
+
+    let expected = quote! {
+        impl ::base::Trait for Test {
+            fn trivial<'__a1>(&'__a1 self) {
+                let __v0 = self;
+                let __v1 = &__v0.r#type;
+                let _ = ::base::FieldAccessor::access_field(__v1);
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_raw_field_by_name() {
+    let input = quote! {
+        struct Test {
+            r#type: String,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        let data = match ex.target_type().as_data() {
+            Data::Struct(Struct::Struct(data)) => data,
+            _ => panic!("expected a struct with named fields"),
+        };
+        let by_name = data.field_by_name("type").unwrap();
+        assert_eq!(by_name.get_name(), "r#type");
+        let by_raw_name = data.field_by_name("r#type").unwrap();
+        assert_eq!(by_raw_name.get_name(), "r#type");
+    }
+
+    let output = reflect::derive(input, derive);
+    assert!(output.is_empty());
+}
+
+#[test]
+fn test_ident_keywords() {
+    let raw = Ident::from_str("r#match");
+    assert_eq!(raw, Ident::new_raw("match"));
+    assert_eq!(raw.as_str(), "match");
+    assert!(raw.is_keyword());
+    assert!(raw == "r#match");
+    assert!(raw != "match");
+
+    let plain = Ident::from_str("matches");
+    assert_eq!(plain.as_str(), "matches");
+    assert!(!plain.is_keyword());
+    assert!(plain == "matches");
+}
+
+#[test]
+#[should_panic(expected = "is a keyword")]
+fn test_ident_from_keyword() {
+    Ident::from_str("match");
+}
+
+#[test]
+#[should_panic(expected = "`self` can not be a raw identifier")]
+fn test_ident_raw_self() {
+    Ident::from_str("r#self");
+}
+
+#[test]
+fn test_ident_hash() {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    fn hash(ident: &Ident) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        ident.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    assert_eq!(hash(&Ident::new("match")), hash(&Ident::new("match")));
+    assert_ne!(hash(&Ident::new("match")), hash(&Ident::new_raw("match")));
+}
+
+#[test]
+fn test_map_type() {
+    let input = quote! {
+        #[repr(C)]
+        struct Mapped(u8, u16);
+    };
+
+    fn derive(ex: Execution) {
+        let data = match ex.target_type() {
+            TypeNode::DataStructure(data) => *data,
+            _ => unreachable!(),
+        };
+        let mapped = DataStructure {
+            data: data.data.map_type(TypeNode::new_reference),
+            ..data.clone()
+        };
+        assert_eq!(mapped.data.attrs(), data.data.attrs());
+
+        let mapped = TypeNode::DataStructure(Box::new(mapped));
+        assert_eq!(mapped.index(0).to_string(), "& u8");
+        assert_eq!(mapped.index(1).to_string(), "& u16");
+    }
+
+    let output = reflect::derive(input, derive);
+    assert!(output.is_empty());
+}
+
+#[test]
+fn test_invoke_as_data() {
+    fn derive(ex: Execution) {
+        stash(ex.target_type());
+        ex.make_trait_impl(RUNTIME::base::Trait, ex.target_type(), |block| {
+            block.make_function(RUNTIME::base::Trait::trivial, |make_function| {
+                let target = stashed::<TypeNode>();
+                let rebuild = FunctionBuilder::new()
+                    .name(Ident::new("rebuild"))
+                    .input(target.new_reference())
+                    .output(target)
+                    .build();
+                let rebuilt = Value::call(rebuild, &[make_function.arg(0)]);
+                match rebuilt.as_data() {
+                    Data::Struct(Struct::Struct(rebuilt)) => {
+                        let test = rebuilt.field_by_name("test").unwrap().get_value();
+                        RUNTIME::base::FieldAccessor::access_field.INVOKE(test.new_reference());
+                    }
+                    _ => panic!("expected a struct with named fields"),
+                }
+                make_function.unit()
+            });
+        });
+    }
+
+    let input = quote! {
+        struct Test {
+            pub test: String,
+        }
+    };
+
+    let expected = quote! {
+        impl ::base::Trait for Test {
+            fn trivial<'__a1>(&'__a1 self) {
+                let __v0 = self;
+                let __v1 = rebuild(__v0);
+                let __v2 = __v1.test;
+                let __v3 = &__v2;
+                let _ = ::base::FieldAccessor::access_field(__v3);
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_nested_as_data() {
+    fn derive(ex: Execution) {
+        stash(ex.target_type());
+        ex.make_trait_impl(RUNTIME::base::Trait, ex.target_type(), |block| {
+            block.make_function(RUNTIME::base::Trait::trivial, |make_function| {
+                let target = stashed::<TypeNode>();
+                let data = match &target {
+                    TypeNode::DataStructure(data) => data,
+                    _ => unreachable!(),
+                };
+                // The same shape, with every field holding the target itself
+                let nested = DataStructure {
+                    data: data.data.map_type(|_| target.clone()),
+                    ..(**data).clone()
+                };
+                let nest = FunctionBuilder::new()
+                    .name(Ident::new("nest"))
+                    .input(target.new_reference())
+                    .output(TypeNode::DataStructure(Box::new(nested)))
+                    .build();
+                let nested = Value::call(nest, &[make_function.arg(0)]);
+                let outer = match nested.as_data() {
+                    Data::Struct(Struct::Struct(outer)) => outer,
+                    _ => panic!("expected a struct with named fields"),
+                };
+                let outer = outer.field_by_name("test").unwrap().get_value();
+                let inner = match outer.as_data() {
+                    Data::Struct(Struct::Struct(inner)) => inner,
+                    _ => panic!("expected a struct with named fields"),
+                };
+                let inner = inner.field_by_name("test").unwrap().get_value();
+                RUNTIME::base::FieldAccessor::access_field.INVOKE(inner.new_reference());
+                make_function.unit()
+            });
+        });
+    }
+
+    let input = quote! {
+        struct Test {
+            pub test: String,
+        }
+    };
+
+    let expected = quote! {
+        impl ::base::Trait for Test {
+            fn trivial<'__a1>(&'__a1 self) {
+                let __v0 = self;
+                let __v1 = nest(__v0);
+                let __v2 = __v1.test;
+                let __v3 = __v2.test;
+                let __v4 = &__v3;
+                let _ = ::base::FieldAccessor::access_field(__v4);
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_filter_fields() {
+    fn derive(ex: Execution) {
+        let data = ex.target_type().as_data();
+        let kept = data.filter_fields(|field| {
+            if field.should_skip("trivial") {
+                None
+            } else {
+                Some(())
+            }
+        });
+        assert_eq!(kept.field_count(), 1);
+        let field = match &kept {
+            Data::Struct(data) => &data.fields()[0],
+            Data::Enum(data) => match &data.variants()[1] {
+                Variant::Tuple(variant) => &variant.fields()[0],
+                _ => unreachable!(),
+            },
+        };
+        assert_eq!(field.index(), Some(1));
+    }
+
+    let inputs = [
+        quote! {
+            struct Pair(#[trivial(skip)] u8, u16);
+        },
+        quote! {
+            enum Either {
+                A,
+                B(#[trivial(skip)] u8, u16),
+            }
+        },
+    ];
+    for input in inputs {
+        assert!(reflect::derive(input, derive).is_empty());
+    }
+}
@@ -1,5 +1,9 @@
 use quote::quote;
 use reflect::*;
+use std::collections::HashSet;
+
+mod stash;
+use stash::{stash, stashed, take_stashed};
 
 library! {
     use std {
@@ -16,7 +20,18 @@ library! {
 
         trait Bound {}
 
+        trait Capacity {
+            fn capacity(&self) -> ::std::primitive::usize;
+        }
+
         trait TraitArgs<'a, 'b: 'a, T, U: 'static> {}
+
+        trait Callback {
+            fn call<'a, 'b, F>(&self, F, &'a Bound, &'b Bound)
+            where
+                'a: 'b,
+                for<'c> F: ::std::ops::Fn(&'c Bound) -> Bound;
+        }
     }
 }
 
@@ -118,3 +133,618 @@ fn test_generics2() {
 
     assert_eq!(&output, &expected.to_string());
 }
+
+#[test]
+fn test_generic_param_counts() {
+    let input = quote! {
+        struct Counts<'a, 'b, T, U: 'a, V> {
+            a: &'a T,
+            b: &'b U,
+            v: V,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        match ex.target_type() {
+            TypeNode::DataStructure(data) => {
+                let generics = &data.generics;
+                assert!(!generics.is_empty());
+                assert_eq!(generics.lifetime_count(), 2);
+                assert_eq!(generics.type_param_count(), 3);
+                assert_eq!(generics.lifetime_params().count(), 2);
+                assert_eq!(generics.type_params().count(), 3);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    let output = reflect::derive(input, derive);
+    assert!(output.is_empty());
+}
+
+#[test]
+fn test_syn_param_map() {
+    let mut param_map = SynParamMap::default();
+    assert!(param_map.contains("'static"));
+    assert!(!param_map.contains("T"));
+    assert_eq!(param_map.len(), 1);
+    assert!(!param_map.is_empty());
+
+    let mut sig = Signature::new();
+    sig.set_generic_params(&["'a", "T"]).unwrap();
+    sig.add_parent_params(&mut param_map);
+    assert!(param_map.is_empty());
+}
+
+#[test]
+fn test_syn_param_map_iter() {
+    let input = quote! {
+        struct Iter<'a, T, const N: usize>(&'a T);
+    };
+
+    fn derive(ex: Execution) {
+        match ex.target_type() {
+            TypeNode::DataStructure(data) => {
+                let generics = &data.generics;
+                let params: Vec<_> = generics.param_map().iter().collect();
+                let names: Vec<&str> = params.iter().map(|(name, _)| *name).collect();
+                assert_eq!(names, ["'a", "'static", "N", "T"]);
+                assert_eq!(
+                    params[0].1,
+                    &GenericParam::Lifetime(generics.lifetime_params().next().unwrap())
+                );
+                assert_eq!(params[1].1, &GenericParam::Lifetime(Lifetime(0)));
+                assert!(matches!(params[2].1, GenericParam::Const(_)));
+                assert_eq!(
+                    params[3].1,
+                    &GenericParam::Type(generics.type_params().next().unwrap())
+                );
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    let output = reflect::derive(input, derive);
+    assert!(output.is_empty());
+}
+
+#[test]
+fn test_implies_bound() {
+    let input = quote! {
+        struct Implied<'a, T: Clone + 'static, U>(&'a (dyn Clone, dyn Copy + 'a), T, U)
+        where
+            U: Copy;
+    };
+
+    fn trait_object_bounds(ty: TypeNode) -> Vec<TypeParamBound> {
+        match ty {
+            TypeNode::TraitObject(bounds) => bounds,
+            _ => unreachable!(),
+        }
+    }
+
+    fn derive(ex: Execution) {
+        let ty = ex.target_type();
+        let objects = ty.index(0).dereference();
+        let clone = trait_object_bounds(objects.index(0));
+        let copy = trait_object_bounds(objects.index(1));
+        let (t, u) = (ty.index(1), ty.index(2));
+        match ty {
+            TypeNode::DataStructure(data) => {
+                let generics = &data.generics;
+                assert!(generics.implies_bound(&t, &clone[0]));
+                assert!(!generics.implies_bound(&t, &copy[0]));
+                assert!(generics.implies_bound(&t, &copy[1]));
+                assert!(generics.implies_bound(&u, &copy[0]));
+                assert!(!generics.implies_bound(&u, &clone[0]));
+                assert!(!generics.implies_bound(&u, &copy[1]));
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    let output = reflect::derive(input, derive);
+    assert!(output.is_empty());
+}
+
+#[test]
+fn test_generics_to_syn() {
+    let input = quote! {
+        struct ToSyn<'a, T: Clone>(&'a T) where T: Send;
+    };
+
+    fn derive(ex: Execution) {
+        match ex.target_type() {
+            TypeNode::DataStructure(data) => {
+                let generics = data.generics.to_syn();
+                assert_eq!(generics.params.len(), 2);
+                let (impl_generics, _, where_clause) = generics.split_for_impl();
+                let expected = quote! {
+                    <'__a1, __T0> where __T0: Clone, __T0: Send,
+                };
+                assert_eq!(
+                    quote!(#impl_generics #where_clause).to_string(),
+                    expected.to_string()
+                );
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    let output = reflect::derive(input, derive);
+    assert!(output.is_empty());
+}
+
+#[test]
+fn test_const_param() {
+    let input = quote! {
+        struct Buf<T, const N: usize> {
+            items: ::std::vec::Vec<T>,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        let target = ex.target_type();
+        let param_map = match &target {
+            TypeNode::DataStructure(data) => data.generics.param_map().clone(),
+            other => panic!("expected a data structure, but {}", other),
+        };
+        assert!(matches!(
+            Value::const_param("T", &param_map),
+            Err(ReflectError::NotAConstParam(_))
+        ));
+        stash(param_map);
+
+        ex.make_trait_impl(RUNTIME::generic::Capacity, target, |block| {
+            block.make_function(RUNTIME::generic::Capacity::capacity, |_make_function| {
+                Value::const_param("N", &stashed::<SynParamMap>()).unwrap()
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl<__T0, const __C0: usize> ::generic::Capacity for Buf<__T0, __C0> {
+            fn capacity<'__a1>(&'__a1 self) -> ::std::primitive::usize {
+                __C0
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_const_arguments() {
+    let mut generics = Generics::default();
+    generics
+        .set_generic_params(&["T", "const N: usize"])
+        .unwrap();
+    let mut param_map = generics.param_map().clone();
+    assert_eq!(generics.const_params().count(), 1);
+    assert_eq!(
+        generics.to_string(),
+        quote!(<__T0, const __C0: usize>).to_string()
+    );
+
+    let path = Path::path_from_str("Buf<T, N, { N }, { N + 1 }, 3>", &mut param_map).unwrap();
+    assert_eq!(
+        path.to_string(),
+        quote!(Buf<__T0, __C0, __C0, { __C0 + 1 }, 3>).to_string()
+    );
+
+    let path = Path::path_from_str("Buf<T, { N }, 3>", &mut param_map).unwrap();
+    let named = path.to_syn_with_names(&param_map).unwrap();
+    assert_eq!(quote!(#named).to_string(), quote!(Buf<T, N, 3>).to_string());
+}
+
+#[test]
+fn test_used_by() {
+    let input = quote! {
+        struct Used<'a, 'b, T: Clone, U: Copy>(&'a T, &'b U)
+        where
+            T: 'a,
+            U: 'b;
+    };
+
+    fn derive(ex: Execution) {
+        let ty = ex.target_type();
+        let field = ty.index(0);
+        match &ty {
+            TypeNode::DataStructure(data) => {
+                let used = data.generics.used_by(&field);
+                assert_eq!(used.type_params().collect::<Vec<_>>(), [TypeParam(0)]);
+                assert_eq!(used.lifetime_params().collect::<Vec<_>>(), [Lifetime(1)]);
+                assert!(used.param_map().contains("T"));
+                assert!(!used.param_map().contains("U"));
+
+                let generics = used.to_syn();
+                let (impl_generics, _, where_clause) = generics.split_for_impl();
+                let expected = quote! {
+                    <'__a1, __T0> where __T0: Clone, __T0: '__a1,
+                };
+                assert_eq!(
+                    quote!(#impl_generics #where_clause).to_string(),
+                    expected.to_string()
+                );
+
+                assert_eq!(data.generics.used_by(&ty), data.generics);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    let output = reflect::derive(input, derive);
+    assert!(output.is_empty());
+}
+
+#[test]
+fn test_function_where_clause() {
+    let input = quote! {
+        struct Unit;
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::generic::Callback, ex.target_type(), |block| {
+            block.make_function(RUNTIME::generic::Callback::call, |make_function| {
+                make_function.unit()
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl ::generic::Callback for Unit {
+            fn call<'__a4, '__a1, '__a2, __T0>(
+                &'__a4 self,
+                __arg0: __T0,
+                __arg1: &'__a1 ::generic::Bound,
+                __arg2: &'__a2 ::generic::Bound
+            )
+            where
+                '__a1: '__a2,
+                for<'__a3> __T0: ::std::ops::Fn(&'__a3 ::generic::Bound) -> ::generic::Bound,
+            {}
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_display() {
+    let input = quote! {
+        struct Display<'a, T: Clone>(&'a ::std::vec::Vec<T>);
+    };
+
+    fn derive(ex: Execution) {
+        match ex.target_type() {
+            TypeNode::DataStructure(data) => {
+                let generics = data.generics.to_string();
+                assert_eq!(
+                    generics,
+                    quote!(<'__a1, __T0> where __T0: Clone,).to_string()
+                );
+                let field = ex.target_type().index(0).to_string();
+                assert_eq!(field, quote!(&'__a1 ::std::vec::Vec<__T0>).to_string());
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    let output = reflect::derive(input, derive);
+    assert!(output.is_empty());
+}
+
+#[test]
+fn test_type_to_syn() {
+    let input = quote! {
+        struct ToSyn<'a, T>(&'a (dyn ::std::fmt::Debug + 'a), (T,), &'static mut str);
+    };
+
+    fn derive(ex: Execution) {
+        let ty = ex.target_type();
+        let types = [ty.index(0), ty.index(1), ty.index(2)];
+        let syn_types: Vec<syn::Type> = types.iter().map(|ty| ty.to_syn().unwrap()).collect();
+        let expected = quote! {
+            &'__a1 (dyn ::std::fmt::Debug + '__a1),
+            (__T0,),
+            &'static mut str
+        };
+        assert_eq!(quote!(#(#syn_types),*).to_string(), expected.to_string());
+    }
+
+    let output = reflect::derive(input, derive);
+    assert!(output.is_empty());
+}
+
+#[test]
+fn test_param_idents() {
+    let input = quote! {
+        struct Idents<'a, T>(&'a T);
+    };
+
+    fn derive(ex: Execution) {
+        match ex.target_type() {
+            TypeNode::DataStructure(data) => {
+                let param_map = data.generics.param_map();
+                let lifetime = data.generics.lifetime_params().next().unwrap();
+                let type_param = data.generics.type_params().next().unwrap();
+                assert_eq!(lifetime.ident(param_map), Some("'a"));
+                assert_eq!(type_param.ident(param_map), Some("T"));
+                assert_eq!(Lifetime(0).ident(param_map), Some("'static"));
+                assert_eq!(TypeParam(100).ident(param_map), None);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    let output = reflect::derive(input, derive);
+    assert!(output.is_empty());
+}
+
+#[test]
+fn test_path_to_syn() {
+    let input = quote! {
+        struct ToSyn<'a, T>(::std::vec::Vec<&'a T>, ::std::borrow::Cow<'static, str>);
+    };
+
+    fn derive(ex: Execution) {
+        let ty = ex.target_type();
+        let param_map = match &ty {
+            TypeNode::DataStructure(data) => data.generics.param_map().clone(),
+            _ => unreachable!(),
+        };
+        let (vec, cow) = match (ty.index(0), ty.index(1)) {
+            (TypeNode::Path(vec), TypeNode::Path(cow)) => (vec, cow),
+            _ => unreachable!(),
+        };
+
+        let generated = vec.to_syn().unwrap();
+        assert_eq!(
+            quote!(#generated).to_string(),
+            quote!(::std::vec::Vec<&'__a1 __T0>).to_string()
+        );
+
+        let named = vec.to_syn_with_names(&param_map).unwrap();
+        assert_eq!(
+            quote!(#named).to_string(),
+            quote!(::std::vec::Vec<&'a T>).to_string()
+        );
+
+        let named = cow.to_syn_with_names(&param_map).unwrap();
+        assert_eq!(
+            quote!(#named).to_string(),
+            quote!(::std::borrow::Cow<'static, str>).to_string()
+        );
+    }
+
+    let output = reflect::derive(input, derive);
+    assert!(output.is_empty());
+}
+
+#[test]
+fn test_canonical() {
+    fn derive(ex: Execution) {
+        stash(ex.target_type());
+    }
+
+    let inputs = [
+        quote!(
+            struct Canonical<'a, T: Clone>(&'a T);
+        ),
+        quote!(
+            struct Canonical<'a, T: Clone>(&'a T);
+        ),
+        quote!(
+            struct Canonical<'b, U: Clone>(&'b U);
+        ),
+        quote!(
+            struct Canonical<'a, T: Copy>(&'a T);
+        ),
+    ];
+    for input in inputs {
+        assert!(reflect::derive(input, derive).is_empty());
+    }
+    let types = take_stashed::<TypeNode>();
+    assert_ne!(types[0], types[1]);
+
+    let canonical: Vec<_> = types.iter().map(TypeNode::canonical).collect();
+    assert_eq!(canonical[0], canonical[1]);
+    assert_eq!(canonical[0], canonical[2]);
+    assert_ne!(canonical[0], canonical[3]);
+    assert_eq!(canonical.iter().collect::<HashSet<_>>().len(), 2);
+
+    match (&types[0], &types[2]) {
+        (TypeNode::DataStructure(first), TypeNode::DataStructure(renamed)) => {
+            assert_eq!(first.generics.canonical(), renamed.generics.canonical());
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn test_is_same_shape() {
+    fn derive(ex: Execution) {
+        stash(ex.target_type());
+    }
+
+    let inputs = [
+        quote!(
+            struct Shape<T, U>(::std::vec::Vec<T>, U);
+        ),
+        quote!(
+            struct Shape<A, B>(::std::vec::Vec<A>, B);
+        ),
+    ];
+    for input in inputs {
+        assert!(reflect::derive(input, derive).is_empty());
+    }
+    let types = take_stashed::<TypeNode>();
+    let params = |ty: &TypeNode| match ty {
+        TypeNode::DataStructure(data) => data.generics.type_params().collect::<Vec<_>>(),
+        _ => unreachable!(),
+    };
+    let (first, second) = (&types[0], &types[1]);
+    let (first_params, second_params) = (params(first), params(second));
+
+    assert!(first.is_same_shape(second, &first_params, &second_params));
+    assert!(!first.is_same_shape(second, &[], &[]));
+
+    let swapped = [second_params[1], second_params[0]];
+    assert!(!first.is_same_shape(second, &first_params, &swapped));
+    assert!(first.index(0).is_same_shape(
+        &second.index(0),
+        &first_params[..1],
+        &second_params[..1]
+    ));
+}
+
+#[test]
+fn test_rename_param() {
+    let input = quote! {
+        struct Renamed<'a, T, X>(&'a T, X);
+    };
+
+    fn derive(ex: Execution) {
+        let mut generics = match ex.target_type() {
+            TypeNode::DataStructure(data) => data.generics,
+            _ => unreachable!(),
+        };
+        let t = generics.type_params().next().unwrap();
+        let a = generics.lifetime_params().next().unwrap();
+
+        generics.rename_type_param(t, "U").unwrap();
+        generics.rename_lifetime(a, "'b").unwrap();
+        assert_eq!(t.ident(generics.param_map()), Some("U"));
+        assert_eq!(a.ident(generics.param_map()), Some("'b"));
+        assert!(!generics.param_map().contains("T"));
+
+        assert!(matches!(
+            generics.rename_type_param(t, "'b"),
+            Err(ReflectError::Parse(_))
+        ));
+        generics.rename_lifetime(a, "'c").unwrap();
+        generics.rename_type_param(t, "U").unwrap();
+        assert!(matches!(
+            generics.rename_type_param(t, "X"),
+            Err(ReflectError::DuplicateParam(name)) if name == "X"
+        ));
+        assert!(matches!(
+            generics.rename_type_param(TypeParam(usize::MAX), "W"),
+            Err(ReflectError::NotATypeParam(_))
+        ));
+    }
+
+    let output = reflect::derive(input, derive);
+    assert!(output.is_empty());
+}
+
+#[test]
+fn test_trait_bound_new() {
+    let mut param_map = SynParamMap::default();
+    let std = Module::root().get_module("std");
+    let debug = std
+        .get_module("fmt")
+        .get_path("Debug", &mut param_map)
+        .unwrap();
+    let send = std
+        .get_module("marker")
+        .get_path("Send", &mut param_map)
+        .unwrap();
+    let object = TypeNode::TraitObject(vec![
+        TypeParamBound::Trait(TraitBound::with_for_lifetimes(debug, &[Lifetime(1)])),
+        TypeParamBound::Trait(TraitBound::new(send)),
+        TypeParamBound::new_lifetime(Lifetime(2)),
+    ]);
+    assert_eq!(
+        object.to_string(),
+        "(dyn for < '__a1 > :: std :: fmt :: Debug + :: std :: marker :: Send + '__a2)"
+    );
+}
+
+#[test]
+fn test_dyn_and_impl_trait() {
+    let mut param_map = SynParamMap::default();
+    let bounds = ["::std::fmt::Debug", "::std::marker::Send"];
+    let object = TypeNode::new_dyn_trait(&bounds, &mut param_map).unwrap();
+    assert_eq!(
+        object.to_string(),
+        "(dyn :: std :: fmt :: Debug + :: std :: marker :: Send)"
+    );
+
+    let opaque = TypeNode::new_impl_trait(&bounds, &mut param_map).unwrap();
+    assert_eq!(
+        opaque.to_string(),
+        "(impl :: std :: fmt :: Debug + :: std :: marker :: Send)"
+    );
+    assert!(matches!(opaque.to_syn(), Ok(syn::Type::Paren(_))));
+
+    let iterator = TypeNode::new_impl_trait(&["::std::iter::Iterator<Item = T>"], &mut param_map)
+        .unwrap()
+        .new_reference();
+    assert_eq!(
+        iterator.to_string(),
+        "& impl :: std :: iter :: Iterator < Item = T >"
+    );
+    assert!(matches!(iterator.to_syn(), Ok(syn::Type::Reference(_))));
+
+    let target = object.dereference().new_reference();
+    assert_eq!(
+        target.to_string(),
+        "& < (dyn :: std :: fmt :: Debug + :: std :: marker :: Send) as :: core :: ops :: Deref > :: Target"
+    );
+    assert!(matches!(
+        target.to_syn(),
+        Err(ReflectError::Unsupported("dereferenced types"))
+    ));
+}
+
+#[test]
+fn test_print_data_structure() {
+    let input = quote! {
+        struct Pair<'a, T, const N: usize>(&'a T);
+    };
+
+    fn derive(ex: Execution) {
+        let ty = ex.target_type();
+        assert_eq!(ty.to_string(), "Pair < '__a1 , __T0 , __C0 >");
+        assert!(matches!(ty.to_syn(), Ok(syn::Type::Path(_))));
+    }
+
+    let output = reflect::derive(input, derive);
+    assert!(output.is_empty());
+}
+
+#[test]
+fn test_with_additional_bound() {
+    let input = quote! {
+        struct Bounded<T: Clone, U>(T, U);
+    };
+
+    fn derive(ex: Execution) {
+        let generics = match ex.target_type() {
+            TypeNode::DataStructure(data) => data.generics,
+            _ => unreachable!(),
+        };
+        let bound =
+            |path| TraitBound::new(Path::path_from_str(path, &mut SynParamMap::default()).unwrap());
+
+        let print = |generics: Generics| {
+            let generics = generics.to_syn();
+            let (impl_generics, _, where_clause) = generics.split_for_impl();
+            quote!(#impl_generics #where_clause).to_string()
+        };
+        assert_eq!(
+            print(generics.with_additional_bound(TypeParam(1), bound("Send"))),
+            quote!(<__T0, __T1> where __T0: Clone, __T1: Send,).to_string()
+        );
+        assert_eq!(
+            print(generics.with_bound_for_all_type_params(bound("Clone"))),
+            quote!(<__T0, __T1> where __T0: Clone, __T1: Clone,).to_string()
+        );
+    }
+
+    let output = reflect::derive(input, derive);
+    assert!(output.is_empty());
+}
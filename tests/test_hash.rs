@@ -106,3 +106,44 @@ fn test_generic_hash() {
     let actual = reflect::derive(input, derive);
     assert_eq!(actual.to_string(), expected.to_string());
 }
+
+#[test]
+fn test_hash_into() {
+    let input = quote! {
+        struct Point {
+            x: i32,
+            y: i32,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::std::hash::Hash, ex.target_type(), |block| {
+            block.make_function(RUNTIME::std::hash::Hash::hash, |f| {
+                let hasher = f.arg(1);
+                for field in f.arg(0).as_data().fields() {
+                    field.get_value().hash_into(hasher);
+                }
+                f.unit()
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl ::std::hash::Hash for Point {
+            fn hash<'__a1, '__a2, __T0>(&'__a1 self, __arg0: &'__a2 mut __T0)
+            where
+                __T0: ::std::hash::Hasher,
+            {
+                let __v0 = __arg0;
+                let __v1 = self;
+                let __v2 = &__v1.x;
+                let __v3 = &__v1.y;
+                let _ = ::core::hash::Hash::hash(__v2, __v0);
+                let _ = ::core::hash::Hash::hash(__v3, __v0);
+            }
+        }
+    };
+
+    let actual = reflect::derive(input, derive);
+    assert_eq!(actual.to_string(), expected.to_string());
+}
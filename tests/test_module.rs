@@ -0,0 +1,268 @@
+use reflect::*;
+use std::rc::Rc;
+
+#[test]
+fn test_crate_root() {
+    let mut param_map = SynParamMap::default();
+    let module = Module::crate_root().get_module("shapes");
+    let ty = module.get_path_type("Circle", &mut param_map).unwrap();
+    assert_eq!(ty.to_string(), "crate :: shapes :: Circle");
+
+    let ty = Module::root()
+        .get_module("shapes")
+        .get_path_type("Circle", &mut param_map)
+        .unwrap();
+    assert_eq!(ty.to_string(), ":: shapes :: Circle");
+}
+
+#[test]
+fn test_crate_path_from_str() {
+    let path = Path::path_from_str("crate::shapes::Circle", &mut SynParamMap::default()).unwrap();
+    assert_eq!(
+        path,
+        Module::crate_root()
+            .get_module("shapes")
+            .get_path("Circle", &mut SynParamMap::default())
+            .unwrap()
+    );
+}
+
+#[test]
+fn test_super_and_self_module() {
+    let mut param_map = SynParamMap::default();
+    let shapes = Module::crate_root().get_module("shapes");
+    let ty = shapes
+        .super_module()
+        .unwrap()
+        .get_path_type("Circle", &mut param_map)
+        .unwrap();
+    assert_eq!(ty.to_string(), "crate :: Circle");
+
+    let ty = Module::root()
+        .get_module("std")
+        .get_module("vec")
+        .super_module()
+        .unwrap()
+        .get_path_type("String", &mut param_map)
+        .unwrap();
+    assert_eq!(ty.to_string(), ":: std :: String");
+
+    let ty = shapes
+        .self_module()
+        .get_path_type("Circle", &mut param_map)
+        .unwrap();
+    assert_eq!(ty.to_string(), "crate :: shapes :: Circle");
+}
+
+#[test]
+fn test_super_module_of_root() {
+    assert!(matches!(
+        Module::crate_root().super_module(),
+        Err(ReflectError::Unsupported(_))
+    ));
+    assert!(matches!(
+        Module::root().super_module(),
+        Err(ReflectError::Unsupported(_))
+    ));
+}
+
+#[test]
+fn test_std_and_core_modules() {
+    let mut param_map = SynParamMap::default();
+    let ty = Module::std_module()
+        .get_module("vec")
+        .get_path_type("Vec", &mut param_map)
+        .unwrap();
+    assert_eq!(ty.to_string(), ":: std :: vec :: Vec");
+
+    let ty = Module::core_module()
+        .get_module("option")
+        .get_path_type("Option", &mut param_map)
+        .unwrap();
+    assert_eq!(ty.to_string(), ":: core :: option :: Option");
+    assert!(ty.is_option());
+}
+
+#[test]
+fn test_append_type_arg() {
+    let mut param_map = SynParamMap::default();
+    let key = Module::root()
+        .get_module("std")
+        .get_module("string")
+        .get_path_type("String", &mut param_map)
+        .unwrap();
+    let value = TypeNode::new_unit();
+    let hash_map = Module::root()
+        .get_module("std")
+        .get_module("collections")
+        .get_path("HashMap", &mut param_map)
+        .unwrap()
+        .append_type_arg(key)
+        .append_type_arg(value);
+    assert_eq!(
+        TypeNode::Path(hash_map).to_string(),
+        ":: std :: collections :: HashMap < :: std :: string :: String , () >"
+    );
+}
+
+#[test]
+fn test_all_items() {
+    let shapes = Module::crate_root().get_module("shapes");
+    let area = FunctionBuilder::new().name(Ident::new("area")).build();
+    let (parent, _) = ImplBuilder::for_type(
+        shapes
+            .get_path_type("Circle", &mut SynParamMap::default())
+            .unwrap(),
+    )
+    .unwrap()
+    .build();
+    shapes.register_function(area.clone());
+    let perimeter = FunctionBuilder::new().name(Ident::new("perimeter")).build();
+    shapes.get_module("inner").register_function(perimeter);
+    shapes.register_parent(parent.clone());
+
+    let items = shapes.all_items();
+    assert_eq!(items.len(), 2);
+    assert!(matches!(&items[0], Item::Function(f) if Rc::ptr_eq(f, &area)));
+    assert!(matches!(&items[1], Item::Parent(p) if Rc::ptr_eq(p, &parent)));
+    assert!(Module::crate_root().all_items().is_empty());
+}
+
+#[test]
+fn test_path_absolute() {
+    let mut param_map = SynParamMap::default();
+    let relative = Path::path_from_str("std::vec::Vec", &mut param_map).unwrap();
+    assert!(relative.is_relative());
+    assert!(!relative.is_absolute());
+
+    let absolute = relative.make_absolute().unwrap();
+    assert!(absolute.is_absolute());
+    assert_eq!(
+        absolute,
+        Path::path_from_str("::std::vec::Vec", &mut param_map).unwrap()
+    );
+    assert_eq!(absolute.make_absolute().unwrap(), absolute);
+
+    let crate_path = Module::crate_root()
+        .get_path("Circle", &mut param_map)
+        .unwrap();
+    assert!(crate_path.is_relative());
+}
+
+#[test]
+fn test_make_absolute_crate_path() {
+    let err = Module::crate_root()
+        .get_path("Circle", &mut SynParamMap::default())
+        .unwrap()
+        .make_absolute()
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "reflect does not support `::` before `crate`, `self` or `super`"
+    );
+}
+
+#[test]
+fn test_impl_builder_for_non_path() {
+    let err = ImplBuilder::for_type(TypeNode::new_unit()).err().unwrap();
+    assert_eq!(
+        err.to_string(),
+        "expected a path or a data structure, found `()`"
+    );
+}
+
+#[test]
+fn test_path_segments() {
+    let mut param_map = SynParamMap::default();
+    let option = Path::path_from_str("::std::option::Option", &mut param_map).unwrap();
+    assert!(*option.first_segment().unwrap().ident() == "std");
+    assert!(*option.last_segment().unwrap().ident() == "Option");
+    assert!(*option.last_ident().unwrap() == "Option");
+}
+
+#[test]
+fn test_well_known_paths() {
+    let mut param_map = SynParamMap::default();
+    let option = Path::path_from_str("::std::option::Option", &mut param_map).unwrap();
+    assert!(option.ends_with(&["Option"]));
+    assert!(option.ends_with(&["std", "option", "Option"]));
+    assert!(!option.ends_with(&["core", "option", "Option"]));
+    assert!(!option.ends_with(&["", "std", "option", "Option"]));
+
+    assert!(TypeNode::Path(option).is_option());
+    let core_option =
+        TypeNode::Path(Path::path_from_str("core::option::Option", &mut param_map).unwrap());
+    assert!(core_option.is_option());
+    let vec = TypeNode::Path(Path::path_from_str("Vec", &mut param_map).unwrap());
+    assert!(vec.is_vec());
+    assert!(!vec.is_option());
+    let other = TypeNode::Path(Path::path_from_str("shapes::Vec", &mut param_map).unwrap());
+    assert!(!other.is_vec());
+}
+
+#[test]
+fn test_trait_method_path() {
+    let mut param_map = SynParamMap::default();
+    let circle = Module::crate_root()
+        .get_module("shapes")
+        .get_path_type("Circle", &mut param_map)
+        .unwrap();
+    let default = Path::path_from_str("::core::default::Default", &mut param_map).unwrap();
+    let path = Path::for_trait_method(default, Ident::new("default"), circle);
+    assert_eq!(
+        path.to_string(),
+        "< crate :: shapes :: Circle as :: core :: default :: Default > :: default"
+    );
+    assert!(*path.last_ident().unwrap() == "default");
+}
+
+#[test]
+fn test_build_generic_arguments() {
+    let mut param_map = SynParamMap::default();
+    let u8 = Path::path_from_str("u8", &mut param_map).unwrap();
+    let mut args = GenericArguments::new();
+    args.push_binding(Ident::new("Item"), TypeNode::Path(u8));
+    let iter = Path::path_from_str("::std::iter", &mut param_map).unwrap();
+    let iterator = iter
+        .with_arguments("Iterator", args, &mut param_map)
+        .unwrap();
+    assert_eq!(
+        iterator,
+        Path::path_from_str("::std::iter::Iterator<Item = u8>", &mut param_map).unwrap()
+    );
+
+    let cow = Path::path_from_str("Cow::<'static, str>", &mut param_map).unwrap();
+    let lifetime = cow
+        .last_segment()
+        .unwrap()
+        .generic_args()
+        .unwrap()
+        .lifetimes()
+        .next();
+    let mut args = GenericArguments::new();
+    args.push_lifetime(lifetime.unwrap());
+    args.push_type(TypeNode::new_primitive_str());
+    let borrow = Path::path_from_str("::std::borrow", &mut param_map).unwrap();
+    let cow = borrow.with_arguments("Cow", args, &mut param_map).unwrap();
+    assert_eq!(cow.to_string(), ":: std :: borrow :: Cow < 'static , str >");
+}
+
+#[test]
+fn test_generic_arguments() {
+    let mut param_map = SynParamMap::default();
+    let cow = Path::path_from_str("::std::borrow::Cow::<'static, str>", &mut param_map).unwrap();
+    let args = cow.last_segment().unwrap().generic_args().unwrap();
+    assert_eq!(args.types().count(), 1);
+    assert_eq!(args.lifetimes().count(), 1);
+    assert_eq!(args.bindings().count(), 0);
+
+    let iterator = Path::path_from_str("Iterator::<Item = u8>", &mut param_map).unwrap();
+    let args = iterator.last_segment().unwrap().generic_args().unwrap();
+    let binding = args.bindings().next().unwrap();
+    assert!(*binding.ident() == "Item");
+    assert_eq!(binding.ty().to_string(), "u8");
+    assert_eq!(args.types().count(), 0);
+
+    let plain = Path::path_from_str("Vec", &mut param_map).unwrap();
+    assert!(plain.last_segment().unwrap().generic_args().is_none());
+}
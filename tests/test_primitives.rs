@@ -0,0 +1,61 @@
+use reflect::runtime::prelude::*;
+use reflect::runtime::std_types::{Box, Option, Result, Vec};
+use reflect::runtime::RuntimeType;
+use reflect::{Path, SynParamMap, TypeNode};
+
+#[test]
+fn test_primitive_types() {
+    let mut param_map = SynParamMap::default();
+    assert_eq!(
+        u8.SELF(),
+        Path::path_from_str("u8", &mut param_map).unwrap().SELF()
+    );
+    assert_eq!(
+        i128.SELF(),
+        Path::path_from_str("i128", &mut param_map).unwrap().SELF()
+    );
+    assert_eq!(
+        bool.SELF(),
+        Path::path_from_str("bool", &mut param_map).unwrap().SELF()
+    );
+    assert_eq!(
+        f64.SELF(),
+        Path::path_from_str("f64", &mut param_map).unwrap().SELF()
+    );
+    assert_eq!(
+        char.SELF(),
+        Path::path_from_str("char", &mut param_map).unwrap().SELF()
+    );
+}
+
+#[test]
+fn test_std_generic_types() {
+    let mut param_map = SynParamMap::default();
+    assert_eq!(
+        Option(u8).SELF(),
+        Path::path_from_str("::std::option::Option<u8>", &mut param_map)
+            .unwrap()
+            .SELF()
+    );
+    assert_eq!(
+        Vec(Box(u32)).SELF(),
+        Path::path_from_str("::std::vec::Vec<::std::boxed::Box<u32> >", &mut param_map)
+            .unwrap()
+            .SELF()
+    );
+    assert_eq!(
+        Result(bool, char).SELF(),
+        Path::path_from_str("::std::result::Result<bool, char>", &mut param_map)
+            .unwrap()
+            .SELF()
+    );
+}
+
+#[test]
+fn test_reference_types() {
+    assert_eq!(
+        Ref(str).SELF(),
+        TypeNode::new_primitive_str().new_reference()
+    );
+    assert_eq!(RefMut(Vec(u8)).SELF(), Vec(u8).SELF().new_reference_mut());
+}
@@ -0,0 +1,106 @@
+#![cfg(feature = "testing")]
+
+use quote::quote;
+use reflect::*;
+
+mod stash;
+use stash::{stash, take_stashed};
+
+fn derive(ex: Execution) {
+    match ex.target_type() {
+        TypeNode::DataStructure(data) => stash((
+            data.generics.type_params().next().unwrap(),
+            data.generics.lifetime_params().next().unwrap(),
+        )),
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn test_reset_global_state() {
+    for _ in 0..2 {
+        reflect::testing::reset_global_state();
+        let input = quote! {
+            struct Reset<'a, T>(&'a T);
+        };
+        let output = reflect::derive(input, derive);
+        assert!(output.is_empty());
+    }
+
+    // The params keep counting across the reset, so they can not collide
+    // with the params of signatures cached before it
+    let params = take_stashed::<(TypeParam, Lifetime)>();
+    assert_eq!(params[0], (TypeParam(0), Lifetime(1)));
+    assert_ne!(params[1].0, params[0].0);
+    assert_ne!(params[1].1, params[0].1);
+}
+
+#[test]
+fn test_assert_tokens_eq() {
+    let actual = quote! {
+        impl Unit { fn unit(&self) -> () { let __v0 = self; } }
+    };
+    reflect::testing::assert_tokens_eq(
+        actual.clone(),
+        "impl Unit {\n    fn unit(&self) -> () {\n        let __v0 = self;\n    }\n}",
+    );
+    reflect::testing::assert_tokens_eq_snapshot(actual, "assert_tokens");
+}
+
+#[test]
+#[should_panic(expected = "generated tokens differ from the expected tokens")]
+fn test_assert_tokens_ne() {
+    reflect::testing::assert_tokens_eq(quote!(let x = 1;), "let x = 2;");
+}
+
+#[test]
+fn test_type_round_trip() {
+    use reflect::testing::{round_trip_type, TypeGenerator};
+
+    let mut generator = TypeGenerator::new(0);
+    for _ in 0..500 {
+        let ty = generator.generate();
+        let round_trip = round_trip_type(ty.clone(), TypeGenerator::generics()).unwrap();
+        assert_eq!(quote!(#round_trip).to_string(), quote!(#ty).to_string(),);
+    }
+
+    // This seed used to give xorshift an all zero state
+    let mut generator = TypeGenerator::new(0x9e37_79b9_7f4a_7c15);
+    let types: Vec<_> = (0..20).map(|_| generator.generate()).collect();
+    assert!(types.iter().any(|ty| *ty != types[0]));
+
+    let slice: syn::Type = syn::parse_quote!(&'a [(T, &'a mut U)]);
+    let round_trip = round_trip_type(slice.clone(), TypeGenerator::generics()).unwrap();
+    assert_eq!(quote!(#round_trip).to_string(), quote!(#slice).to_string());
+
+    let array: syn::Type = syn::parse_quote!([T; 4]);
+    assert!(round_trip_type(array, TypeGenerator::generics()).is_err());
+}
+
+#[test]
+fn test_associated_type() {
+    use reflect::testing::{round_trip_type, TypeGenerator};
+
+    let std = Module::root().get_module("std");
+    let mut param_map = SynParamMap::default();
+    let string = std
+        .get_module("string")
+        .get_path_type("String", &mut param_map)
+        .unwrap();
+    let deref = std
+        .get_module("ops")
+        .get_path("Deref", &mut param_map)
+        .unwrap();
+    let target = TypeNode::new_associated(string, deref, Ident::new("Target"));
+    assert_eq!(
+        target.to_string(),
+        "< :: std :: string :: String as :: std :: ops :: Deref > :: Target"
+    );
+
+    let item: syn::Type = syn::parse_quote!(<&'a T as ::std::iter::IntoIterator>::Item);
+    let round_trip = round_trip_type(item.clone(), TypeGenerator::generics()).unwrap();
+    assert_eq!(quote!(#round_trip).to_string(), quote!(#item).to_string());
+
+    let no_trait: syn::Type = syn::parse_quote!(<T>::Item);
+    assert!(round_trip_type(no_trait, TypeGenerator::generics()).is_err());
+}
@@ -0,0 +1,60 @@
+use quote::quote;
+use reflect::*;
+
+library! {
+    use fallible {
+        type Point;
+        type Error;
+
+        trait Fallible {
+            fn point(&self) -> ::std::result::Result<Point, Error>;
+            fn check(&self) -> ::std::result::Result<(), Error>;
+        }
+    }
+}
+
+fn derive(ex: Execution) {
+    ex.make_trait_impl(RUNTIME::fallible::Fallible, ex.target_type(), |block| {
+        block.make_function(RUNTIME::fallible::Fallible::check, |make_function| {
+            let receiver = make_function.arg(0);
+            assert!(matches!(
+                receiver.try_propagate(),
+                Err(ReflectError::UnexpectedType { .. })
+            ));
+            let point = RUNTIME::fallible::Fallible::point
+                .INVOKE(receiver)
+                .try_propagate()
+                .unwrap();
+            assert_eq!(point.type_ident().unwrap(), Ident::new("Point"));
+            let path =
+                Path::path_from_str("::std::result::Result", &mut SynParamMap::default()).unwrap();
+            Value::construct_variant(
+                path,
+                Ident::new("Ok"),
+                VariantPayload::Tuple(vec![Value::unit()]),
+            )
+        });
+    });
+}
+
+#[test]
+fn test_try() {
+    let input = quote! {
+        struct Checked;
+    };
+
+    let expected = quote! {
+        impl ::fallible::Fallible for Checked {
+            fn check<'__a1>(&'__a1 self) -> ::std::result::Result<(), ::fallible::Error> {
+                let __v0 = self;
+                let __v1 = ::fallible::Fallible::point(__v0);
+                let _ = __v1?;
+                let __v4 = ::std::result::Result::Ok(());
+                __v4
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
@@ -8,6 +8,17 @@ library! {
             fn to_tuple(self) -> (T, U);
         }
     }
+    use single {
+        trait Single<T> {
+            fn wrap(T) -> (T,);
+            fn nothing(self);
+        }
+    }
+    use triple {
+        trait Triple<T: ::std::marker::Copy> {
+            fn triple(T) -> (T, T, T);
+        }
+    }
 }
 
 fn derive(ex: Execution) {
@@ -55,3 +66,71 @@ fn test_tuple() {
     let output = reflect::derive(input, derive);
     assert_eq!(output.to_string(), expected.to_string());
 }
+
+#[test]
+fn test_single_element_tuple() {
+    let input = quote! {
+        struct Wrap;
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::single::Single, ex.target_type(), |block| {
+            block.make_function(RUNTIME::single::Single::wrap, |make_function| {
+                Value::new_tuple(&[make_function.arg(0)])
+            });
+
+            block.make_function(RUNTIME::single::Single::nothing, |_make_function| {
+                Value::unit()
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl<__T0> ::single::Single<__T0> for Wrap {
+            fn wrap(__arg0: __T0) -> (__T0,) {
+                let __v0 = __arg0;
+                let __v1 = (__v0,);
+                __v1
+            }
+
+            fn nothing(self) {}
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_tuple_of_arity() {
+    let ty = TypeNode::new_tuple_of_arity(&TypeNode::new_primitive_str(), 3);
+    assert_eq!(ty.to_string(), "(str , str , str)");
+
+    let input = quote! {
+        struct Triple;
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::triple::Triple, ex.target_type(), |block| {
+            block.make_function(RUNTIME::triple::Triple::triple, |make_function| {
+                Value::new_tuple_of_arity(make_function.arg(0), 3)
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl<__T0> ::triple::Triple<__T0> for Triple
+        where
+            __T0: ::std::marker::Copy,
+        {
+            fn triple(__arg0: __T0) -> (__T0, __T0, __T0) {
+                let __v0 = __arg0;
+                let __v1 = (__v0, __v0, __v0);
+                __v1
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
@@ -0,0 +1,102 @@
+use quote::quote;
+use reflect::*;
+
+#[derive(Default)]
+struct Collect {
+    type_params: Vec<TypeParam>,
+    lifetimes: Vec<Lifetime>,
+    paths: usize,
+}
+
+impl TypeVisitor for Collect {
+    fn visit_type_param(&mut self, type_param: TypeParam) {
+        self.type_params.push(type_param);
+    }
+
+    fn visit_lifetime(&mut self, lifetime: Lifetime) {
+        self.lifetimes.push(lifetime);
+    }
+
+    fn visit_path(&mut self, _path: &Path) {
+        self.paths += 1;
+    }
+}
+
+#[test]
+fn test_walk_type_node() {
+    let input = quote! {
+        struct Visit<'a, T, U> {
+            a: &'a T,
+            b: ::std::vec::Vec<(U, &'static str)>,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        let mut collect = Collect::default();
+        walk_type_node(&mut collect, &ex.target_type());
+        assert_eq!(collect.type_params, [TypeParam(0), TypeParam(1)]);
+        assert_eq!(collect.lifetimes, [Lifetime(1), Lifetime(0)]);
+        assert_eq!(collect.paths, 2);
+    }
+
+    let output = reflect::derive(input, derive);
+    assert!(output.is_empty());
+}
+
+/// Replaces every type param with `()`
+struct EraseTypeParams;
+
+impl TypeFolder for EraseTypeParams {
+    fn fold_type(&mut self, node: &TypeNode) -> TypeNode {
+        match node {
+            TypeNode::TypeParam(_) => TypeNode::new_unit(),
+            other => fold_type(self, other),
+        }
+    }
+}
+
+/// Reverses the type arguments of every path, keeping its lifetimes
+struct ReverseTypeArguments;
+
+impl TypeFolder for ReverseTypeArguments {
+    fn fold_generic_arguments(&mut self, args: &GenericArguments) -> GenericArguments {
+        let args = fold_generic_arguments(self, args);
+        let mut reversed = GenericArguments::new();
+        for lifetime in args.lifetimes() {
+            reversed.push_lifetime(lifetime);
+        }
+        let types: Vec<_> = args.types().cloned().collect();
+        for ty in types.into_iter().rev() {
+            reversed.push_type(ty);
+        }
+        reversed
+    }
+}
+
+#[test]
+fn test_fold_generic_arguments() {
+    let mut param_map = SynParamMap::default();
+    let path = Path::path_from_str("HashMap::<u8, Result::<str, ()>>", &mut param_map).unwrap();
+    let reversed = ReverseTypeArguments.fold_path(&path);
+    assert_eq!(
+        reversed,
+        Path::path_from_str("HashMap::<Result::<(), str>, u8>", &mut param_map).unwrap()
+    );
+}
+
+#[test]
+fn test_type_folder() {
+    let input = quote! {
+        struct Fold<'a, T>(&'a ::std::vec::Vec<(T, u8)>);
+    };
+
+    fn derive(ex: Execution) {
+        let field = ex.target_type().index(0);
+        assert_eq!(DefaultTypeFolder.fold_type(&field), field);
+        let erased = EraseTypeParams.fold_type(&field).to_string();
+        assert_eq!(erased, quote!(&'__a1 ::std::vec::Vec<((), u8)>).to_string());
+    }
+
+    let output = reflect::derive(input, derive);
+    assert!(output.is_empty());
+}